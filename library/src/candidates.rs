@@ -0,0 +1,127 @@
+use crate::negotiation::Negotiation;
+use crate::NetworkError;
+use log::debug;
+use rusty_games_protocol::rtc::IceCandidate;
+
+/// ICE candidates received from a single peer, held back until its remote description is set,
+/// as adding a candidate before that fails. Signaling messages are handled concurrently,
+/// so candidates can overtake the offer or answer they were gathered for.
+#[derive(Debug, Default)]
+pub(crate) struct PendingCandidates {
+    remote_description_set: bool,
+    candidates: Vec<IceCandidate>,
+}
+
+impl PendingCandidates {
+    /// Queues the candidate, unless it can be added right away, in which case it's returned
+    pub(crate) fn push(&mut self, candidate: IceCandidate) -> Option<IceCandidate> {
+        if self.remote_description_set {
+            Some(candidate)
+        } else {
+            debug!(
+                "remote description not set yet, queueing candidate: {:?}",
+                candidate
+            );
+            self.candidates.push(candidate);
+            None
+        }
+    }
+
+    /// Takes the oldest queued candidate, once there are none left
+    /// the remote description is considered set and candidates are no longer queued.
+    /// Candidates arriving while the queue is being drained are queued behind the rest,
+    /// so that they are added in order and end-of-candidates always comes last.
+    pub(crate) fn pop(&mut self) -> Option<IceCandidate> {
+        if self.candidates.is_empty() {
+            self.remote_description_set = true;
+            None
+        } else {
+            Some(self.candidates.remove(0))
+        }
+    }
+}
+
+/// Adds candidates queued before the remote description was set,
+/// `pop` must take the next candidate from [PendingCandidates] of the same peer
+pub(crate) async fn add_pending_candidates(
+    negotiation: &Negotiation,
+    mut pop: impl FnMut() -> Option<IceCandidate>,
+) -> Result<(), NetworkError> {
+    while let Some(candidate) = pop() {
+        debug!("adding queued ice candidate {:?}", candidate);
+        negotiation.add_ice_candidate(candidate).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn candidate(port: u16) -> IceCandidate {
+        IceCandidate {
+            candidate: format!("candidate:1 1 udp 2122260223 192.168.1.2 {} typ host", port),
+            sdp_mid: Some("0".to_string()),
+            sdp_m_line_index: Some(0),
+        }
+    }
+
+    #[test]
+    fn candidates_are_queued_until_remote_description_is_set() {
+        let mut pending = PendingCandidates::default();
+        assert_eq!(pending.push(candidate(1)), None);
+        assert_eq!(pending.push(candidate(2)), None);
+
+        assert_eq!(pending.pop(), Some(candidate(1)));
+        // arrived while the first queued candidate was being added
+        assert_eq!(pending.push(IceCandidate::end_of_candidates()), None);
+        assert_eq!(pending.pop(), Some(candidate(2)));
+        assert_eq!(pending.pop(), Some(IceCandidate::end_of_candidates()));
+        assert_eq!(pending.pop(), None);
+
+        assert_eq!(pending.push(candidate(3)), Some(candidate(3)));
+        assert_eq!(pending.pop(), None);
+    }
+
+    #[test]
+    fn candidates_pass_through_once_nothing_was_queued() {
+        let mut pending = PendingCandidates::default();
+        assert_eq!(pending.pop(), None);
+        assert_eq!(pending.push(candidate(1)), Some(candidate(1)));
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn candidates_arriving_before_offer_are_added_after_it() {
+        use crate::platform::PeerConnection;
+        use crate::{ChannelConfig, ConnectionType};
+        use tokio::task::LocalSet;
+
+        LocalSet::new()
+            .run_until(async {
+                let offerer = PeerConnection::new(&ConnectionType::Local).await.unwrap();
+                let answerer = Negotiation::new(
+                    PeerConnection::new(&ConnectionType::Local).await.unwrap(),
+                    true,
+                    Default::default(),
+                );
+                offerer
+                    .create_data_channel("test", &ChannelConfig::default())
+                    .await
+                    .unwrap();
+                let offer = offerer.create_offer().await.unwrap();
+
+                let mut pending = PendingCandidates::default();
+                assert!(answerer.add_ice_candidate(candidate(1)).await.is_err());
+                assert_eq!(pending.push(candidate(1)), None);
+                assert_eq!(pending.push(IceCandidate::end_of_candidates()), None);
+
+                answerer.accept_offer(offer).await.unwrap();
+                add_pending_candidates(&answerer, || pending.pop())
+                    .await
+                    .unwrap();
+                assert_eq!(pending.push(candidate(2)), Some(candidate(2)));
+            })
+            .await;
+    }
+}
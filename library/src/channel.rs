@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// How hard a data channel tries to deliver messages that were lost on the way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reliability {
+    /// Lost messages are retransmitted until they arrive
+    #[default]
+    Reliable,
+    /// Lost messages are retransmitted at most this many times
+    MaxRetransmits(u16),
+    /// Lost messages are retransmitted for at most this many milliseconds
+    MaxPacketLifeTime(u16),
+}
+
+/// Parameters of an additional data channel opened with `open_channel` of network managers,
+/// the default is the same reliable, ordered delivery as that of the channel opened on connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelConfig {
+    /// Whether messages are delivered in the order they were sent
+    pub ordered: bool,
+    /// Whether lost messages are retransmitted
+    pub reliability: Reliability,
+}
+
+impl ChannelConfig {
+    /// Unordered channel that never retransmits,
+    /// for frequent updates where only the latest one matters
+    pub fn unreliable() -> Self {
+        ChannelConfig {
+            ordered: false,
+            reliability: Reliability::MaxRetransmits(0),
+        }
+    }
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        ChannelConfig {
+            ordered: true,
+            reliability: Reliability::Reliable,
+        }
+    }
+}
+
+/// Callback stored by a network manager to be used by data channels opened later on,
+/// e.g. `on_message_callback` passed to `start` or the one set with `on_channel_open`
+pub(crate) struct SharedCallback<A>(Rc<RefCell<dyn FnMut(A)>>);
+
+impl<A> SharedCallback<A> {
+    pub(crate) fn new(callback: impl FnMut(A) + 'static) -> Self {
+        SharedCallback(Rc::new(RefCell::new(callback)))
+    }
+
+    pub(crate) fn call(&self, argument: A) {
+        (self.0.borrow_mut())(argument);
+    }
+}
+
+impl<A> Clone for SharedCallback<A> {
+    fn clone(&self) -> Self {
+        SharedCallback(self.0.clone())
+    }
+}
+
+impl<A> fmt::Debug for SharedCallback<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedCallback")
+    }
+}
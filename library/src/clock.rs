@@ -0,0 +1,120 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Offset is taken from the ping with the shortest round trip among this many latest ones,
+/// as it was held up the least on its way
+const CLOCK_WINDOW: usize = 8;
+
+/// Estimate of the clock peers of a session synchronize to, relative to the local clock.
+/// Clocks of peers are monotonic and start at an arbitrary point, so only differences
+/// between times of the same clock are meaningful, e.g. when a scheduled event is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClockOffset {
+    /// Reference clock minus the local one, in microseconds
+    pub offset_micros: i64,
+    /// How far off the offset may be, half the round-trip time of the ping it was measured with,
+    /// zero if the local clock is the reference
+    pub uncertainty: Duration,
+}
+
+impl ClockOffset {
+    /// Local time converted to the reference clock
+    pub(crate) fn to_remote(self, local_time: Duration) -> Duration {
+        let micros = local_time.as_micros() as i64 + self.offset_micros;
+        Duration::from_micros(micros.max(0) as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    offset_micros: i64,
+    round_trip_time: Duration,
+}
+
+/// NTP-style estimate of the offset of the clock of a single peer, refined with every pong
+#[derive(Debug, Default)]
+pub(crate) struct ClockSync {
+    samples: VecDeque<Sample>,
+}
+
+impl ClockSync {
+    /// Records a pong, the peer is assumed to read its clock
+    /// halfway between the ping being sent and the pong being received
+    pub(crate) fn add(&mut self, sent_at: Duration, remote_time: Duration, received_at: Duration) {
+        let round_trip_time = received_at.saturating_sub(sent_at);
+        let midpoint = sent_at + round_trip_time / 2;
+        if self.samples.len() == CLOCK_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            offset_micros: remote_time.as_micros() as i64 - midpoint.as_micros() as i64,
+            round_trip_time,
+        });
+    }
+
+    /// `None` until the first pong arrives
+    pub(crate) fn offset(&self) -> Option<ClockOffset> {
+        self.samples
+            .iter()
+            .min_by_key(|sample| sample.round_trip_time)
+            .map(|sample| ClockOffset {
+                offset_micros: sample.offset_micros,
+                uncertainty: sample.round_trip_time / 2,
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(milliseconds: u64) -> Duration {
+        Duration::from_millis(milliseconds)
+    }
+
+    #[test]
+    fn offset_assumes_symmetric_delay() {
+        let mut clock = ClockSync::default();
+        assert_eq!(clock.offset(), None);
+        // remote clock is 5 s ahead, 20 ms each way
+        clock.add(ms(1000), ms(6020), ms(1040));
+        let offset = clock.offset().unwrap();
+        assert_eq!(offset.offset_micros, 5_000_000);
+        assert_eq!(offset.uncertainty, ms(20));
+        assert_eq!(offset.to_remote(ms(2000)), ms(7000));
+    }
+
+    #[test]
+    fn ping_with_shortest_round_trip_wins() {
+        let mut clock = ClockSync::default();
+        // delayed on the way back, which skews the offset
+        clock.add(ms(0), ms(10), ms(300));
+        clock.add(ms(1000), ms(1005), ms(1010));
+        clock.add(ms(2000), ms(2050), ms(2100));
+        let offset = clock.offset().unwrap();
+        assert_eq!(offset.offset_micros, 0);
+        assert_eq!(offset.uncertainty, ms(5));
+    }
+
+    #[test]
+    fn old_samples_are_forgotten() {
+        let mut clock = ClockSync::default();
+        clock.add(ms(0), ms(1), ms(2));
+        for i in 1..=CLOCK_WINDOW as u64 {
+            let sent_at = ms(i * 1000);
+            clock.add(sent_at, sent_at - ms(100), sent_at + ms(50));
+        }
+        let offset = clock.offset().unwrap();
+        assert_eq!(offset.offset_micros, -125_000);
+        assert_eq!(offset.uncertainty, ms(25));
+    }
+
+    #[test]
+    fn remote_time_never_goes_below_zero() {
+        let offset = ClockOffset {
+            offset_micros: -5_000_000,
+            uncertainty: Duration::ZERO,
+        };
+        assert_eq!(offset.to_remote(ms(1000)), Duration::ZERO);
+    }
+}
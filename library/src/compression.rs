@@ -0,0 +1,179 @@
+use crate::fragmentation::MAX_MESSAGE_LENGTH;
+use crate::framing::{Frame, FrameError, FLAG_COMPRESSED};
+use std::cell::Cell;
+
+/// Session description attribute by which a peer states it wants messages compressed.
+/// It's added to descriptions sent over signaling and removed from received ones
+/// before they reach WebRTC.
+const SDP_ATTRIBUTE: &str = "a=x-rusty-games-compression:deflate";
+
+/// Deflate level, a balance between speed and size that suits JSON well
+const LEVEL: u8 = 6;
+
+/// How well messages sent by a network manager compressed so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompressionStats {
+    /// Number of messages sent compressed
+    pub compressed_messages: u64,
+    /// Total length of those messages before compression
+    pub uncompressed_bytes: u64,
+    /// Total length of those messages after compression
+    pub compressed_bytes: u64,
+}
+
+impl CompressionStats {
+    /// How many times smaller compressed messages got, `1.0` if none were compressed
+    pub fn ratio(&self) -> f64 {
+        if self.compressed_bytes == 0 {
+            return 1.0;
+        }
+        self.uncompressed_bytes as f64 / self.compressed_bytes as f64
+    }
+}
+
+/// Compression settings of a network manager, shared with negotiations of all its connections
+#[derive(Debug, Default)]
+pub(crate) struct Compression {
+    /// Messages longer than this are compressed, `None` if compression is disabled
+    threshold: Cell<Option<usize>>,
+    stats: Cell<CompressionStats>,
+}
+
+impl Compression {
+    pub(crate) fn set_threshold(&self, threshold: Option<usize>) {
+        self.threshold.set(threshold);
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.threshold.get().is_some()
+    }
+
+    pub(crate) fn stats(&self) -> CompressionStats {
+        self.stats.get()
+    }
+
+    /// Compresses the payload of the frame if it's longer than the threshold
+    /// and compression makes it shorter
+    pub(crate) fn compress(&self, frame: Frame) -> Frame {
+        match self.threshold.get() {
+            Some(threshold) if frame.payload.len() > threshold => {}
+            _ => return frame,
+        }
+        let compressed = miniz_oxide::deflate::compress_to_vec(&frame.payload, LEVEL);
+        if compressed.len() >= frame.payload.len() {
+            return frame;
+        }
+        let mut stats = self.stats.get();
+        stats.compressed_messages += 1;
+        stats.uncompressed_bytes += frame.payload.len() as u64;
+        stats.compressed_bytes += compressed.len() as u64;
+        self.stats.set(stats);
+        Frame {
+            flags: frame.flags | FLAG_COMPRESSED,
+            payload: compressed,
+            ..frame
+        }
+    }
+}
+
+/// Restores the payload of a frame sent compressed, other frames are returned as they are
+pub(crate) fn decompress(frame: Frame) -> Result<Frame, FrameError> {
+    if frame.flags & FLAG_COMPRESSED == 0 {
+        return Ok(frame);
+    }
+    let payload =
+        miniz_oxide::inflate::decompress_to_vec_with_limit(&frame.payload, MAX_MESSAGE_LENGTH)
+            .map_err(|_| FrameError::InvalidCompression)?;
+    Ok(Frame {
+        flags: frame.flags & !FLAG_COMPRESSED,
+        payload,
+        ..frame
+    })
+}
+
+/// Adds the compression attribute to a session description about to be sent to the peer
+pub(crate) fn advertise(sdp: &mut String) {
+    if !sdp.ends_with('\n') {
+        sdp.push_str("\r\n");
+    }
+    sdp.push_str(SDP_ATTRIBUTE);
+    sdp.push_str("\r\n");
+}
+
+/// Removes the compression attribute from a session description received from the peer,
+/// returns whether it was there
+pub(crate) fn take_advertisement(sdp: &mut String) -> bool {
+    let advertised = sdp.lines().any(|line| line.trim() == SDP_ATTRIBUTE);
+    if advertised {
+        *sdp = sdp
+            .lines()
+            .filter(|line| line.trim() != SDP_ATTRIBUTE)
+            .map(|line| format!("{}\r\n", line))
+            .collect();
+    }
+    advertised
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot() -> String {
+        let players: Vec<String> = (0..100)
+            .map(|i| format!(r#"{{"id":{},"x":{},"y":{},"alive":true}}"#, i, i * 3, i * 7))
+            .collect();
+        format!(r#"{{"players":[{}]}}"#, players.join(","))
+    }
+
+    #[test]
+    fn messages_above_threshold_survive_compression() {
+        let compression = Compression::default();
+        compression.set_threshold(Some(100));
+        let frame = Frame::text(&snapshot());
+        let compressed = compression.compress(frame.clone());
+        assert_ne!(compressed.flags & FLAG_COMPRESSED, 0);
+        assert!(compressed.payload.len() * 5 < frame.payload.len());
+        assert_eq!(decompress(compressed), Ok(frame));
+
+        let stats = compression.stats();
+        assert_eq!(stats.compressed_messages, 1);
+        assert!(stats.ratio() > 5.0);
+    }
+
+    #[test]
+    fn short_or_incompressible_messages_are_sent_as_they_are() {
+        let compression = Compression::default();
+        let frame = Frame::text(&snapshot());
+        assert_eq!(compression.compress(frame.clone()), frame);
+
+        compression.set_threshold(Some(10));
+        let short = Frame::text("short");
+        assert_eq!(compression.compress(short.clone()), short);
+        let noise: String = (0u32..64)
+            .map(|i| char::from(b'!' + (i.wrapping_mul(2_654_435_761) >> 26) as u8))
+            .collect();
+        let incompressible = Frame::text(&noise);
+        assert_eq!(compression.compress(incompressible.clone()), incompressible);
+        assert_eq!(compression.stats(), CompressionStats::default());
+        assert_eq!(compression.stats().ratio(), 1.0);
+    }
+
+    #[test]
+    fn corrupted_payload_is_rejected() {
+        let frame = Frame {
+            flags: FLAG_COMPRESSED,
+            ..Frame::text("not deflate at all")
+        };
+        assert_eq!(decompress(frame), Err(FrameError::InvalidCompression));
+    }
+
+    #[test]
+    fn advertisement_is_removed_from_received_description() {
+        let mut sdp = "v=0\r\na=sctp-port:5000\r\n".to_string();
+        assert!(!take_advertisement(&mut sdp.clone()));
+        advertise(&mut sdp);
+        assert!(sdp.ends_with("a=x-rusty-games-compression:deflate\r\n"));
+        assert!(take_advertisement(&mut sdp));
+        assert_eq!(sdp, "v=0\r\na=sctp-port:5000\r\n");
+    }
+}
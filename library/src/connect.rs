@@ -0,0 +1,58 @@
+use crate::platform::sleep;
+use crate::NetworkError;
+use futures::channel::oneshot;
+use futures::future::{select, Either};
+use rusty_games_protocol::UserId;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Returned by `connect` methods of network managers once the first data channel opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connected {
+    /// Peer on the other end of the data channel that opened
+    pub peer_id: UserId,
+}
+
+type ConnectResult = Result<Connected, NetworkError>;
+
+/// Pending `connect` call, completed with the first outcome reported by any of the callbacks.
+/// Outcomes reported when nobody is waiting are ignored.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConnectWaiter {
+    sender: Rc<RefCell<Option<oneshot::Sender<ConnectResult>>>>,
+}
+
+impl ConnectWaiter {
+    /// Waits for an outcome to be reported, failing with [NetworkError::Timeout]
+    /// if none is reported in time
+    pub(crate) async fn wait(
+        &self,
+        timeout: Duration,
+        start: impl FnOnce() -> Result<(), NetworkError>,
+    ) -> ConnectResult {
+        let (sender, receiver) = oneshot::channel();
+        *self.sender.borrow_mut() = Some(sender);
+        if let Err(error) = start() {
+            self.sender.borrow_mut().take();
+            return Err(error);
+        }
+
+        let timeout_future = Box::pin(sleep(timeout));
+        match select(receiver, timeout_future).await {
+            Either::Left((Ok(result), _)) => result,
+            // sender was replaced by another connect call
+            Either::Left((Err(_), _)) => Err(NetworkError::Timeout(timeout)),
+            Either::Right(_) => {
+                self.sender.borrow_mut().take();
+                Err(NetworkError::Timeout(timeout))
+            }
+        }
+    }
+
+    pub(crate) fn resolve(&self, result: ConnectResult) {
+        if let Some(sender) = self.sender.borrow_mut().take() {
+            let _ = sender.send(result);
+        }
+    }
+}
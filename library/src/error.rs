@@ -0,0 +1,162 @@
+use crate::{FrameError, PlatformError};
+use rusty_games_protocol::signal::{ErrorCode, VersionRange, PROTOCOL_VERSION};
+use rusty_games_protocol::{SessionId, UserId};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// Error reported by the signaling server with [SignalMessage::Error](rusty_games_protocol::signal::SignalMessage::Error)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignalingError {
+    /// Signaling server does not support the protocol version spoken by this library,
+    /// either the library or the server has to be updated
+    UnsupportedVersion {
+        /// Version spoken by this library
+        version: u32,
+        /// Versions supported by the signaling server
+        supported: VersionRange,
+    },
+    /// Any other error returned by the signaling server
+    Server {
+        /// Session the error relates to, if any
+        session_id: Option<SessionId>,
+        /// Category of the error
+        code: ErrorCode,
+        /// Detailed description of the cause
+        description: String,
+    },
+}
+
+impl SignalingError {
+    pub(crate) fn new(session_id: Option<SessionId>, code: ErrorCode, description: String) -> Self {
+        match code {
+            ErrorCode::UnsupportedVersion { supported } => SignalingError::UnsupportedVersion {
+                version: PROTOCOL_VERSION,
+                supported,
+            },
+            code => SignalingError::Server {
+                session_id,
+                code,
+                description,
+            },
+        }
+    }
+}
+
+impl Display for SignalingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignalingError::UnsupportedVersion { version, supported } => write!(
+                f,
+                "signaling server supports protocol versions {}, but library speaks version {}",
+                supported, version
+            ),
+            SignalingError::Server {
+                session_id,
+                code,
+                description,
+            } => write!(
+                f,
+                "signaling server returned error {:?} (session id: {:?}): {}",
+                code, session_id, description
+            ),
+        }
+    }
+}
+
+impl Error for SignalingError {}
+
+/// Error returned by network managers, or reported while they connect
+#[derive(Debug, Clone)]
+pub enum NetworkError {
+    /// No data channel opened within the given time
+    Timeout(Duration),
+    /// There is no connection with the given peer, e.g. it never joined the session
+    NoConnectionForUser(UserId),
+    /// Data channel with the peer is not open yet, or was already closed
+    DataChannelNotOpen,
+    /// More data than the high-water mark is waiting to be sent over the data channel,
+    /// the message can be sent once it drains, or with one of the `async` send methods
+    WouldBlock,
+    /// No channel with the given name was opened with the peer
+    UnknownChannel(String),
+    /// Signaling server rejected the connection or reported an error
+    Signaling(SignalingError),
+    /// Connection with the signaling server is closed
+    SignalingClosed,
+    /// Message for the signaling server could not be encoded
+    Serialization(String),
+    /// ICE failed to find a working pair of candidates to connect with the peer
+    IceFailed(UserId),
+    /// Message is longer than the library is willing to send, even in fragments
+    MessageTooLarge(usize),
+    /// Peer sent a message that doesn't follow the [framing format](crate#message-framing)
+    MalformedFrame(FrameError),
+    /// WebRTC or websocket implementation of the platform failed,
+    /// on the web this holds the `JsValue` thrown by the browser
+    Platform(PlatformError),
+}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkError::Timeout(timeout) => {
+                write!(f, "connection was not established within {:?}", timeout)
+            }
+            NetworkError::NoConnectionForUser(user_id) => {
+                write!(f, "there is no connection with peer {}", user_id)
+            }
+            NetworkError::DataChannelNotOpen => write!(f, "data channel is not open"),
+            NetworkError::WouldBlock => {
+                write!(
+                    f,
+                    "too much data is waiting to be sent over the data channel"
+                )
+            }
+            NetworkError::UnknownChannel(name) => write!(f, "there is no channel named {}", name),
+            NetworkError::Signaling(error) => write!(f, "{}", error),
+            NetworkError::SignalingClosed => {
+                write!(f, "connection with signaling server was closed")
+            }
+            NetworkError::Serialization(error) => {
+                write!(f, "failed to serialize signal message: {}", error)
+            }
+            NetworkError::IceFailed(user_id) => {
+                write!(f, "ICE failed to connect with peer {}", user_id)
+            }
+            NetworkError::MessageTooLarge(length) => {
+                write!(f, "message of {} bytes is too large to send", length)
+            }
+            NetworkError::MalformedFrame(error) => write!(f, "malformed message: {}", error),
+            NetworkError::Platform(error) => write!(f, "platform error: {:?}", error),
+        }
+    }
+}
+
+impl Error for NetworkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NetworkError::Signaling(error) => Some(error),
+            NetworkError::MalformedFrame(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<SignalingError> for NetworkError {
+    fn from(error: SignalingError) -> Self {
+        NetworkError::Signaling(error)
+    }
+}
+
+impl From<FrameError> for NetworkError {
+    fn from(error: FrameError) -> Self {
+        NetworkError::MalformedFrame(error)
+    }
+}
+
+impl From<PlatformError> for NetworkError {
+    fn from(error: PlatformError) -> Self {
+        NetworkError::Platform(error)
+    }
+}
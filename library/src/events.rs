@@ -0,0 +1,193 @@
+use crate::NetworkError;
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::Stream;
+use log::debug;
+use rusty_games_protocol::UserId;
+use std::collections::{vec_deque, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Content of a message received from a peer
+pub type Payload = String;
+
+/// Something that happened on the network
+#[derive(Debug, Clone)]
+pub enum NetworkEvent {
+    /// Data channel with the peer opened and messages can be sent to it
+    PeerConnected(UserId),
+    /// Peer has sent a message
+    Message(UserId, Payload),
+    /// Additional channel with the peer, opened by either side with `open_channel`, is now open
+    ChannelOpened(UserId, String),
+    /// Data channel with the peer closed
+    PeerDisconnected(UserId),
+    /// Signaling or connecting with one of the peers failed
+    Error(NetworkError),
+}
+
+/// [Stream] of [NetworkEvent]s of a single network manager,
+/// an alternative to callbacks passed to `start` methods of network managers.
+/// It doesn't end until the network manager is closed, as new peers can join the session at any time.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use rusty_games_library::one_to_many::MiniServer;
+/// use rusty_games_library::{ConnectionType, NetworkEvent, SessionId};
+///
+/// # async fn run() {
+/// let mut server = MiniServer::new(
+///     "ws://0.0.0.0:9001/signal",
+///     SessionId::new("dummy-session-id".to_string()),
+///     ConnectionType::Local,
+/// )
+/// .unwrap();
+/// let mut events = server.start_with_events().unwrap();
+/// while let Some(event) = events.next().await {
+///     match event {
+///         NetworkEvent::PeerConnected(user_id) => server.send_message(user_id, "ping!").unwrap(),
+///         NetworkEvent::Message(user_id, message) => println!("{}: {}", user_id, message),
+///         NetworkEvent::ChannelOpened(user_id, name) => println!("{} opened {}", user_id, name),
+///         NetworkEvent::PeerDisconnected(user_id) => println!("{} left", user_id),
+///         NetworkEvent::Error(error) => println!("{}", error),
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct NetworkEvents {
+    receiver: UnboundedReceiver<NetworkEvent>,
+}
+
+impl NetworkEvents {
+    pub(crate) fn channel() -> (UnboundedSender<NetworkEvent>, Self) {
+        let (sender, receiver) = mpsc::unbounded();
+        (sender, NetworkEvents { receiver })
+    }
+}
+
+impl Stream for NetworkEvents {
+    type Item = NetworkEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+/// Decides which events are lost once the queue drained with `poll_events` is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Make room for the new event by dropping the oldest queued one
+    #[default]
+    DropOldest,
+    /// Keep the queued events and drop the new one
+    DropNewest,
+}
+
+/// Bounded queue of events waiting to be drained once per frame of a game loop
+#[derive(Debug, Clone)]
+pub(crate) struct EventQueue {
+    events: VecDeque<NetworkEvent>,
+    capacity: usize,
+    drop_policy: DropPolicy,
+}
+
+impl EventQueue {
+    pub(crate) fn new(capacity: usize, drop_policy: DropPolicy) -> Self {
+        EventQueue {
+            events: VecDeque::new(),
+            capacity,
+            drop_policy,
+        }
+    }
+
+    pub(crate) fn push(&mut self, event: NetworkEvent) {
+        if self.events.len() >= self.capacity {
+            match self.drop_policy {
+                DropPolicy::DropOldest => {
+                    let dropped = self.events.pop_front();
+                    debug!("event queue is full, dropped event: {:?}", dropped);
+                }
+                DropPolicy::DropNewest => {
+                    debug!("event queue is full, dropped event: {:?}", event);
+                    return;
+                }
+            }
+        }
+        if self.capacity > 0 {
+            self.events.push_back(event);
+        }
+    }
+
+    pub(crate) fn drain(&mut self) -> vec_deque::IntoIter<NetworkEvent> {
+        std::mem::take(&mut self.events).into_iter()
+    }
+}
+
+/// Where network managers deliver [NetworkEvent]s, depending on how they were started
+#[derive(Debug, Clone)]
+pub(crate) enum EventSink {
+    Stream(UnboundedSender<NetworkEvent>),
+    Queue(EventQueue),
+}
+
+impl EventSink {
+    pub(crate) fn send(&mut self, event: NetworkEvent) {
+        match self {
+            EventSink::Stream(sender) => {
+                let _ = sender.unbounded_send(event);
+            }
+            EventSink::Queue(queue) => queue.push(event),
+        }
+    }
+
+    /// Takes all queued events, there are none if events are delivered as a stream
+    pub(crate) fn drain(&mut self) -> vec_deque::IntoIter<NetworkEvent> {
+        match self {
+            EventSink::Stream(_) => VecDeque::new().into_iter(),
+            EventSink::Queue(queue) => queue.drain(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(number: u128) -> NetworkEvent {
+        NetworkEvent::Message(UserId::new(number), number.to_string())
+    }
+
+    fn drained_messages(queue: &mut EventQueue) -> Vec<String> {
+        queue
+            .drain()
+            .map(|event| match event {
+                NetworkEvent::Message(_, message) => message,
+                event => panic!("unexpected event: {:?}", event),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn full_queue_drops_oldest_events() {
+        let mut queue = EventQueue::new(2, DropPolicy::DropOldest);
+        (1..=3).for_each(|number| queue.push(message(number)));
+        assert_eq!(drained_messages(&mut queue), vec!["2", "3"]);
+        assert!(drained_messages(&mut queue).is_empty());
+    }
+
+    #[test]
+    fn full_queue_drops_newest_events() {
+        let mut queue = EventQueue::new(2, DropPolicy::DropNewest);
+        (1..=3).for_each(|number| queue.push(message(number)));
+        assert_eq!(drained_messages(&mut queue), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn zero_capacity_queue_drops_everything() {
+        let mut queue = EventQueue::new(0, DropPolicy::DropOldest);
+        queue.push(message(1));
+        assert!(drained_messages(&mut queue).is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::compression::{self, Compression};
+use crate::framing::{FragmentHeader, Frame, FrameError, FrameKind, Pong, MAX_HEADER_LENGTH};
+use crate::NetworkError;
+use log::debug;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+/// Maximum message size of a peer whose session description doesn't state one,
+/// and the size of fragments sent to peers accepting larger messages
+pub(crate) const DEFAULT_MAX_MESSAGE_SIZE: usize = 65536;
+
+/// Longest message that is sent or reassembled, which bounds memory used by a single message
+pub(crate) const MAX_MESSAGE_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Messages whose fragments were lost on an unreliable channel are dropped
+/// once this many newer ones are being reassembled
+const MAX_PENDING_MESSAGES: usize = 16;
+
+thread_local! {
+    static NEXT_MESSAGE_ID: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Maximum message size stated by `a=max-message-size` of a session description,
+/// `usize::MAX` if the peer accepts messages of any size
+pub(crate) fn max_message_size(sdp: &str) -> usize {
+    sdp.lines()
+        .find_map(|line| line.trim().strip_prefix("a=max-message-size:"))
+        .and_then(|size| size.trim().parse::<usize>().ok())
+        .map_or(DEFAULT_MAX_MESSAGE_SIZE, |size| match size {
+            0 => usize::MAX,
+            size => size,
+        })
+}
+
+/// Encodes the text or binary frame as is, or as fragments if it's larger than
+/// `max_message_size` (or the default, if the peer's one is not known yet).
+/// The frame is compressed first if `compression` was negotiated with the peer.
+pub(crate) fn encode(
+    mut frame: Frame,
+    max_message_size: Option<usize>,
+    compression: Option<&Compression>,
+) -> Result<Vec<Vec<u8>>, NetworkError> {
+    if frame.payload.len() > MAX_MESSAGE_LENGTH {
+        return Err(NetworkError::MessageTooLarge(frame.payload.len()));
+    }
+    let max_message_size = max_message_size
+        .unwrap_or(DEFAULT_MAX_MESSAGE_SIZE)
+        .min(DEFAULT_MAX_MESSAGE_SIZE);
+    if let Some(compression) = compression {
+        frame = compression.compress(frame);
+    }
+    Ok(split(frame, max_message_size)
+        .iter()
+        .map(Frame::encode)
+        .collect())
+}
+
+/// Splits the frame into fragments whose encoded length doesn't exceed `max_message_size`
+fn split(frame: Frame, max_message_size: usize) -> Vec<Frame> {
+    if frame.encoded_length() <= max_message_size {
+        return vec![frame];
+    }
+    let fragment_length = max_message_size.saturating_sub(MAX_HEADER_LENGTH).max(1);
+    let count = frame.payload.len().div_ceil(fragment_length) as u32;
+    let message_id = NEXT_MESSAGE_ID.with(|next| {
+        let message_id = next.get();
+        next.set(message_id.wrapping_add(1));
+        message_id
+    });
+    let length = frame.payload.len() as u32;
+    let checksum = crc32(&frame.payload);
+    frame
+        .payload
+        .chunks(fragment_length)
+        .zip(0..)
+        .map(|(payload, index)| Frame {
+            fragment: Some(FragmentHeader {
+                message_id,
+                index,
+                count,
+                length,
+                checksum,
+            }),
+            payload: payload.to_vec(),
+            ..frame.clone()
+        })
+        .collect()
+}
+
+/// Data channel message decoded by [Reassembler::receive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Received {
+    /// Whole text message for the application
+    Text(String),
+    /// Whole binary message for the application
+    Binary(Vec<u8>),
+    /// Ping with the given id, to be answered with a pong
+    Ping(u32),
+    /// Answer to our ping
+    Pong(Pong),
+}
+
+/// Puts fragmented messages received over a single data channel back together
+#[derive(Debug, Default)]
+pub(crate) struct Reassembler {
+    pending: BTreeMap<u32, PartialMessage>,
+}
+
+#[derive(Debug)]
+struct PartialMessage {
+    /// First fragment received, which the following ones are checked against
+    first: Frame,
+    fragments: BTreeMap<u32, Vec<u8>>,
+    received_length: usize,
+}
+
+impl Reassembler {
+    /// Decodes a received data channel message into the message or control frame it carries,
+    /// returns `None` while there are fragments of a message missing
+    pub(crate) fn receive(&mut self, bytes: &[u8]) -> Result<Option<Received>, FrameError> {
+        let frame = Frame::decode(bytes)?;
+        match frame.kind {
+            FrameKind::Ping => Ok(Some(Received::Ping(frame.ping_id()?))),
+            FrameKind::Pong => Ok(Some(Received::Pong(frame.pong_payload()?))),
+            FrameKind::Text | FrameKind::Binary => self
+                .push(frame)?
+                .map(|frame| {
+                    let frame = compression::decompress(frame)?;
+                    match frame.kind {
+                        FrameKind::Binary => Ok(Received::Binary(frame.payload)),
+                        _ => Ok(Received::Text(frame.into_text()?)),
+                    }
+                })
+                .transpose(),
+        }
+    }
+
+    /// Returns the whole message once its last missing fragment is pushed,
+    /// frames that are not fragments are returned right away
+    pub(crate) fn push(&mut self, frame: Frame) -> Result<Option<Frame>, FrameError> {
+        let Some(header) = frame.fragment else {
+            return Ok(Some(frame));
+        };
+        if header.count == 0 || header.index >= header.count || header.count > header.length {
+            return Err(FrameError::InvalidFragment);
+        }
+        if header.length as usize > MAX_MESSAGE_LENGTH {
+            return Err(FrameError::MessageTooLarge(header.length));
+        }
+
+        if !self.pending.contains_key(&header.message_id) {
+            self.evict_stale();
+            self.pending.insert(
+                header.message_id,
+                PartialMessage {
+                    first: frame.clone(),
+                    fragments: BTreeMap::new(),
+                    received_length: 0,
+                },
+            );
+        }
+        let partial = self.pending.get_mut(&header.message_id).unwrap();
+        let expected = partial.first.fragment.unwrap();
+        let matches_message = partial.first.kind == frame.kind
+            && partial.first.flags == frame.flags
+            && (expected.count, expected.length, expected.checksum)
+                == (header.count, header.length, header.checksum);
+        if !matches_message
+            || partial.fragments.contains_key(&header.index)
+            || partial.received_length + frame.payload.len() > header.length as usize
+        {
+            self.pending.remove(&header.message_id);
+            return Err(FrameError::InvalidFragment);
+        }
+        partial.received_length += frame.payload.len();
+        partial.fragments.insert(header.index, frame.payload);
+        if partial.fragments.len() < header.count as usize {
+            return Ok(None);
+        }
+
+        let partial = self.pending.remove(&header.message_id).unwrap();
+        if partial.received_length != header.length as usize {
+            return Err(FrameError::InvalidFragment);
+        }
+        let payload: Vec<u8> = partial.fragments.into_values().flatten().collect();
+        if crc32(&payload) != header.checksum {
+            return Err(FrameError::ChecksumMismatch);
+        }
+        Ok(Some(Frame {
+            fragment: None,
+            payload,
+            ..partial.first
+        }))
+    }
+
+    fn evict_stale(&mut self) {
+        while self.pending.len() >= MAX_PENDING_MESSAGES {
+            if let Some((message_id, _)) = self.pending.pop_first() {
+                debug!("dropping incomplete message {}", message_id);
+            }
+        }
+    }
+}
+
+/// CRC-32 (IEEE 802.3), as used by zlib and PNG
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn reassemble(
+        frames: impl IntoIterator<Item = Frame>,
+    ) -> Vec<Result<Option<Frame>, FrameError>> {
+        let mut reassembler = Reassembler::default();
+        frames
+            .into_iter()
+            .map(|frame| reassembler.push(Frame::decode(&frame.encode()).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn checksum_is_standard_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn max_message_size_is_read_from_sdp() {
+        let sdp = "v=0\r\nm=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=sctp-port:5000\r\na=max-message-size:262144\r\n";
+        assert_eq!(max_message_size(sdp), 262144);
+        assert_eq!(
+            max_message_size("v=0\r\na=max-message-size:0\r\n"),
+            usize::MAX
+        );
+        assert_eq!(max_message_size("v=0\r\n"), DEFAULT_MAX_MESSAGE_SIZE);
+    }
+
+    #[test]
+    fn small_frames_are_not_split() {
+        let frame = Frame::text("short");
+        assert_eq!(split(frame.clone(), 100), vec![frame.clone()]);
+        assert_eq!(reassemble([frame.clone()]), vec![Ok(Some(frame))]);
+    }
+
+    #[test]
+    fn fragments_fit_max_message_size_and_reassemble_in_any_order() {
+        let message: String = (0..1000)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect();
+        let frame = Frame::text(&message);
+        let mut fragments = split(frame.clone(), 100);
+        assert_eq!(fragments.len(), 14);
+        assert!(fragments
+            .iter()
+            .all(|fragment| fragment.encode().len() <= 100));
+
+        fragments.reverse();
+        let results = reassemble(fragments);
+        assert!(results[..13].iter().all(|result| result == &Ok(None)));
+        assert_eq!(results[13], Ok(Some(frame)));
+    }
+
+    #[test]
+    fn interleaved_messages_are_reassembled_separately() {
+        let first = Frame::text(&"1".repeat(300));
+        let second = Frame::text(&"2".repeat(300));
+        let mut fragments = vec![];
+        for (a, b) in split(first.clone(), 100)
+            .into_iter()
+            .zip(split(second.clone(), 100))
+        {
+            fragments.extend([a, b]);
+        }
+        let messages: Vec<Frame> = reassemble(fragments)
+            .into_iter()
+            .filter_map(|result| result.unwrap())
+            .collect();
+        assert_eq!(messages, vec![first, second]);
+    }
+
+    #[test]
+    fn corrupted_fragments_are_rejected() {
+        let fragments = split(Frame::text(&"x".repeat(300)), 100);
+
+        let mut corrupted = fragments.clone();
+        corrupted[1].payload[0] = b'y';
+        assert_eq!(
+            reassemble(corrupted).pop().unwrap(),
+            Err(FrameError::ChecksumMismatch)
+        );
+
+        let mut duplicated = fragments.clone();
+        duplicated[1] = duplicated[0].clone();
+        assert_eq!(reassemble(duplicated)[1], Err(FrameError::InvalidFragment));
+
+        let mut out_of_range = fragments[0].clone();
+        out_of_range.fragment.as_mut().unwrap().index = 10;
+        assert_eq!(
+            reassemble([out_of_range]),
+            vec![Err(FrameError::InvalidFragment)]
+        );
+    }
+
+    #[test]
+    fn control_frames_are_told_apart_from_messages() {
+        let mut reassembler = Reassembler::default();
+        let mut receive = |frame: Frame| reassembler.receive(&frame.encode());
+        assert_eq!(receive(Frame::ping(1)), Ok(Some(Received::Ping(1))));
+        let pong = Pong {
+            ping_id: 2,
+            time: 0,
+            pinging_peer: 5_usize.into(),
+        };
+        assert_eq!(receive(Frame::pong(&pong)), Ok(Some(Received::Pong(pong))));
+        assert_eq!(
+            receive(Frame::text("ping")),
+            Ok(Some(Received::Text("ping".to_string())))
+        );
+        assert_eq!(
+            receive(Frame::binary(vec![0, 255])),
+            Ok(Some(Received::Binary(vec![0, 255])))
+        );
+        let fragmented_ping = Frame {
+            fragment: split(Frame::text(&"x".repeat(300)), 100)[0].fragment,
+            ..Frame::ping(3)
+        };
+        assert_eq!(receive(fragmented_ping), Err(FrameError::InvalidControl));
+    }
+
+    #[test]
+    fn incomplete_messages_are_eventually_dropped() {
+        let mut reassembler = Reassembler::default();
+        for _ in 0..MAX_PENDING_MESSAGES * 2 {
+            let first_fragment = split(Frame::text(&"x".repeat(300)), 100).remove(0);
+            assert_eq!(reassembler.push(first_fragment), Ok(None));
+        }
+        assert_eq!(reassembler.pending.len(), MAX_PENDING_MESSAGES);
+    }
+}
@@ -0,0 +1,415 @@
+use rusty_games_protocol::UserId;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Version of the framing format written in the first byte of every frame
+pub(crate) const FRAME_VERSION: u8 = 1;
+
+/// Flag set when the header is followed by a sequence number
+pub(crate) const FLAG_SEQUENCE: u8 = 0b0000_0001;
+/// Flag set when the frame carries only a part of a message, described by a [FragmentHeader]
+pub(crate) const FLAG_FRAGMENT: u8 = 0b0000_0010;
+/// Flag set when the payload is compressed with raw deflate (RFC 1951)
+pub(crate) const FLAG_COMPRESSED: u8 = 0b0000_0100;
+
+/// Flags this version of the library understands, frames with any other flag set are rejected
+const KNOWN_FLAGS: u8 = FLAG_SEQUENCE | FLAG_FRAGMENT | FLAG_COMPRESSED;
+/// Flags that follow from optional parts of [Frame], rather than being stored in it
+const PART_FLAGS: u8 = FLAG_SEQUENCE | FLAG_FRAGMENT;
+
+const HEADER_LENGTH: usize = 3;
+const SEQUENCE_LENGTH: usize = 4;
+const FRAGMENT_HEADER_LENGTH: usize = 20;
+const PING_LENGTH: usize = 4;
+const PONG_LENGTH: usize = 28;
+
+/// Longest header a frame can have, the rest of a data channel message is left for the payload
+pub(crate) const MAX_HEADER_LENGTH: usize =
+    HEADER_LENGTH + SEQUENCE_LENGTH + FRAGMENT_HEADER_LENGTH;
+
+/// Reason a message received from a peer could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// Message is shorter than the header it declares
+    Truncated,
+    /// Frame was written with a framing version this library doesn't speak
+    UnsupportedVersion(u8),
+    /// Kind byte doesn't name any known kind of message
+    UnknownKind(u8),
+    /// Some of the flags are not known to this library
+    UnknownFlags(u8),
+    /// Payload of a text frame is not valid UTF-8
+    InvalidText,
+    /// Fragment doesn't fit the other fragments of its message
+    InvalidFragment,
+    /// Reassembled message is larger than the library accepts
+    MessageTooLarge(u32),
+    /// Reassembled message doesn't match the checksum it was sent with
+    ChecksumMismatch,
+    /// Compressed payload can't be decompressed, or is too large once decompressed
+    InvalidCompression,
+    /// Ping or pong is fragmented, or its payload is not as long as it should be
+    InvalidControl,
+}
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::Truncated => write!(f, "frame is shorter than its header"),
+            FrameError::UnsupportedVersion(version) => {
+                write!(f, "unsupported framing version {}", version)
+            }
+            FrameError::UnknownKind(kind) => write!(f, "unknown frame kind {}", kind),
+            FrameError::UnknownFlags(flags) => write!(f, "unknown frame flags {:#010b}", flags),
+            FrameError::InvalidText => write!(f, "text frame payload is not valid UTF-8"),
+            FrameError::InvalidFragment => {
+                write!(f, "fragment doesn't match other fragments of its message")
+            }
+            FrameError::MessageTooLarge(length) => {
+                write!(f, "fragmented message of {} bytes is too large", length)
+            }
+            FrameError::ChecksumMismatch => {
+                write!(f, "checksum of a reassembled message doesn't match")
+            }
+            FrameError::InvalidCompression => {
+                write!(f, "compressed payload can't be decompressed")
+            }
+            FrameError::InvalidControl => write!(f, "ping or pong frame is malformed"),
+        }
+    }
+}
+
+impl Error for FrameError {}
+
+/// What the payload of a frame holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrameKind {
+    Text = 0,
+    Binary = 1,
+    /// Control frame answered by the library of the peer, never delivered to the application
+    Ping = 2,
+    /// Answer to a [FrameKind::Ping], described by [Pong]
+    Pong = 3,
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = FrameError;
+
+    fn try_from(kind: u8) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(FrameKind::Text),
+            1 => Ok(FrameKind::Binary),
+            2 => Ok(FrameKind::Ping),
+            3 => Ok(FrameKind::Pong),
+            kind => Err(FrameError::UnknownKind(kind)),
+        }
+    }
+}
+
+/// Position of a fragment within the message it's a part of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FragmentHeader {
+    /// Same for all fragments of a message, and different for messages sent around the same time
+    pub(crate) message_id: u32,
+    pub(crate) index: u32,
+    pub(crate) count: u32,
+    /// Length of the whole message
+    pub(crate) length: u32,
+    /// CRC-32 of the whole message
+    pub(crate) checksum: u32,
+}
+
+impl FragmentHeader {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        for field in [
+            self.message_id,
+            self.index,
+            self.count,
+            self.length,
+            self.checksum,
+        ] {
+            bytes.extend_from_slice(&field.to_be_bytes());
+        }
+    }
+
+    fn decode(bytes: &[u8; FRAGMENT_HEADER_LENGTH]) -> Self {
+        let field =
+            |index: usize| u32::from_be_bytes(bytes[index * 4..index * 4 + 4].try_into().unwrap());
+        FragmentHeader {
+            message_id: field(0),
+            index: field(1),
+            count: field(2),
+            length: field(3),
+            checksum: field(4),
+        }
+    }
+}
+
+/// Payload of a pong frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Pong {
+    /// Id of the ping being answered
+    pub(crate) ping_id: u32,
+    /// Clock of the answering peer when it answered, in microseconds
+    pub(crate) time: u64,
+    /// Id the signaling server gave the pinging peer, as known by the answering peer,
+    /// which is how peers learn their own id
+    pub(crate) pinging_peer: UserId,
+}
+
+/// Single message sent over a data channel, see [crate docs](crate#message-framing) for its layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Frame {
+    pub(crate) kind: FrameKind,
+    /// Flags other than [FLAG_SEQUENCE] and [FLAG_FRAGMENT], which follow from the fields below
+    pub(crate) flags: u8,
+    pub(crate) sequence: Option<u32>,
+    pub(crate) fragment: Option<FragmentHeader>,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl Frame {
+    pub(crate) fn text(message: &str) -> Self {
+        Frame {
+            kind: FrameKind::Text,
+            flags: 0,
+            sequence: None,
+            fragment: None,
+            payload: message.as_bytes().to_vec(),
+        }
+    }
+
+    pub(crate) fn binary(payload: Vec<u8>) -> Self {
+        Frame {
+            kind: FrameKind::Binary,
+            flags: 0,
+            sequence: None,
+            fragment: None,
+            payload,
+        }
+    }
+
+    pub(crate) fn ping(ping_id: u32) -> Self {
+        Frame {
+            kind: FrameKind::Ping,
+            flags: 0,
+            sequence: None,
+            fragment: None,
+            payload: ping_id.to_be_bytes().to_vec(),
+        }
+    }
+
+    pub(crate) fn pong(pong: &Pong) -> Self {
+        let mut payload = Vec::with_capacity(PONG_LENGTH);
+        payload.extend_from_slice(&pong.ping_id.to_be_bytes());
+        payload.extend_from_slice(&pong.time.to_be_bytes());
+        payload.extend_from_slice(&pong.pinging_peer.into_inner().to_be_bytes());
+        Frame {
+            kind: FrameKind::Pong,
+            flags: 0,
+            sequence: None,
+            fragment: None,
+            payload,
+        }
+    }
+
+    /// Id of the ping a ping frame carries as a big-endian `u32`
+    pub(crate) fn ping_id(&self) -> Result<u32, FrameError> {
+        let payload = self.control_payload::<PING_LENGTH>()?;
+        Ok(u32::from_be_bytes(payload))
+    }
+
+    pub(crate) fn pong_payload(&self) -> Result<Pong, FrameError> {
+        let payload = self.control_payload::<PONG_LENGTH>()?;
+        Ok(Pong {
+            ping_id: u32::from_be_bytes(payload[0..4].try_into().unwrap()),
+            time: u64::from_be_bytes(payload[4..12].try_into().unwrap()),
+            pinging_peer: UserId::new(u128::from_be_bytes(payload[12..28].try_into().unwrap())),
+        })
+    }
+
+    fn control_payload<const LENGTH: usize>(&self) -> Result<[u8; LENGTH], FrameError> {
+        if self.fragment.is_some() {
+            return Err(FrameError::InvalidControl);
+        }
+        self.payload
+            .as_slice()
+            .try_into()
+            .map_err(|_| FrameError::InvalidControl)
+    }
+
+    /// Length of the frame once encoded
+    pub(crate) fn encoded_length(&self) -> usize {
+        let mut length = HEADER_LENGTH + self.payload.len();
+        if self.sequence.is_some() {
+            length += SEQUENCE_LENGTH;
+        }
+        if self.fragment.is_some() {
+            length += FRAGMENT_HEADER_LENGTH;
+        }
+        length
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut flags = self.flags & !PART_FLAGS;
+        if self.sequence.is_some() {
+            flags |= FLAG_SEQUENCE;
+        }
+        if self.fragment.is_some() {
+            flags |= FLAG_FRAGMENT;
+        }
+        let mut bytes = Vec::with_capacity(self.encoded_length());
+        bytes.extend_from_slice(&[FRAME_VERSION, self.kind as u8, flags]);
+        if let Some(sequence) = self.sequence {
+            bytes.extend_from_slice(&sequence.to_be_bytes());
+        }
+        if let Some(fragment) = &self.fragment {
+            fragment.encode(&mut bytes);
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, FrameError> {
+        let (header, rest) = bytes
+            .split_first_chunk::<HEADER_LENGTH>()
+            .ok_or(FrameError::Truncated)?;
+        let [version, kind, flags] = *header;
+        if version != FRAME_VERSION {
+            return Err(FrameError::UnsupportedVersion(version));
+        }
+        let kind = FrameKind::try_from(kind)?;
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(FrameError::UnknownFlags(flags & !KNOWN_FLAGS));
+        }
+        let (sequence, rest) = if flags & FLAG_SEQUENCE != 0 {
+            let (sequence, rest) = rest
+                .split_first_chunk::<SEQUENCE_LENGTH>()
+                .ok_or(FrameError::Truncated)?;
+            (Some(u32::from_be_bytes(*sequence)), rest)
+        } else {
+            (None, rest)
+        };
+        let (fragment, payload) = if flags & FLAG_FRAGMENT != 0 {
+            let (fragment, payload) = rest
+                .split_first_chunk::<FRAGMENT_HEADER_LENGTH>()
+                .ok_or(FrameError::Truncated)?;
+            (Some(FragmentHeader::decode(fragment)), payload)
+        } else {
+            (None, rest)
+        };
+        Ok(Frame {
+            kind,
+            flags: flags & !PART_FLAGS,
+            sequence,
+            fragment,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Payload of a text frame, the kind is checked by the caller
+    pub(crate) fn into_text(self) -> Result<String, FrameError> {
+        String::from_utf8(self.payload).map_err(|_| FrameError::InvalidText)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn text_frame_has_three_byte_header() {
+        assert_eq!(Frame::text("hi").encode(), vec![1, 0, 0, b'h', b'i']);
+        assert_eq!(Frame::text("").encode(), vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn frames_survive_round_trip() {
+        let frames = [
+            Frame::text(""),
+            Frame::text("zażółć gęślą jaźń"),
+            Frame::binary(vec![]),
+            Frame::binary(vec![0, 1, 2, 255]),
+            Frame {
+                sequence: Some(0xDEAD_BEEF),
+                ..Frame::text("numbered")
+            },
+            Frame {
+                sequence: Some(1),
+                fragment: Some(FragmentHeader {
+                    message_id: 2,
+                    index: 3,
+                    count: 4,
+                    length: 5,
+                    checksum: 6,
+                }),
+                ..Frame::binary(vec![7])
+            },
+        ];
+        for frame in frames {
+            assert_eq!(Frame::decode(&frame.encode()).unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn sequence_number_is_big_endian_after_header() {
+        let frame = Frame {
+            sequence: Some(258),
+            ..Frame::binary(vec![7])
+        };
+        assert_eq!(frame.encode(), vec![1, 1, FLAG_SEQUENCE, 0, 0, 1, 2, 7]);
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        assert_eq!(Frame::decode(&[]), Err(FrameError::Truncated));
+        assert_eq!(Frame::decode(&[1, 0]), Err(FrameError::Truncated));
+        assert_eq!(
+            Frame::decode(&[1, 0, FLAG_SEQUENCE, 0, 0]),
+            Err(FrameError::Truncated)
+        );
+        assert_eq!(
+            Frame::decode(&[1, 0, FLAG_FRAGMENT, 0, 0, 0, 1]),
+            Err(FrameError::Truncated)
+        );
+        assert_eq!(
+            Frame::decode(b"xhello"),
+            Err(FrameError::UnsupportedVersion(b'x'))
+        );
+        assert_eq!(Frame::decode(&[1, 9, 0]), Err(FrameError::UnknownKind(9)));
+        assert_eq!(
+            Frame::decode(&[1, 2, 0, 0, 1]).unwrap().ping_id(),
+            Err(FrameError::InvalidControl)
+        );
+        assert_eq!(
+            Frame::decode(&[1, 0, 0b1000_0001, 0, 0, 0, 0]),
+            Err(FrameError::UnknownFlags(0b1000_0000))
+        );
+    }
+
+    #[test]
+    fn ping_and_pong_carry_ping_id() {
+        assert_eq!(Frame::ping(258).encode(), vec![1, 2, 0, 0, 0, 1, 2]);
+        let pong = Pong {
+            ping_id: 258,
+            time: 3,
+            pinging_peer: UserId::new(4),
+        };
+        let encoded = Frame::pong(&pong).encode();
+        assert_eq!(encoded.len(), 3 + PONG_LENGTH);
+        assert_eq!(encoded[..7], [1, 3, 0, 0, 0, 1, 2]);
+        assert_eq!(encoded[14], 3);
+        assert_eq!(encoded[30], 4);
+        let decoded = Frame::decode(&encoded).unwrap();
+        assert_eq!(decoded.kind, FrameKind::Pong);
+        assert_eq!(decoded.pong_payload(), Ok(pong));
+        assert_eq!(decoded.ping_id(), Err(FrameError::InvalidControl));
+    }
+
+    #[test]
+    fn only_valid_utf8_converts_to_text() {
+        let decode_text = |bytes: &[u8]| Frame::decode(bytes).unwrap().into_text();
+        assert_eq!(decode_text(&[1, 0, 0, b'o', b'k']), Ok("ok".to_string()));
+        assert_eq!(decode_text(&[1, 0, 0, 0xFF]), Err(FrameError::InvalidText));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::clock::{ClockOffset, ClockSync};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How often network managers ping their peers, unless changed with `set_ping_interval`
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pings that aren't answered within this long are counted as lost
+const PONG_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of the latest pings packet loss is calculated over
+const LOSS_WINDOW: usize = 100;
+
+/// Latency of a connection with a single peer, measured with pings sent by the library
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LatencyStats {
+    /// Round-trip time smoothed over the latest pings, the way TCP does it (RFC 6298)
+    pub round_trip_time: Duration,
+    /// How much round-trip time varies between consecutive pings (RFC 3550)
+    pub jitter: Duration,
+    /// Fraction of the latest pings that weren't answered in time, from `0.0` to `1.0`
+    pub packet_loss: f64,
+}
+
+/// Pings sent to a single peer, and latency and clock offset measured from their pongs
+#[derive(Debug, Default)]
+pub(crate) struct LatencyTracker {
+    next_ping_id: u32,
+    /// Pings waiting for a pong, by id, with the time they were sent at, oldest first
+    unanswered: VecDeque<(u32, Duration)>,
+    /// Whether each of the latest settled pings was answered in time, oldest first
+    answered: VecDeque<bool>,
+    round_trip_time: Option<Duration>,
+    last_sample: Option<Duration>,
+    jitter: Duration,
+    clock: ClockSync,
+}
+
+impl LatencyTracker {
+    /// Id for the next ping, which is only expected to be answered once it's [sent](Self::sent)
+    pub(crate) fn next_ping_id(&mut self) -> u32 {
+        let ping_id = self.next_ping_id;
+        self.next_ping_id = ping_id.wrapping_add(1);
+        ping_id
+    }
+
+    pub(crate) fn sent(&mut self, ping_id: u32, now: Duration) {
+        self.unanswered.push_back((ping_id, now));
+    }
+
+    /// Counts pings that waited for their pong too long as lost,
+    /// returns whether any were
+    pub(crate) fn expire(&mut self, now: Duration) -> bool {
+        let mut expired = false;
+        while let Some(&(_, sent_at)) = self.unanswered.front() {
+            if now.saturating_sub(sent_at) < PONG_TIMEOUT {
+                break;
+            }
+            self.unanswered.pop_front();
+            self.settle(false);
+            expired = true;
+        }
+        expired
+    }
+
+    /// Measures round-trip time of the ping and clock of the peer at `remote_time`,
+    /// returns updated stats unless the pong doesn't answer any ping still waiting for it
+    pub(crate) fn pong(
+        &mut self,
+        ping_id: u32,
+        remote_time: Duration,
+        now: Duration,
+    ) -> Option<LatencyStats> {
+        self.expire(now);
+        let index = self.unanswered.iter().position(|(id, _)| *id == ping_id)?;
+        let (_, sent_at) = self.unanswered.remove(index)?;
+        self.settle(true);
+        self.clock.add(sent_at, remote_time, now);
+
+        let sample = now.saturating_sub(sent_at);
+        self.round_trip_time = Some(match self.round_trip_time {
+            None => sample,
+            Some(round_trip_time) => round_trip_time.mul_f64(7.0 / 8.0) + sample.mul_f64(1.0 / 8.0),
+        });
+        if let Some(last_sample) = self.last_sample.replace(sample) {
+            let difference = sample.abs_diff(last_sample).as_secs_f64();
+            let jitter = self.jitter.as_secs_f64();
+            self.jitter = Duration::from_secs_f64(jitter + (difference - jitter) / 16.0);
+        }
+        self.stats()
+    }
+
+    /// `None` until the first pong arrives
+    pub(crate) fn stats(&self) -> Option<LatencyStats> {
+        let lost = self.answered.iter().filter(|answered| !**answered).count();
+        Some(LatencyStats {
+            round_trip_time: self.round_trip_time?,
+            jitter: self.jitter,
+            packet_loss: lost as f64 / self.answered.len() as f64,
+        })
+    }
+
+    /// Offset of the clock of the peer, `None` until the first pong arrives
+    pub(crate) fn clock_offset(&self) -> Option<ClockOffset> {
+        self.clock.offset()
+    }
+
+    fn settle(&mut self, answered: bool) {
+        if self.answered.len() == LOSS_WINDOW {
+            self.answered.pop_front();
+        }
+        self.answered.push_back(answered);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(milliseconds: u64) -> Duration {
+        Duration::from_millis(milliseconds)
+    }
+
+    fn ping(tracker: &mut LatencyTracker, now: Duration) -> u32 {
+        let ping_id = tracker.next_ping_id();
+        tracker.sent(ping_id, now);
+        ping_id
+    }
+
+    #[test]
+    fn round_trip_time_is_smoothed() {
+        let mut tracker = LatencyTracker::default();
+        assert_eq!(tracker.stats(), None);
+
+        let first = ping(&mut tracker, ms(0));
+        let stats = tracker.pong(first, ms(0), ms(80)).unwrap();
+        assert_eq!(stats.round_trip_time, ms(80));
+        assert_eq!(stats.jitter, ms(0));
+
+        let second = ping(&mut tracker, ms(1000));
+        let stats = tracker.pong(second, ms(0), ms(1160)).unwrap();
+        assert_eq!(stats.round_trip_time, ms(90));
+        assert_eq!(stats.jitter, ms(5));
+        assert_eq!(stats.packet_loss, 0.0);
+    }
+
+    #[test]
+    fn pings_without_timely_pong_are_lost() {
+        let mut tracker = LatencyTracker::default();
+        let lost = ping(&mut tracker, ms(0));
+        let answered = ping(&mut tracker, ms(1000));
+        assert!(!tracker.expire(ms(1999)));
+        assert!(tracker.expire(ms(2000)));
+
+        assert_eq!(tracker.pong(lost, ms(0), ms(2100)), None);
+        let stats = tracker.pong(answered, ms(0), ms(2100)).unwrap();
+        assert_eq!(stats.round_trip_time, ms(1100));
+        assert_eq!(stats.packet_loss, 0.5);
+    }
+
+    #[test]
+    fn loss_is_calculated_over_latest_pings() {
+        let mut tracker = LatencyTracker::default();
+        for _ in 0..LOSS_WINDOW {
+            ping(&mut tracker, ms(0));
+        }
+        tracker.expire(PONG_TIMEOUT);
+        for i in 0..LOSS_WINDOW as u64 / 2 {
+            let now = PONG_TIMEOUT + ms(i);
+            let ping_id = ping(&mut tracker, now);
+            tracker.pong(ping_id, ms(0), now + ms(10));
+        }
+        assert_eq!(tracker.stats().unwrap().packet_loss, 0.5);
+    }
+}
@@ -15,16 +15,98 @@ After that you can send messages back and forth without worrying about the imple
 Library contains three network topologies, [one-to-one](one_to_one), which creates an equal connection between two peers,
 [one-to-many](one_to_many), which specifies a host and arbitrary number of clients
 and [many-to-many] that creates connection for pair of peers and allows sending messages to any of them.
+On top of one-to-many, [replication] sends authoritative game state from the host to its clients,
+while [rollback] keeps a game simulated by both peers of one-to-one in sync.
+
+# Native targets
+
+By default the library uses browser APIs and must be compiled to WebAssembly.
+With the `native` feature enabled it uses a pure Rust WebRTC implementation instead,
+so that the same topologies can be used by native games, bots and tests.
+Natively all callbacks run on the current thread, which requires creating and starting
+network managers from within a [tokio LocalSet](https://docs.rs/tokio/latest/tokio/task/struct.LocalSet.html).
+
+# Message framing
+
+Every message is sent over the data channel as a single binary message starting with a header,
+so that peers written in other languages can talk to the library:
+
+| bytes | content |
+|-------|---------|
+| 0     | framing version, currently `1` |
+| 1     | kind of the payload: `0` for UTF-8 text, `1` for binary, `2` for a ping and `3` for a pong |
+| 2     | flags: bit `0` is set when a sequence number follows, bit `1` when a fragment header follows, bit `2` when the payload is compressed, all other bits are reserved and must be `0` |
+|       | sequence number as a big-endian `u32`, only present if its flag is set |
+|       | fragment header, only present if its flag is set |
+| rest  | payload, which may be empty |
+
+Messages that don't fit into the `max-message-size` the peer stated in its session description
+(64 KiB if it didn't, and at most 64 KiB in any case) are split into fragments,
+each sent as a separate frame with the kind and flags of the whole message.
+The fragment header is made of five big-endian `u32`s: id of the message, shared by all of
+its fragments, index of the fragment, number of fragments, length of the whole message
+and its CRC-32 (IEEE 802.3) checksum. The receiver joins fragments in the order of their indices
+and delivers the message once all of them arrived and the checksum matches.
+
+Peers that enabled compression with `set_compression_threshold` of network managers
+say so in their session descriptions, with an `a=x-rusty-games-compression:deflate` attribute.
+Between two such peers, messages longer than the threshold are compressed with raw deflate
+(RFC 1951) before being split into fragments, unless that doesn't make them shorter.
+Such frames have the compressed flag set, and the checksum of their fragments
+is calculated over the compressed payload.
+
+Pings and pongs are never fragmented. A ping carries its id as a big-endian `u32`.
+Every ping is answered with a pong over the data channel the connection was opened with,
+and neither is delivered to the application. The pong carries the id of the ping,
+the clock of the answering peer in microseconds as a big-endian `u64`
+and the [UserId] of the pinging peer, as the answering peer knows it, as a big-endian `u128`.
+Network managers send pings every `set_ping_interval` to measure latency of each connection
+and synchronize clocks.
+
+Text messages are passed to `on_message_callback` of network managers, and binary ones
+to the callback set with `on_binary_message`, which receives them as sent with `send_binary_message`.
+Messages that don't follow this format are not delivered, and are reported as
+[NetworkError::MalformedFrame] instead.
 
 */
 
+mod candidates;
+mod channel;
+mod clock;
+mod compression;
+mod connect;
+mod error;
+mod events;
+mod fragmentation;
+mod framing;
+mod latency;
 #[deny(missing_docs)]
 pub mod many_to_many;
+mod negotiation;
 pub mod one_to_many;
 pub mod one_to_one;
+mod platform;
+pub mod replication;
+pub mod rollback;
+mod scheduler;
+mod send_queue;
+mod stats;
+mod timer;
 mod utils;
 
+pub use channel::{ChannelConfig, Reliability};
+pub use clock::ClockOffset;
+pub use compression::CompressionStats;
+pub use connect::Connected;
+pub use error::{NetworkError, SignalingError};
+pub use events::{DropPolicy, NetworkEvent, NetworkEvents, Payload};
+pub use framing::FrameError;
+pub use latency::{LatencyStats, DEFAULT_PING_INTERVAL};
+pub use platform::PlatformError;
 pub use rusty_games_protocol::{SessionId, UserId};
+pub use scheduler::Priority;
+pub use send_queue::DEFAULT_HIGH_WATER_MARK;
+pub use stats::{CandidateType, ChannelStats, ConnectionStats, IceConnectionState};
 pub use utils::ConnectionType;
 
 /// Returns a new SessionId instance that can be used to identify a session by signaling server.
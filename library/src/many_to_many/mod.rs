@@ -3,6 +3,8 @@ Library module for implementation of the many-to-many topology of peer communica
 
 Each peer in session is an equal, with ability to send and receive messages from any other peer.
 Unlike with one-to-many topology, any peer can leave at any time without compromising the network.
+Either peer of a connection can renegotiate it at any time, when both do so at once,
+the peer that was already in session when the other one joined gives way.
 
 To identify peers you should store [UserId] accessible inside `on_open_callback` in some custom structure.
 Then you can use it in [NetworkManager::send_message] to specify exactly which peer should receive the message.
@@ -13,16 +15,16 @@ In this example we create 3 peers that all establish connection with each other.
 Each of the peers will send a `ping` message to each new connection.
 Also each peer will respond with a `pong` response.
 Overall we will expect 6 `ping` and 6 `pong` messages (3 connections, both peers in each).
-```
-
+```no_run
 use rusty_games_library::many_to_many::NetworkManager;
-use rusty_games_library::{ConnectionType, SessionId};
+use rusty_games_library::{ConnectionType, SessionId, UserId};
 use std::cell::RefCell;
 use std::rc::Rc;
 use web_sys::console;
 
 // there should be a signaling server from accompanying crate listening on this port
-const SIGNALING_SERVER_URL: &str = "ws://0.0.0.0:9001/one-to-many";
+const SIGNALING_SERVER_URL: &str = "ws://0.0.0.0:9001/signal";
+const STUN_SERVER_URLS: &str = "stun:openrelay.metered.ca:80";
 
 let opened_connections_count = Rc::new(RefCell::new(0));
 let received_messages_count = Rc::new(RefCell::new(0));
@@ -31,24 +33,24 @@ let peer_generator = || {
     let mut server = NetworkManager::new(
         SIGNALING_SERVER_URL,
         SessionId::new("dummy-session-id".to_string()),
-        ConnectionType::Stun,
+        ConnectionType::Stun { urls: STUN_SERVER_URLS.to_string() },
     )
     .unwrap();
 
     let server_clone = server.clone();
     let opened_connections_count = opened_connections_count.clone();
     let server_on_open = {
-        move |user_id| {
+        move |user_id: UserId| {
             console::log_1(&format!("connection to user established: {:?}", user_id).into());
             *opened_connections_count.borrow_mut() += 1;
-            server_clone.send_message(user_id, "ping!");
+            server_clone.send_message(user_id, "ping!").unwrap();
         }
     };
 
     let server_clone = server.clone();
     let received_messages_count = received_messages_count.clone();
     let server_on_message = {
-        move |user_id, message| {
+        move |user_id: UserId, message: String| {
             console::log_1(
                 &format!(
                     "server received message from client {:?}: {}",
@@ -57,7 +59,7 @@ let peer_generator = || {
                 .into(),
             );
             *received_messages_count.borrow_mut() += 1;
-            server_clone.send_message(user_id, "pong!");
+            server_clone.send_message(user_id, "pong!").unwrap();
         }
     };
     server.start(server_on_open, server_on_message).unwrap();
@@ -69,9 +71,14 @@ peer_generator();
  */
 
 use crate::one_to_many::NetworkManager as OneToManyNetworkManager;
-use crate::ConnectionType;
+use crate::{
+    ChannelConfig, ClockOffset, CompressionStats, Connected, ConnectionStats, ConnectionType,
+    DropPolicy, LatencyStats, NetworkError, NetworkEvent, NetworkEvents, Priority, SignalingError,
+};
+use rusty_games_protocol::signal::Topology;
 use rusty_games_protocol::{SessionId, UserId};
-use wasm_bindgen::JsValue;
+use std::collections::HashMap;
+use std::time::Duration;
 
 /// Abstraction over WebRTC peer-to-peer connection.
 /// Structure representing equal peer in many-to-many topology.
@@ -101,9 +108,15 @@ impl NetworkManager {
         signaling_server_url: &str,
         session_id: SessionId,
         connection_type: ConnectionType,
-    ) -> Result<Self, JsValue> {
+    ) -> Result<Self, NetworkError> {
         Ok(NetworkManager {
-            inner: OneToManyNetworkManager::new(signaling_server_url, session_id, connection_type, true)?,
+            inner: OneToManyNetworkManager::new(
+                signaling_server_url,
+                session_id,
+                connection_type,
+                Topology::ManyToMany,
+                true,
+            )?,
         })
     }
 
@@ -115,18 +128,250 @@ impl NetworkManager {
         &mut self,
         on_open_callback: impl FnMut(UserId) + Clone + 'static,
         on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
-    ) -> Result<(), JsValue> {
+    ) -> Result<(), NetworkError> {
         self.inner.start(on_open_callback, on_message_callback)
     }
 
+    /// Same as [NetworkManager::start], but waits until the connection with the first peer opens.
+    /// Fails if signaling or ICE fails, or if no peer connects within the `timeout`.
+    pub async fn connect(
+        &mut self,
+        on_open_callback: impl FnMut(UserId) + Clone + 'static,
+        on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
+        timeout: Duration,
+    ) -> Result<Connected, NetworkError> {
+        self.inner
+            .connect(on_open_callback, on_message_callback, timeout)
+            .await
+    }
+
+    /// Same as [NetworkManager::start], but instead of calling callbacks
+    /// everything that happens on the network is delivered as [NetworkEvents].
+    pub fn start_with_events(&mut self) -> Result<NetworkEvents, NetworkError> {
+        self.inner.start_with_events()
+    }
+
+    /// Same as [NetworkManager::start_with_events], but events are kept in a queue
+    /// of at most `capacity` events, drained with [NetworkManager::poll_events],
+    /// e.g. once per frame of a game loop.
+    /// Events arriving when the queue is full are handled according to the `drop_policy`.
+    pub fn start_with_event_queue(
+        &mut self,
+        capacity: usize,
+        drop_policy: DropPolicy,
+    ) -> Result<(), NetworkError> {
+        self.inner.start_with_event_queue(capacity, drop_policy)
+    }
+
+    /// Takes all events queued since the last call, oldest first.
+    /// Yields nothing unless started with [NetworkManager::start_with_event_queue].
+    pub fn poll_events(&self) -> impl Iterator<Item = NetworkEvent> {
+        self.inner.poll_events()
+    }
+
     /// Sends message over established data channel to a single peer represented by
     /// the [UserId] returned by signaling server during connection establishment.
-    pub fn send_message(&self, user_id: UserId, message: &str) -> Result<(), JsValue> {
+    /// Fails with [NetworkError::WouldBlock] while more than the high-water mark
+    /// is waiting to be sent, see [NetworkManager::send_message_async].
+    pub fn send_message(&self, user_id: UserId, message: &str) -> Result<(), NetworkError> {
         self.inner.send_message(user_id, message)
     }
 
+    /// Same as [NetworkManager::send_message], but sends the bytes as a binary message,
+    /// which the peer receives with the callback set by [NetworkManager::on_binary_message]
+    pub fn send_binary_message(&self, user_id: UserId, message: &[u8]) -> Result<(), NetworkError> {
+        self.inner.send_binary_message(user_id, message)
+    }
+
+    /// Sets a callback called with every binary message a peer sends, over any channel.
+    /// Binary messages are never passed to `on_message_callback` nor delivered as [NetworkEvents],
+    /// without this callback they are dropped.
+    pub fn on_binary_message(&self, callback: impl FnMut(UserId, Vec<u8>) + 'static) {
+        self.inner.on_binary_message(callback)
+    }
+
     /// Convenience method that sends the same message to all connected peers.
-    pub fn send_message_to_all(&self, message: &str) {
+    /// Returns the outcome of sending to each of them, as some may fail while others succeed.
+    pub fn send_message_to_all(&self, message: &str) -> HashMap<UserId, Result<(), NetworkError>> {
         self.inner.send_message_to_all(message)
     }
+
+    /// Same as [NetworkManager::send_message], but instead of failing with
+    /// [NetworkError::WouldBlock] waits until the data channel drains below the high-water mark.
+    /// Messages are sent in the order this method was called.
+    pub async fn send_message_async(
+        &self,
+        user_id: UserId,
+        message: &str,
+    ) -> Result<(), NetworkError> {
+        self.inner.send_message_async(user_id, message).await
+    }
+
+    /// Same as [NetworkManager::send_message_to_all], but waits for each peer
+    /// like [NetworkManager::send_message_async], without a slow one holding back the others.
+    pub async fn send_message_to_all_async(
+        &self,
+        message: &str,
+    ) -> HashMap<UserId, Result<(), NetworkError>> {
+        self.inner.send_message_to_all_async(message).await
+    }
+
+    /// Number of bytes that may wait to be sent over a data channel before sending
+    /// fails with [NetworkError::WouldBlock],
+    /// [DEFAULT_HIGH_WATER_MARK](crate::DEFAULT_HIGH_WATER_MARK) unless changed
+    pub fn set_high_water_mark(&self, high_water_mark: usize) {
+        self.inner.set_high_water_mark(high_water_mark)
+    }
+
+    /// Compresses messages longer than `threshold` bytes, `None` (the default) disables compression.
+    /// Compression is used with peers that enabled it too, which is agreed on
+    /// when connecting, so it has to be set before peers join.
+    /// Received messages are decompressed before they reach `on_message_callback`.
+    pub fn set_compression_threshold(&self, threshold: Option<usize>) {
+        self.inner.set_compression_threshold(threshold)
+    }
+
+    /// How well messages sent so far to all peers compressed
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.inner.compression_stats()
+    }
+
+    /// Queues the message for the peer, so that it's sent once queued messages
+    /// of the same or higher [Priority] are, e.g. a critical "player died" event
+    /// overtakes map chunks queued as bulk traffic.
+    /// Fails right away only if the message can't be sent at all,
+    /// errors of sending it later on are reported as [NetworkEvent::Error].
+    pub fn send_message_with_priority(
+        &self,
+        user_id: UserId,
+        message: &str,
+        priority: Priority,
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .schedule_message(user_id, message, priority, None)
+    }
+
+    /// Same as [NetworkManager::send_message_with_priority], but for state where only
+    /// the latest value matters: a message queued earlier under the same `key`
+    /// and [Priority] that wasn't sent yet is dropped, as the new one supersedes it.
+    pub fn send_state_update(
+        &self,
+        user_id: UserId,
+        key: &str,
+        message: &str,
+        priority: Priority,
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .schedule_message(user_id, message, priority, Some(key))
+    }
+
+    /// Limits how many bytes per second of messages sent with
+    /// [NetworkManager::send_message_with_priority] go to the peer,
+    /// except [Priority::Critical] ones, which are never held back. `None` removes the limit.
+    pub fn set_bandwidth_budget(
+        &self,
+        user_id: UserId,
+        bytes_per_second: Option<usize>,
+    ) -> Result<(), NetworkError> {
+        self.inner.set_bandwidth_budget(user_id, bytes_per_second)
+    }
+
+    /// Opens an additional data channel with the peer on an established connection,
+    /// e.g. an unreliable one for frequent state updates, and renegotiates the connection.
+    /// Opening a channel under a name that's already in use replaces the old channel,
+    /// which is how parameters of a channel are changed.
+    pub async fn open_channel(
+        &self,
+        user_id: UserId,
+        name: &str,
+        config: ChannelConfig,
+    ) -> Result<(), NetworkError> {
+        self.inner.open_channel(user_id, name, config).await
+    }
+
+    /// Sets a callback called with the name of every channel opened with
+    /// [NetworkManager::open_channel], on both ends of the connection, once it opens.
+    /// Messages received on such channels are passed to the same `on_message_callback`.
+    pub fn on_channel_open(&self, callback: impl FnMut(UserId, String) + 'static) {
+        self.inner.on_channel_open(callback)
+    }
+
+    /// Sends message over a channel opened with the peer by either side.
+    /// Fails with [NetworkError::UnknownChannel] if there is no such channel.
+    pub fn send_message_on_channel(
+        &self,
+        user_id: UserId,
+        channel: &str,
+        message: &str,
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .send_message_on_channel(user_id, channel, message)
+    }
+
+    /// Largest message the peer accepts in one piece, as stated in its session description,
+    /// `usize::MAX` if it accepts any size. Larger messages can still be sent,
+    /// they are split into fragments and put back together by the peer.
+    /// Fails with [NetworkError::DataChannelNotOpen] until the connection is negotiated.
+    pub fn max_message_size(&self, user_id: UserId) -> Result<usize, NetworkError> {
+        self.inner.max_message_size(user_id)
+    }
+
+    /// Round-trip time, traffic of each channel, types of the candidates in use
+    /// (telling whether the connection is relayed through a TURN server)
+    /// and state of ICE of the connection with the peer.
+    /// Fails with [NetworkError::NoConnectionForUser] if there is no such connection.
+    pub async fn stats(&self, user_id: UserId) -> Result<ConnectionStats, NetworkError> {
+        self.inner.stats(user_id).await
+    }
+
+    /// How often peers are pinged to measure latency,
+    /// [DEFAULT_PING_INTERVAL](crate::DEFAULT_PING_INTERVAL) unless changed,
+    /// `None` stops pinging. Pings of other peers are answered regardless.
+    pub fn set_ping_interval(&self, interval: Option<Duration>) {
+        self.inner.set_ping_interval(interval)
+    }
+
+    /// Smoothed round-trip time, jitter and packet loss of the connection with the peer,
+    /// measured with pings. `None` until the first pong arrives, or if there is no such connection.
+    pub fn latency(&self, user_id: UserId) -> Option<LatencyStats> {
+        self.inner.latency(user_id)
+    }
+
+    /// Offset of the clock all peers synchronize to, which is the clock of the peer
+    /// with the lowest [UserId] among connected ones, refined with every pong of that peer.
+    /// Zero if that's the local clock, `None` until it's known which one it is.
+    /// The reference changes when a peer with a lower id joins, or the reference leaves.
+    pub fn clock_offset(&self) -> Option<ClockOffset> {
+        self.inner.clock_offset()
+    }
+
+    /// Time of the clock all peers synchronize to, estimated from the local clock
+    /// and [NetworkManager::clock_offset]. See [ClockOffset] for what the time means.
+    pub fn estimated_remote_time(&self) -> Option<Duration> {
+        self.inner.estimated_remote_time()
+    }
+
+    /// Sets a callback called with [LatencyStats] of a peer whenever they change,
+    /// that is when a pong arrives or a ping goes unanswered, e.g. to show ping of each player
+    pub fn on_latency_update(&self, callback: impl FnMut(UserId, LatencyStats) + 'static) {
+        self.inner.on_latency_update(callback)
+    }
+
+    /// Closes the connection with a single peer, which sees its data channel close.
+    /// Fails with [NetworkError::NoConnectionForUser] if there is no such connection.
+    pub fn disconnect(&self, user_id: UserId) -> Result<(), NetworkError> {
+        self.inner.disconnect(user_id)
+    }
+
+    /// Closes connections with all peers and leaves the signaling session.
+    /// All callbacks are dropped and [NetworkEvents] end, the instance can't be used afterwards.
+    pub fn close(&self) {
+        self.inner.close()
+    }
+
+    /// Last error reported by the signaling server, e.g. [SignalingError::UnsupportedVersion]
+    /// when it doesn't speak the protocol version of this library.
+    pub fn signaling_error(&self) -> Option<SignalingError> {
+        self.inner.signaling_error()
+    }
 }
@@ -0,0 +1,243 @@
+use crate::compression::{self, Compression};
+use crate::fragmentation;
+use crate::platform::PeerConnection;
+use crate::NetworkError;
+use log::debug;
+use rusty_games_protocol::rtc::{IceCandidate, SdpAnswer, SdpOffer};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Offer/answer exchange with a single peer following the "perfect negotiation" pattern,
+/// which lets either peer start a (re)negotiation at any time without help from signaling server.
+///
+/// When both peers send an offer at the same time, the impolite peer ignores the incoming offer
+/// and waits for an answer to its own, while the polite one rolls its offer back
+/// and answers instead. The peer that was told to create the first offer by the signaling server
+/// with [IsHost](rusty_games_protocol::IsHost) is impolite, the one answering it is polite.
+///
+/// Natively the offer is not rolled back, but answered with the last session description
+/// of the peer, see [PeerConnection::rollback].
+#[derive(Debug, Clone)]
+pub(crate) struct Negotiation {
+    peer_connection: PeerConnection,
+    /// Settings of the network manager, advertised to the peer in every session description
+    compression: Rc<Compression>,
+    state: Rc<RefCell<NegotiationState>>,
+}
+
+#[derive(Debug, Default)]
+struct NegotiationState {
+    polite: bool,
+    making_offer: bool,
+    ignore_offer: bool,
+    setting_remote_answer_pending: bool,
+    remote_max_message_size: Option<usize>,
+    remote_accepts_compression: bool,
+}
+
+impl Negotiation {
+    pub(crate) fn new(
+        peer_connection: PeerConnection,
+        polite: bool,
+        compression: Rc<Compression>,
+    ) -> Self {
+        Negotiation {
+            peer_connection,
+            compression,
+            state: Rc::new(RefCell::new(NegotiationState {
+                polite,
+                ..NegotiationState::default()
+            })),
+        }
+    }
+
+    /// For when the role is only known once signaling server pairs the peers
+    pub(crate) fn set_polite(&self, polite: bool) {
+        self.state.borrow_mut().polite = polite;
+    }
+
+    pub(crate) fn peer_connection(&self) -> &PeerConnection {
+        &self.peer_connection
+    }
+
+    /// Largest message the peer accepts, known once its session description is
+    pub(crate) fn max_message_size(&self) -> Option<usize> {
+        self.state.borrow().remote_max_message_size
+    }
+
+    /// Compression to use for messages sent to the peer, only if both peers enabled it
+    pub(crate) fn compression(&self) -> Option<&Compression> {
+        (self.compression.is_enabled() && self.state.borrow().remote_accepts_compression)
+            .then_some(&*self.compression)
+    }
+
+    fn advertise_compression(&self, sdp: &mut String) {
+        if self.compression.is_enabled() {
+            compression::advertise(sdp);
+        }
+    }
+
+    /// Creates an offer and sets it as local description,
+    /// returns `None` if another exchange is already in progress
+    pub(crate) async fn create_offer(&self) -> Result<Option<SdpOffer>, NetworkError> {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.making_offer || !self.peer_connection.is_stable() {
+                debug!("negotiation already in progress, not creating another offer");
+                return Ok(None);
+            }
+            state.making_offer = true;
+        }
+        let offer = self.peer_connection.create_offer().await;
+        self.state.borrow_mut().making_offer = false;
+        let mut offer = offer?;
+        self.advertise_compression(&mut offer.sdp);
+        Ok(Some(offer))
+    }
+
+    /// Answers the offer, returns `None` if it collided with our own offer and was ignored
+    pub(crate) async fn accept_offer(
+        &self,
+        mut offer: SdpOffer,
+    ) -> Result<Option<SdpAnswer>, NetworkError> {
+        let collision = {
+            let mut state = self.state.borrow_mut();
+            let ready_for_offer = !state.making_offer
+                && (self.peer_connection.is_stable() || state.setting_remote_answer_pending);
+            state.ignore_offer = !state.polite && !ready_for_offer;
+            if state.ignore_offer {
+                debug!("offer collided with our own, ignoring it");
+                return Ok(None);
+            }
+            !ready_for_offer
+        };
+        if collision && !self.peer_connection.is_stable() {
+            debug!("offer collided with our own, rolling back");
+            self.peer_connection.rollback().await?;
+        }
+        let max_message_size = fragmentation::max_message_size(&offer.sdp);
+        let accepts_compression = compression::take_advertisement(&mut offer.sdp);
+        let mut answer = self.peer_connection.create_answer(offer).await?;
+        {
+            let mut state = self.state.borrow_mut();
+            state.remote_max_message_size = Some(max_message_size);
+            state.remote_accepts_compression = accepts_compression;
+        }
+        self.advertise_compression(&mut answer.sdp);
+        Ok(Some(answer))
+    }
+
+    pub(crate) async fn accept_answer(&self, mut answer: SdpAnswer) -> Result<(), NetworkError> {
+        self.state.borrow_mut().setting_remote_answer_pending = true;
+        let max_message_size = fragmentation::max_message_size(&answer.sdp);
+        let accepts_compression = compression::take_advertisement(&mut answer.sdp);
+        let result = self.peer_connection.set_remote_answer(answer).await;
+        let mut state = self.state.borrow_mut();
+        state.setting_remote_answer_pending = false;
+        if result.is_ok() {
+            state.remote_max_message_size = Some(max_message_size);
+            state.remote_accepts_compression = accepts_compression;
+        }
+        Ok(result?)
+    }
+
+    /// Candidates of an ignored offer are expected to fail, so their errors are dropped
+    pub(crate) async fn add_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<(), NetworkError> {
+        match self.peer_connection.add_ice_candidate(ice_candidate).await {
+            Err(error) if self.state.borrow().ignore_offer => {
+                debug!("ignoring candidate of an ignored offer: {:?}", error);
+                Ok(())
+            }
+            result => Ok(result?),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod test {
+    use super::*;
+    use crate::{ChannelConfig, ConnectionType};
+    use tokio::task::LocalSet;
+
+    async fn negotiation(polite: bool) -> Negotiation {
+        let peer_connection = PeerConnection::new(&ConnectionType::Local).await.unwrap();
+        peer_connection
+            .create_data_channel("test", &ChannelConfig::default())
+            .await
+            .unwrap();
+        Negotiation::new(peer_connection, polite, Rc::default())
+    }
+
+    #[tokio::test]
+    async fn impolite_peer_ignores_colliding_offer() {
+        LocalSet::new()
+            .run_until(async {
+                let polite = negotiation(true).await;
+                let impolite = negotiation(false).await;
+                let polite_offer = polite.create_offer().await.unwrap().unwrap();
+                impolite.create_offer().await.unwrap().unwrap();
+
+                assert_eq!(impolite.accept_offer(polite_offer).await.unwrap(), None);
+                // candidates of the ignored offer can't be added, but that's not an error
+                let candidate = IceCandidate {
+                    candidate: "candidate:1 1 udp 2122260223 192.168.1.2 1 typ host".to_string(),
+                    sdp_mid: Some("0".to_string()),
+                    sdp_m_line_index: Some(0),
+                };
+                impolite.add_ice_candidate(candidate).await.unwrap();
+                assert!(!impolite.peer_connection().is_stable());
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn polite_peer_rolls_back_colliding_offer() {
+        LocalSet::new()
+            .run_until(async {
+                let polite = negotiation(true).await;
+                let impolite = negotiation(false).await;
+                let offer = impolite.create_offer().await.unwrap().unwrap();
+                let answer = polite.accept_offer(offer).await.unwrap().unwrap();
+                impolite.accept_answer(answer).await.unwrap();
+
+                polite.create_offer().await.unwrap().unwrap();
+                let impolite_offer = impolite.create_offer().await.unwrap().unwrap();
+                let answer = polite.accept_offer(impolite_offer).await.unwrap().unwrap();
+                impolite.accept_answer(answer).await.unwrap();
+                assert!(polite.peer_connection().is_stable());
+                assert!(impolite.peer_connection().is_stable());
+
+                // both peers can renegotiate again
+                for (offerer, answerer) in [(&polite, &impolite), (&impolite, &polite)] {
+                    let offer = offerer.create_offer().await.unwrap().unwrap();
+                    let answer = answerer.accept_offer(offer).await.unwrap().unwrap();
+                    offerer.accept_answer(answer).await.unwrap();
+                }
+                assert!(polite.peer_connection().is_stable());
+                assert!(impolite.peer_connection().is_stable());
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn connection_can_be_renegotiated_by_either_peer() {
+        LocalSet::new()
+            .run_until(async {
+                let polite = negotiation(true).await;
+                let impolite = negotiation(false).await;
+                for (offerer, answerer) in [(&impolite, &polite), (&polite, &impolite)] {
+                    let offer = offerer.create_offer().await.unwrap().unwrap();
+                    assert_eq!(offerer.create_offer().await.unwrap(), None);
+                    let answer = answerer.accept_offer(offer).await.unwrap().unwrap();
+                    offerer.accept_answer(answer).await.unwrap();
+                }
+
+                assert!(polite.peer_connection().is_stable());
+                assert!(impolite.peer_connection().is_stable());
+            })
+            .await;
+    }
+}
@@ -1,36 +1,46 @@
+use crate::fragmentation::{Reassembler, Received};
 use crate::one_to_many::{websocket_handler, NetworkManager};
-use crate::utils::IceCandidate;
-use js_sys::JsString;
+use crate::platform::{spawn_local, DataChannel, PeerConnection, SignalingSocket};
+use crate::{NetworkError, NetworkEvent};
 use log::{debug, error, info};
-use rusty_games_protocol::one_to_many::SignalMessage;
+use rusty_games_protocol::signal::{Capability, SignalMessage, Topology, PROTOCOL_VERSION};
 use rusty_games_protocol::{SessionId, UserId};
-use wasm_bindgen::closure::Closure;
-use wasm_bindgen::{JsCast, JsValue};
-use web_sys::{
-    MessageEvent, RtcDataChannel, RtcDataChannelEvent, RtcPeerConnection,
-    RtcPeerConnectionIceEvent, WebSocket,
-};
 
 /// also calls:
 /// * set_data_channel_on_open
 /// * set_data_channel_on_message
-/// * set_data_channel_on_error
+/// * set_data_channel_on_close
 pub(crate) fn set_peer_connection_on_data_channel(
-    peer_connection: &RtcPeerConnection,
+    peer_connection: &PeerConnection,
     client_id: UserId,
     network_manager: NetworkManager,
     on_open_callback: impl FnMut(UserId) + Clone + 'static,
     on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
 ) {
-    let on_open_callback_clone = on_open_callback;
-    let on_message_callback_clone = on_message_callback;
-    let on_datachannel = Closure::wrap(Box::new(move |data_channel_event: RtcDataChannelEvent| {
+    peer_connection.on_data_channel(move |data_channel| {
         info!("received data channel");
-        let data_channel = data_channel_event.channel();
 
-        set_data_channel_on_open(&data_channel, client_id, on_open_callback_clone.clone());
-        set_data_channel_on_error(&data_channel);
-        set_data_channel_on_message(&data_channel, client_id, on_message_callback_clone.clone());
+        // the first channel is the one every connection starts with,
+        // any other was opened later with open_channel
+        let connection_has_channel = network_manager
+            .inner
+            .borrow()
+            .connections
+            .get(&client_id)
+            .map(|connection| connection.data_channel.is_some());
+        if connection_has_channel == Some(true) {
+            network_manager.add_channel(client_id, data_channel);
+            return;
+        }
+
+        set_data_channel_on_open(&data_channel, client_id, on_open_callback.clone());
+        set_data_channel_on_message(
+            &data_channel,
+            client_id,
+            network_manager.clone(),
+            on_message_callback.clone(),
+        );
+        set_data_channel_on_close(&data_channel, client_id, network_manager.clone());
 
         network_manager
             .inner
@@ -39,190 +49,178 @@ pub(crate) fn set_peer_connection_on_data_channel(
             .get_mut(&client_id)
             .unwrap()
             .data_channel = Some(data_channel);
-    }) as Box<dyn FnMut(RtcDataChannelEvent)>);
-    peer_connection.set_ondatachannel(Some(on_datachannel.as_ref().unchecked_ref()));
-    on_datachannel.forget();
+    });
 }
 
 /// handle message sent by signaling server
 pub(crate) fn set_websocket_on_message(
-    websocket: &WebSocket,
+    signaling_socket: &SignalingSocket,
     network_manager: NetworkManager,
     on_open_callback: impl FnMut(UserId) + Clone + 'static,
     on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
     is_host: bool,
 ) {
-    let websocket_clone = websocket.clone();
-    let _on_open_callback_clone = on_open_callback.clone();
-    let _on_message_callback_clone = on_message_callback.clone();
-    let onmessage_callback = Closure::wrap(Box::new(move |ev: MessageEvent| {
-        if let Ok(message) = ev.data().dyn_into::<JsString>() {
-            match serde_json_wasm::from_str(&String::from(message)) {
-                Ok(message) => {
-                    let network_manager = network_manager.clone();
-                    let websocket = websocket_clone.clone();
-                    let on_open_callback_clone = on_open_callback.clone();
-                    let on_message_callback_clone = on_message_callback.clone();
-                    wasm_bindgen_futures::spawn_local(async move {
-                        websocket_handler::handle_websocket_message(
-                            network_manager,
-                            message,
-                            websocket,
-                            on_open_callback_clone,
-                            on_message_callback_clone,
-                            is_host,
-                        )
-                        .await
-                        .unwrap_or_else(|error| {
-                            error!("error handling websocket message: {:?}", error);
-                        })
-                    });
-                }
-                Err(_) => {
-                    error!("failed to deserialize onmessage callback content.");
-                }
-            }
-        }
-    }) as Box<dyn FnMut(MessageEvent)>);
-    websocket.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
-    onmessage_callback.forget();
-}
-
-/// once websocket is open, send a request to start or join a session
-pub(crate) fn set_websocket_on_open(websocket: &WebSocket, session_id: SessionId, is_host: bool) {
-    {
-        let websocket_clone = websocket.clone();
-        let onopen_callback = Closure::wrap(Box::new(move |_| {
-            let signal_message = SignalMessage::SessionJoin(session_id.clone(), is_host);
-            let signal_message = serde_json_wasm::to_string(&signal_message)
-                .expect("failed serializing SignalMessage");
-            websocket_clone
-                .send_with_str(&signal_message)
-                .expect("failed sending start-or-join message to the websocket");
-        }) as Box<dyn FnMut(JsValue)>);
-        websocket.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-        onopen_callback.forget();
-    }
+    let signaling_socket_clone = signaling_socket.clone();
+    signaling_socket.on_message(move |message| {
+        let network_manager = network_manager.clone();
+        let signaling_socket = signaling_socket_clone.clone();
+        let on_open_callback_clone = on_open_callback.clone();
+        let on_message_callback_clone = on_message_callback.clone();
+        spawn_local(async move {
+            websocket_handler::handle_websocket_message(
+                network_manager.clone(),
+                message,
+                signaling_socket,
+                on_open_callback_clone,
+                on_message_callback_clone,
+                is_host,
+            )
+            .await
+            .unwrap_or_else(|error| {
+                error!("error handling websocket message: {:?}", error);
+                network_manager.report_error(error);
+            })
+        });
+    });
 }
 
-pub(crate) fn set_peer_connection_on_negotiation_needed(peer_connection: &RtcPeerConnection) {
-    let on_negotiation_needed = Closure::wrap(Box::new(move || {
-        debug!("on negotiation needed event occurred");
-    }) as Box<dyn FnMut()>);
-    peer_connection.set_onnegotiationneeded(Some(on_negotiation_needed.as_ref().unchecked_ref()));
-    on_negotiation_needed.forget();
+pub(crate) fn set_websocket_on_close(
+    signaling_socket: &SignalingSocket,
+    network_manager: NetworkManager,
+) {
+    signaling_socket.on_close(move || {
+        error!("connection with signaling server closed");
+        let network_manager = network_manager.clone();
+        // queued after handlers of already received messages, so that the error
+        // explaining why the server closed the connection is reported first
+        spawn_local(async move {
+            network_manager.report_error(NetworkError::SignalingClosed);
+        });
+    });
 }
 
-pub(crate) fn set_peer_connection_on_ice_gathering_state_change(
-    peer_connection: &RtcPeerConnection,
+/// once websocket is open, introduce yourself and send a request to start or join a session
+pub(crate) fn set_websocket_on_open(
+    signaling_socket: &SignalingSocket,
+    session_id: SessionId,
+    topology: Topology,
+    is_host: bool,
+    network_manager: NetworkManager,
 ) {
-    let peer_connection_clone = peer_connection.clone();
-    let on_ice_gathering_state_change = Closure::wrap(Box::new(move || {
-        debug!(
-            "ice gathering state: {:?}",
-            peer_connection_clone.ice_gathering_state()
-        );
-    }) as Box<dyn FnMut()>);
-    peer_connection.set_onicegatheringstatechange(Some(
-        on_ice_gathering_state_change.as_ref().unchecked_ref(),
-    ));
-    on_ice_gathering_state_change.forget();
+    let signaling_socket_clone = signaling_socket.clone();
+    signaling_socket.on_open(move || {
+        let signal_message = SignalMessage::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            topology,
+            capabilities: vec![Capability::TrickleIce, Capability::MessagePack],
+        };
+        let session_join = SignalMessage::SessionJoin(session_id.clone(), is_host);
+        let result = signaling_socket_clone
+            .send(&signal_message)
+            .and_then(|()| signaling_socket_clone.send(&session_join));
+        // e.g. the socket closed right after opening, which fails pending `connect`
+        if let Err(error) = result {
+            error!("failed to join the session: {}", error);
+            network_manager.report_error(error);
+        }
+    });
 }
 
+/// Fragmented messages are reassembled first, those that can't be decoded are reported as errors instead.
+/// Binary messages, pings and pongs are handled by the network manager and never reach the callback.
 pub(crate) fn set_data_channel_on_message(
-    data_channel: &RtcDataChannel,
+    data_channel: &DataChannel,
     client_id: UserId,
+    network_manager: NetworkManager,
     mut on_message_callback: impl FnMut(UserId, String) + 'static,
 ) {
-    let datachannel_on_message = Closure::wrap(Box::new(move |ev: MessageEvent| {
-        if let Some(message) = ev.data().as_string() {
-            debug!(
-                "message from datachannel (will call on_message): {:?}",
-                message
-            );
-            on_message_callback(
-                client_id,
-                message
-                    // this is an ugly fix to the fact, that if you send empty string as message
-                    // webrtc fails with a cryptic "The operation failed for an operation-specific reason"
-                    // message
-                    .strip_prefix('x')
-                    .expect("messages must have a fix-bug x prepended")
-                    .to_string(),
-            );
+    let mut reassembler = Reassembler::default();
+    data_channel.on_message(move |message| {
+        debug!(
+            "message from datachannel (will call on_message): {:?}",
+            message
+        );
+        match reassembler.receive(&message) {
+            Ok(Some(Received::Text(message))) => on_message_callback(client_id, message),
+            Ok(Some(Received::Binary(message))) => {
+                network_manager.receive_binary(client_id, message)
+            }
+            Ok(Some(Received::Ping(ping_id))) => network_manager.send_pong(client_id, ping_id),
+            Ok(Some(Received::Pong(pong))) => network_manager.receive_pong(client_id, pong),
+            Ok(None) => debug!("waiting for the remaining fragments of the message"),
+            Err(error) => network_manager.report_error(error.into()),
         }
-    }) as Box<dyn FnMut(MessageEvent)>);
-    data_channel.set_onmessage(Some(datachannel_on_message.as_ref().unchecked_ref()));
-    datachannel_on_message.forget();
-}
-
-pub(crate) fn set_data_channel_on_error(data_channel: &RtcDataChannel) {
-    let onerror = Closure::wrap(Box::new(move |data_channel_error| {
-        error!("data channel error: {:?}", data_channel_error);
-    }) as Box<dyn FnMut(JsValue)>);
-    data_channel.set_onerror(Some(onerror.as_ref().unchecked_ref()));
-    onerror.forget();
+    });
 }
 
 pub(crate) fn set_data_channel_on_open(
-    data_channel: &RtcDataChannel,
+    data_channel: &DataChannel,
     client_id: UserId,
     mut on_open_callback: impl FnMut(UserId) + 'static,
 ) {
-    let onopen_callback = Closure::wrap(Box::new(move |_| {
+    data_channel.on_open(move || {
         debug!("data channel is now open, calling on_open!");
         on_open_callback(client_id);
-    }) as Box<dyn FnMut(JsValue)>);
-    data_channel.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-    onopen_callback.forget();
+    });
 }
 
-pub(crate) fn set_peer_connection_on_ice_connection_state_change(
-    peer_connection: &RtcPeerConnection,
+pub(crate) fn set_data_channel_on_close(
+    data_channel: &DataChannel,
+    client_id: UserId,
+    network_manager: NetworkManager,
 ) {
-    let peer_connection_clone = peer_connection.clone();
-    let on_ice_connection_state_change = Closure::wrap(Box::new(move || {
-        debug!(
-            "connection state change: {:?}",
-            peer_connection_clone.ice_connection_state()
-        )
-    }) as Box<dyn FnMut()>);
-    peer_connection.set_oniceconnectionstatechange(Some(
-        on_ice_connection_state_change.as_ref().unchecked_ref(),
-    ));
-    on_ice_connection_state_change.forget();
+    data_channel.on_close(move || {
+        debug!("data channel with {:?} closed", client_id);
+        network_manager.emit(NetworkEvent::PeerDisconnected(client_id));
+    });
 }
 
 pub(crate) fn set_peer_connection_on_ice_candidate(
-    peer_connection: &RtcPeerConnection,
+    peer_connection: &PeerConnection,
     client_id: UserId,
-    websocket_clone: WebSocket,
+    signaling_socket_clone: SignalingSocket,
     session_id_clone: SessionId,
 ) {
-    let on_ice_candidate = Closure::wrap(Box::new(move |ev: RtcPeerConnectionIceEvent| {
-        if let Some(candidate) = ev.candidate() {
-            let signaled_candidate = IceCandidate {
-                candidate: candidate.candidate(),
-                sdp_mid: candidate.sdp_mid(),
-                sdp_m_line_index: candidate.sdp_m_line_index(),
-            };
-            debug!("signaled candidate: {:#?}", signaled_candidate);
-            let signaled_candidate = serde_json_wasm::to_string(&signaled_candidate)
-                .expect("failed to serialize IceCandidate");
+    peer_connection.on_ice_candidate(move |signaled_candidate| {
+        debug!("signaled candidate: {:#?}", signaled_candidate);
 
-            let signal_message = SignalMessage::IceCandidate(
-                session_id_clone.clone(),
-                client_id,
-                signaled_candidate,
-            );
-            let signal_message = serde_json_wasm::to_string(&signal_message)
-                .expect("failed to serialize SignalMessage");
+        let signal_message =
+            SignalMessage::IceCandidate(session_id_clone.clone(), client_id, signaled_candidate);
+        signaling_socket_clone
+            .send(&signal_message)
+            .unwrap_or_else(|_| error!("failed to send one of the ICE candidates"));
+    });
+}
 
-            websocket_clone
-                .send_with_str(&signal_message)
-                .unwrap_or_else(|_| error!("failed to send one of the ICE candidates"));
-        }
-    }) as Box<dyn FnMut(RtcPeerConnectionIceEvent)>);
-    peer_connection.set_onicecandidate(Some(on_ice_candidate.as_ref().unchecked_ref()));
-    on_ice_candidate.forget();
+pub(crate) fn set_peer_connection_on_ice_connection_failed(
+    peer_connection: &PeerConnection,
+    client_id: UserId,
+    network_manager: NetworkManager,
+) {
+    peer_connection.on_ice_connection_failed(move || {
+        error!("ICE connection with {:?} failed", client_id);
+        network_manager.report_error(NetworkError::IceFailed(client_id));
+    });
+}
+
+/// renegotiate whenever the connection changes, e.g. a data channel is added
+pub(crate) fn set_peer_connection_on_negotiation_needed(
+    peer_connection: &PeerConnection,
+    client_id: UserId,
+    network_manager: NetworkManager,
+) {
+    peer_connection.on_negotiation_needed(move || {
+        let network_manager = network_manager.clone();
+        spawn_local(async move {
+            websocket_handler::send_offer(&network_manager, client_id)
+                .await
+                .unwrap_or_else(|error| {
+                    error!(
+                        "failed to renegotiate connection with {:?}: {:?}",
+                        client_id, error
+                    );
+                    network_manager.report_error(error);
+                })
+        });
+    });
 }
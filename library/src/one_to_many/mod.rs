@@ -3,7 +3,7 @@ Library module for the one-to-many topology in client-server architecture.
 There can be exactly one instance of [MiniServer] and arbitrary number of [MiniClient]'s
 connected to the same session.
 
-A RtcPeerConnection with an accompanying RtcDataChannel will be established between the [MiniServer]
+A PeerConnection with an accompanying DataChannel will be established between the [MiniServer]
 and each of the [MiniClient]'s. [MiniServer] can decide whether to send a message to a single peer,
 identified by [UserId] returned by signaling server during connection establishment method,
 with [MiniServer::send_message], or to fire to all clients with [MiniServer::send_message_to_all].
@@ -16,20 +16,23 @@ This example shows three peers connecting, with one being a dedicated host.
 Host waits for both peers to connect and only then sends `ping` messages to both
 and clients independently respond with `pong` messages.
 
-```
+```no_run
 use rusty_games_library::one_to_many::{MiniClient, MiniServer};
-use rusty_games_library::ConnectionType;
+use rusty_games_library::{ConnectionType, SessionId, UserId};
 use std::cell::RefCell;
 use std::rc::Rc;
-use rusty_games_protocol::SessionId;
 use web_sys::console;
 
-const SIGNALING_SERVER_URL: &str = "ws://0.0.0.0:9001/one-to-many";
+const SIGNALING_SERVER_URL: &str = "ws://0.0.0.0:9001/signal";
+const STUN_SERVER_URLS: &str = "stun:openrelay.metered.ca:80";
+
+let server_received_message = Rc::new(RefCell::new(false));
+let client_received_message = Rc::new(RefCell::new(false));
 
 let mut server = MiniServer::new(
     SIGNALING_SERVER_URL,
     SessionId::new("dummy-session-id".to_string()),
-    ConnectionType::Stun,
+    ConnectionType::Stun { urls: STUN_SERVER_URLS.to_string() },
 )
 .unwrap();
 let server_open_connections_count = Rc::new(RefCell::new(0));
@@ -37,7 +40,7 @@ let server_open_connections_count = Rc::new(RefCell::new(0));
 let server_clone = server.clone();
 let server_on_open = {
     let server_open_connections_count = server_open_connections_count.clone();
-    move |user_id| {
+    move |user_id: UserId| {
         console::log_1(&format!("connection to user established: {:?}", user_id).into());
         *server_open_connections_count.borrow_mut() += 1;
         if *server_open_connections_count.borrow() == 2 {
@@ -47,7 +50,7 @@ let server_on_open = {
 };
 let server_on_message = {
     let server_received_message = server_received_message.clone();
-    move |user_id, message| {
+    move |user_id: UserId, message: String| {
         console::log_1(
             &format!(
                 "server received message from client {:?}: {}",
@@ -64,16 +67,16 @@ let client_generator = || {
     let mut client = MiniClient::new(
         SIGNALING_SERVER_URL,
         SessionId::new("dummy-session-id".to_string()),
-        ConnectionType::Stun,
+        ConnectionType::Stun { urls: STUN_SERVER_URLS.to_string() },
     )
     .unwrap();
     let client_on_open = |_| { /* do nothing */ };
     let client_clone = client.clone();
     let client_on_message = {
         let client_received_message = client_received_message.clone();
-        move |_, message| {
+        move |_, message: String| {
             console::log_1(&format!("client received message: {}", message).into());
-            client_clone.send_message_to_host("pong!");
+            client_clone.send_message_to_host("pong!").unwrap();
             *client_received_message.borrow_mut() = true;
         }
     };
@@ -87,38 +90,133 @@ client_generator();
 mod callbacks;
 mod websocket_handler;
 
-use crate::one_to_many::callbacks::{set_websocket_on_message, set_websocket_on_open};
-use crate::ConnectionType;
+use crate::candidates::PendingCandidates;
+use crate::channel::SharedCallback;
+use crate::clock::ClockOffset;
+use crate::compression::Compression;
+use crate::connect::ConnectWaiter;
+use crate::events::{EventQueue, EventSink};
+use crate::framing::{Frame, Pong};
+use crate::latency::LatencyTracker;
+use crate::negotiation::Negotiation;
+use crate::one_to_many::callbacks::{
+    set_data_channel_on_message, set_websocket_on_close, set_websocket_on_message,
+    set_websocket_on_open,
+};
+use crate::platform::{self, DataChannel, SignalingSocket};
+use crate::scheduler::{Next, Scheduler};
+use crate::send_queue::{OutgoingMessage, DEFAULT_HIGH_WATER_MARK};
+use crate::timer::IntervalTimer;
+use crate::{
+    ChannelConfig, CompressionStats, Connected, ConnectionStats, ConnectionType, DropPolicy,
+    LatencyStats, NetworkError, NetworkEvent, NetworkEvents, Priority, SignalingError,
+    DEFAULT_PING_INTERVAL,
+};
+use futures::channel::oneshot;
+use futures::future::{self, join_all};
+use log::debug;
+use rusty_games_protocol::rtc::IceCandidate;
+use rusty_games_protocol::signal::{SignalMessage, Topology};
 use rusty_games_protocol::{SessionId, UserId};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use wasm_bindgen::JsValue;
-use web_sys::{RtcDataChannel, RtcPeerConnection, WebSocket};
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 struct Connection {
-    peer_connection: RtcPeerConnection,
-    data_channel: Option<RtcDataChannel>,
+    negotiation: Negotiation,
+    data_channel: Option<DataChannel>,
+    /// Channels opened with [NetworkManager::open_channel] by either peer, by name
+    channels: HashMap<String, DataChannel>,
+    /// Messages sent with a [Priority], waiting for their turn
+    scheduler: Rc<RefCell<Scheduler<OutgoingMessage>>>,
+    latency: Rc<RefCell<LatencyTracker>>,
 }
 
 impl Connection {
-    fn new(peer_connection: RtcPeerConnection, data_channel: Option<RtcDataChannel>) -> Self {
+    fn new(negotiation: Negotiation, data_channel: Option<DataChannel>) -> Self {
         Connection {
-            peer_connection,
+            negotiation,
             data_channel,
+            channels: HashMap::new(),
+            scheduler: Rc::default(),
+            latency: Rc::default(),
+        }
+    }
+
+    fn close(&self) {
+        self.scheduler.borrow_mut().clear();
+        if let Some(data_channel) = &self.data_channel {
+            data_channel.close();
+        }
+        for data_channel in self.channels.values() {
+            data_channel.close();
         }
+        self.negotiation.peer_connection().close();
     }
 }
 
 #[derive(Debug)]
 struct NetworkManagerInner {
     session_id: SessionId,
-    websocket: WebSocket,
+    signaling_socket: SignalingSocket,
     connection_type: ConnectionType,
+    topology: Topology,
     is_host: bool,
     connections: HashMap<UserId, Connection>,
+    pending_candidates: HashMap<UserId, PendingCandidates>,
+    signaling_error: Option<SignalingError>,
+    connect_waiter: ConnectWaiter,
+    event_sink: Option<EventSink>,
+    on_message: Option<SharedCallback<(UserId, String)>>,
+    on_channel_open: Option<SharedCallback<(UserId, String)>>,
+    on_binary_message: Option<SharedCallback<(UserId, Vec<u8>)>>,
+    /// Channels used by the library itself, e.g. for replication, by name,
+    /// their messages go to the handler instead of `on_message`
+    channel_handlers: HashMap<String, SharedCallback<(UserId, String)>>,
+    high_water_mark: usize,
+    /// Shared with negotiations of all connections
+    compression: Rc<Compression>,
+    ping_timer: Rc<IntervalTimer>,
+    /// Timers of features built on top of the manager, e.g. replication, stopped on close
+    timers: Vec<Rc<IntervalTimer>>,
+    on_latency_update: Option<SharedCallback<(UserId, LatencyStats)>>,
+    /// Learned from pongs, as only other peers are told the id
+    own_id: Option<UserId>,
 }
+
+impl NetworkManagerInner {
+    /// Encodes the message for the default data channel with the peer, or the one with the given name
+    fn outgoing_message(
+        &self,
+        user_id: UserId,
+        channel: Option<&str>,
+        frame: Frame,
+    ) -> Result<OutgoingMessage, NetworkError> {
+        let connection = self
+            .connections
+            .get(&user_id)
+            .ok_or(NetworkError::NoConnectionForUser(user_id))?;
+        let data_channel = match channel {
+            None => connection
+                .data_channel
+                .as_ref()
+                .ok_or(NetworkError::DataChannelNotOpen)?,
+            Some(channel) => connection
+                .channels
+                .get(channel)
+                .ok_or_else(|| NetworkError::UnknownChannel(channel.to_string()))?,
+        };
+        OutgoingMessage::new(
+            data_channel,
+            frame,
+            Some(&connection.negotiation),
+            self.high_water_mark,
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct NetworkManager {
     inner: Rc<RefCell<NetworkManagerInner>>,
@@ -129,75 +227,699 @@ impl NetworkManager {
         signaling_server_url: &str,
         session_id: SessionId,
         connection_type: ConnectionType,
+        topology: Topology,
         is_host: bool,
-    ) -> Result<Self, JsValue> {
-        let websocket = WebSocket::new(signaling_server_url)?;
-        websocket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+    ) -> Result<Self, NetworkError> {
+        let signaling_socket = SignalingSocket::new(signaling_server_url)?;
 
         Ok(NetworkManager {
             inner: Rc::new(RefCell::new(NetworkManagerInner {
                 session_id,
-                websocket,
+                signaling_socket,
                 connection_type,
+                topology,
                 is_host,
                 connections: HashMap::new(),
+                pending_candidates: HashMap::new(),
+                signaling_error: None,
+                connect_waiter: ConnectWaiter::default(),
+                event_sink: None,
+                on_message: None,
+                on_channel_open: None,
+                on_binary_message: None,
+                channel_handlers: HashMap::new(),
+                high_water_mark: DEFAULT_HIGH_WATER_MARK,
+                compression: Rc::default(),
+                ping_timer: Rc::new(IntervalTimer::new(Some(DEFAULT_PING_INTERVAL))),
+                timers: Vec::new(),
+                on_latency_update: None,
+                own_id: None,
             })),
         })
     }
 
     pub(crate) fn start(
         &mut self,
-        on_open_callback: impl FnMut(UserId) + Clone + 'static,
+        mut on_open_callback: impl FnMut(UserId) + Clone + 'static,
         on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
-    ) -> Result<(), JsValue> {
-        let websocket = self.inner.borrow().websocket.clone();
+    ) -> Result<(), NetworkError> {
+        let signaling_socket = self.inner.borrow().signaling_socket.clone();
         let session_id = self.inner.borrow().session_id.clone();
+        let topology = self.inner.borrow().topology;
         let is_host = self.inner.borrow().is_host;
+        let connect_waiter = self.inner.borrow().connect_waiter.clone();
+        let on_open_callback = move |user_id| {
+            connect_waiter.resolve(Ok(Connected { peer_id: user_id }));
+            on_open_callback(user_id);
+        };
+        self.inner.borrow_mut().on_message = Some(SharedCallback::new({
+            let mut on_message_callback = on_message_callback.clone();
+            move |(user_id, message)| on_message_callback(user_id, message)
+        }));
 
-        set_websocket_on_open(&websocket, session_id, is_host);
+        set_websocket_on_open(
+            &signaling_socket,
+            session_id,
+            topology,
+            is_host,
+            self.clone(),
+        );
         set_websocket_on_message(
-            &websocket,
+            &signaling_socket,
             self.clone(),
             on_open_callback,
             on_message_callback,
             is_host,
         );
+        set_websocket_on_close(&signaling_socket, self.clone());
+        self.start_pinging();
+
+        Ok(())
+    }
+
+    pub(crate) async fn connect(
+        &mut self,
+        on_open_callback: impl FnMut(UserId) + Clone + 'static,
+        on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
+        timeout: Duration,
+    ) -> Result<Connected, NetworkError> {
+        let connect_waiter = self.inner.borrow().connect_waiter.clone();
+        connect_waiter
+            .wait(timeout, || {
+                self.start(on_open_callback, on_message_callback)
+            })
+            .await
+    }
+
+    pub(crate) fn start_with_events(&mut self) -> Result<NetworkEvents, NetworkError> {
+        let (event_sender, events) = NetworkEvents::channel();
+        self.start_with_sink(EventSink::Stream(event_sender))?;
+        Ok(events)
+    }
+
+    pub(crate) fn start_with_event_queue(
+        &mut self,
+        capacity: usize,
+        drop_policy: DropPolicy,
+    ) -> Result<(), NetworkError> {
+        self.start_with_sink(EventSink::Queue(EventQueue::new(capacity, drop_policy)))
+    }
+
+    pub(crate) fn poll_events(&self) -> impl Iterator<Item = NetworkEvent> {
+        self.inner
+            .borrow_mut()
+            .event_sink
+            .as_mut()
+            .map(EventSink::drain)
+            .into_iter()
+            .flatten()
+    }
+
+    fn start_with_sink(&mut self, event_sink: EventSink) -> Result<(), NetworkError> {
+        self.inner.borrow_mut().event_sink = Some(event_sink);
+
+        let on_open_callback = {
+            let network_manager = self.clone();
+            move |user_id| network_manager.emit(NetworkEvent::PeerConnected(user_id))
+        };
+        let on_message_callback = {
+            let network_manager = self.clone();
+            move |user_id, message| network_manager.emit(NetworkEvent::Message(user_id, message))
+        };
+        self.start(on_open_callback, on_message_callback)
+    }
+
+    /// Fails pending [NetworkManager::connect] call, if any
+    pub(crate) fn report_error(&self, error: NetworkError) {
+        let connect_waiter = self.inner.borrow().connect_waiter.clone();
+        connect_waiter.resolve(Err(error.clone()));
+        self.emit(NetworkEvent::Error(error));
+    }
+
+    /// Delivers the event if [NetworkManager::start_with_events]
+    /// or [NetworkManager::start_with_event_queue] was used
+    pub(crate) fn emit(&self, event: NetworkEvent) {
+        if let Some(event_sink) = &mut self.inner.borrow_mut().event_sink {
+            event_sink.send(event);
+        }
+    }
+
+    pub(crate) fn send_message(&self, user_id: UserId, message: &str) -> Result<(), NetworkError> {
+        self.inner
+            .borrow()
+            .outgoing_message(user_id, None, Frame::text(message))?
+            .try_send()
+    }
+
+    pub(crate) fn send_binary_message(
+        &self,
+        user_id: UserId,
+        message: &[u8],
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .borrow()
+            .outgoing_message(user_id, None, Frame::binary(message.to_vec()))?
+            .try_send()
+    }
+
+    pub(crate) async fn send_message_async(
+        &self,
+        user_id: UserId,
+        message: &str,
+    ) -> Result<(), NetworkError> {
+        let outgoing_message =
+            self.inner
+                .borrow()
+                .outgoing_message(user_id, None, Frame::text(message))?;
+        outgoing_message.send().await
+    }
+
+    fn start_pinging(&self) {
+        let ping_timer = self.inner.borrow().ping_timer.clone();
+        let network_manager = self.clone();
+        ping_timer.start(move || network_manager.send_pings());
+    }
+
+    /// Pings every peer over the data channel the connection was opened with
+    fn send_pings(&self) {
+        let now = platform::now();
+        let connections: Vec<(UserId, Connection)> = self
+            .inner
+            .borrow()
+            .connections
+            .iter()
+            .map(|(user_id, connection)| (*user_id, connection.clone()))
+            .collect();
+        for (user_id, connection) in connections {
+            let Some(data_channel) = &connection.data_channel else {
+                continue;
+            };
+            let expired = {
+                let mut latency = connection.latency.borrow_mut();
+                let ping_id = latency.next_ping_id();
+                if data_channel.send(&Frame::ping(ping_id).encode()).is_ok() {
+                    latency.sent(ping_id, now);
+                }
+                latency.expire(now)
+            };
+            if expired {
+                self.latency_updated(user_id, &connection);
+            }
+        }
+    }
+
+    pub(crate) fn send_pong(&self, user_id: UserId, ping_id: u32) {
+        let data_channel = self
+            .inner
+            .borrow()
+            .connections
+            .get(&user_id)
+            .and_then(|connection| connection.data_channel.clone());
+        let pong = Pong {
+            ping_id,
+            time: platform::now().as_micros() as u64,
+            pinging_peer: user_id,
+        };
+        let result = data_channel
+            .ok_or(NetworkError::DataChannelNotOpen)
+            .and_then(|data_channel| data_channel.send(&Frame::pong(&pong).encode()));
+        if let Err(error) = result {
+            debug!("failed to answer ping of {:?}: {}", user_id, error);
+        }
+    }
+
+    pub(crate) fn receive_pong(&self, user_id: UserId, pong: Pong) {
+        let connection = {
+            let mut inner = self.inner.borrow_mut();
+            inner.own_id = Some(pong.pinging_peer);
+            inner.connections.get(&user_id).cloned()
+        };
+        if let Some(connection) = connection {
+            let remote_time = Duration::from_micros(pong.time);
+            let updated = connection
+                .latency
+                .borrow_mut()
+                .pong(pong.ping_id, remote_time, platform::now())
+                .is_some();
+            if updated {
+                self.latency_updated(user_id, &connection);
+            }
+        }
+    }
+
+    fn latency_updated(&self, user_id: UserId, connection: &Connection) {
+        let stats = connection.latency.borrow().stats();
+        let on_latency_update = self.inner.borrow().on_latency_update.clone();
+        if let (Some(stats), Some(on_latency_update)) = (stats, on_latency_update) {
+            on_latency_update.call((user_id, stats));
+        }
+    }
+
+    /// Restarts pinging with the new interval if already started
+    pub(crate) fn set_ping_interval(&self, interval: Option<Duration>) {
+        let ping_timer = self.inner.borrow().ping_timer.clone();
+        ping_timer.set_interval(interval);
+        if ping_timer.is_started() {
+            self.start_pinging();
+        }
+    }
+
+    pub(crate) fn latency(&self, user_id: UserId) -> Option<LatencyStats> {
+        self.inner
+            .borrow()
+            .connections
+            .get(&user_id)?
+            .latency
+            .borrow()
+            .stats()
+    }
+
+    /// Clients synchronize to the host, peers of other topologies to the one with the lowest id,
+    /// which may change as peers come and go
+    pub(crate) fn clock_offset(&self) -> Option<ClockOffset> {
+        let inner = self.inner.borrow();
+        let reference = match inner.topology {
+            Topology::OneToMany if inner.is_host => return Some(ClockOffset::default()),
+            // host is the only peer a client connects with
+            Topology::OneToMany => inner.connections.keys().next().copied()?,
+            _ => {
+                let own_id = inner.own_id?;
+                let lowest = inner
+                    .connections
+                    .keys()
+                    .copied()
+                    .min_by_key(|user_id| user_id.into_inner());
+                match lowest {
+                    Some(lowest) if lowest.into_inner() < own_id.into_inner() => lowest,
+                    _ => return Some(ClockOffset::default()),
+                }
+            }
+        };
+        let clock_offset = inner
+            .connections
+            .get(&reference)?
+            .latency
+            .borrow()
+            .clock_offset();
+        clock_offset
+    }
+
+    pub(crate) fn estimated_remote_time(&self) -> Option<Duration> {
+        self.clock_offset()
+            .map(|clock_offset| clock_offset.to_remote(platform::now()))
+    }
+
+    pub(crate) fn on_latency_update(
+        &self,
+        mut callback: impl FnMut(UserId, LatencyStats) + 'static,
+    ) {
+        self.inner.borrow_mut().on_latency_update =
+            Some(SharedCallback::new(move |(user_id, stats)| {
+                callback(user_id, stats)
+            }));
+    }
+
+    pub(crate) fn set_high_water_mark(&self, high_water_mark: usize) {
+        self.inner.borrow_mut().high_water_mark = high_water_mark;
+    }
+
+    pub(crate) fn set_compression_threshold(&self, threshold: Option<usize>) {
+        self.inner.borrow().compression.set_threshold(threshold);
+    }
 
+    pub(crate) fn compression_stats(&self) -> CompressionStats {
+        self.inner.borrow().compression.stats()
+    }
+
+    /// Queues the message for the peer, a message with the same `key` that is still queued
+    /// with the same priority is dropped, as the new one supersedes it
+    pub(crate) fn schedule_message(
+        &self,
+        user_id: UserId,
+        message: &str,
+        priority: Priority,
+        key: Option<&str>,
+    ) -> Result<(), NetworkError> {
+        let (outgoing_message, scheduler) = {
+            let inner = self.inner.borrow();
+            let outgoing_message = inner.outgoing_message(user_id, None, Frame::text(message))?;
+            (
+                outgoing_message,
+                inner.connections[&user_id].scheduler.clone(),
+            )
+        };
+        let start_sending = {
+            let mut scheduler = scheduler.borrow_mut();
+            scheduler.push(
+                priority,
+                key.map(str::to_string),
+                outgoing_message.length(),
+                outgoing_message,
+            );
+            if let Some(waiting) = scheduler.waiting.take() {
+                let _ = waiting.send(());
+            }
+            !std::mem::replace(&mut scheduler.sending, true)
+        };
+        if start_sending {
+            let network_manager = self.clone();
+            platform::spawn_local(async move {
+                network_manager.send_scheduled(scheduler).await;
+            });
+        }
         Ok(())
     }
 
-    pub(crate) fn send_message(&self, user_id: UserId, message: &str) -> Result<(), JsValue> {
+    /// Sends queued messages of a single peer until there are none left
+    async fn send_scheduled(&self, scheduler: Rc<RefCell<Scheduler<OutgoingMessage>>>) {
+        loop {
+            let next = scheduler.borrow_mut().next(platform::now());
+            match next {
+                Next::Send(outgoing_message) => {
+                    if let Err(error) = outgoing_message.send().await {
+                        self.report_error(error);
+                    }
+                }
+                Next::Wait(duration) => {
+                    let (sender, receiver) = oneshot::channel();
+                    scheduler.borrow_mut().waiting = Some(sender);
+                    future::select(Box::pin(platform::sleep(duration)), receiver).await;
+                }
+                Next::Idle => break,
+            }
+        }
+        scheduler.borrow_mut().sending = false;
+    }
+
+    pub(crate) fn set_bandwidth_budget(
+        &self,
+        user_id: UserId,
+        bytes_per_second: Option<usize>,
+    ) -> Result<(), NetworkError> {
         self.inner
             .borrow()
             .connections
             .get(&user_id)
-            .ok_or_else(|| JsValue::from_str(&format!("no connection for user {}", user_id)))?
-            .data_channel
-            .as_ref()
-            .ok_or_else(|| {
-                JsValue::from_str(&format!("no data channel setup yet for user {}", user_id))
-            })?
-            // this is an ugly fix to the fact, that if you send empty string as message
-            // webrtc fails with a cryptic "The operation failed for an operation-specific reason"
-            // message
-            .send_with_str(&format!("x{}", message))
-    }
-
-    pub(crate) fn send_message_to_all(&self, message: &str) {
-        for data_channel in self
+            .ok_or(NetworkError::NoConnectionForUser(user_id))?
+            .scheduler
+            .borrow_mut()
+            .set_budget(bytes_per_second, platform::now());
+        Ok(())
+    }
+
+    pub(crate) fn send_message_on_channel(
+        &self,
+        user_id: UserId,
+        channel: &str,
+        message: &str,
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .borrow()
+            .outgoing_message(user_id, Some(channel), Frame::text(message))?
+            .try_send()
+    }
+
+    pub(crate) fn max_message_size(&self, user_id: UserId) -> Result<usize, NetworkError> {
+        self.negotiation(user_id)?
+            .max_message_size()
+            .ok_or(NetworkError::DataChannelNotOpen)
+    }
+
+    pub(crate) async fn stats(&self, user_id: UserId) -> Result<ConnectionStats, NetworkError> {
+        let negotiation = self.negotiation(user_id)?;
+        Ok(negotiation.peer_connection().stats().await?)
+    }
+
+    pub(crate) fn send_message_to_all(
+        &self,
+        message: &str,
+    ) -> HashMap<UserId, Result<(), NetworkError>> {
+        let user_ids: Vec<UserId> = self.inner.borrow().connections.keys().copied().collect();
+        user_ids
+            .into_iter()
+            .map(|user_id| (user_id, self.send_message(user_id, message)))
+            .collect()
+    }
+
+    /// Waits for each peer separately, so that a slow one doesn't hold back the others
+    pub(crate) async fn send_message_to_all_async(
+        &self,
+        message: &str,
+    ) -> HashMap<UserId, Result<(), NetworkError>> {
+        let user_ids = self.user_ids();
+        let results = join_all(
+            user_ids
+                .iter()
+                .map(|user_id| self.send_message_async(*user_id, message)),
+        )
+        .await;
+        user_ids.into_iter().zip(results).collect()
+    }
+
+    pub(crate) fn disconnect(&self, user_id: UserId) -> Result<(), NetworkError> {
+        let connection = self
             .inner
+            .borrow_mut()
+            .connections
+            .remove(&user_id)
+            .ok_or(NetworkError::NoConnectionForUser(user_id))?;
+        self.inner.borrow_mut().pending_candidates.remove(&user_id);
+        connection.close();
+        Ok(())
+    }
+
+    pub(crate) fn close(&self) {
+        let (session_id, signaling_socket, connections, connect_waiter) = {
+            let mut inner = self.inner.borrow_mut();
+            inner.event_sink = None;
+            inner.on_message = None;
+            inner.on_channel_open = None;
+            inner.on_binary_message = None;
+            inner.channel_handlers.clear();
+            inner.on_latency_update = None;
+            inner.pending_candidates.clear();
+            inner.ping_timer.stop();
+            for timer in std::mem::take(&mut inner.timers) {
+                timer.stop();
+            }
+            (
+                inner.session_id.clone(),
+                inner.signaling_socket.clone(),
+                std::mem::take(&mut inner.connections),
+                inner.connect_waiter.clone(),
+            )
+        };
+        for connection in connections.values() {
+            connection.close();
+        }
+        if let Err(error) = signaling_socket.send(&SignalMessage::SessionLeave(session_id)) {
+            debug!("failed to leave signaling session: {}", error);
+        }
+        signaling_socket.close();
+        connect_waiter.resolve(Err(NetworkError::SignalingClosed));
+    }
+
+    pub(crate) fn signaling_error(&self) -> Option<SignalingError> {
+        self.inner.borrow().signaling_error.clone()
+    }
+
+    /// Peers of all connections, there is at most one for a [MiniClient]
+    pub(crate) fn user_ids(&self) -> Vec<UserId> {
+        self.inner.borrow().connections.keys().copied().collect()
+    }
+
+    pub(crate) fn on_channel_open(&self, callback: impl FnMut(UserId, String) + 'static) {
+        let mut callback = callback;
+        self.inner.borrow_mut().on_channel_open =
+            Some(SharedCallback::new(move |(user_id, channel)| {
+                callback(user_id, channel)
+            }));
+    }
+
+    pub(crate) fn on_binary_message(&self, callback: impl FnMut(UserId, Vec<u8>) + 'static) {
+        let mut callback = callback;
+        self.inner.borrow_mut().on_binary_message =
+            Some(SharedCallback::new(move |(user_id, message)| {
+                callback(user_id, message)
+            }));
+    }
+
+    pub(crate) fn receive_binary(&self, user_id: UserId, message: Vec<u8>) {
+        let on_binary_message = self.inner.borrow().on_binary_message.clone();
+        match on_binary_message {
+            Some(on_binary_message) => on_binary_message.call((user_id, message)),
+            None => debug!(
+                "dropping binary message from {:?} without a callback",
+                user_id
+            ),
+        }
+    }
+
+    /// Stops the timer when the manager closes, along with the callback it holds
+    pub(crate) fn stop_on_close(&self, timer: Rc<IntervalTimer>) {
+        self.inner.borrow_mut().timers.push(timer);
+    }
+
+    /// Messages of channels named `name` are passed to the handler instead of `on_message`,
+    /// and their opening isn't announced to the application
+    pub(crate) fn handle_channel(&self, name: &str, handler: impl FnMut(UserId, String) + 'static) {
+        let mut handler = handler;
+        self.inner.borrow_mut().channel_handlers.insert(
+            name.to_string(),
+            SharedCallback::new(move |(user_id, message)| handler(user_id, message)),
+        );
+    }
+
+    pub(crate) fn has_channel(&self, user_id: UserId, name: &str) -> bool {
+        self.inner
             .borrow()
             .connections
-            .values()
-            .filter_map(|connection| connection.data_channel.as_ref())
+            .get(&user_id)
+            .is_some_and(|connection| connection.channels.contains_key(name))
+    }
+
+    /// Whether the data channel the connection with the peer was opened with is open
+    pub(crate) fn is_open(&self, user_id: UserId) -> bool {
+        self.inner
+            .borrow()
+            .connections
+            .get(&user_id)
+            .and_then(|connection| connection.data_channel.as_ref())
+            .is_some_and(DataChannel::is_open)
+    }
+
+    /// Opens an additional data channel with the peer and renegotiates the connection.
+    /// Opening a channel under a name that's already in use replaces the old channel,
+    /// which is how parameters of a channel are changed.
+    pub(crate) async fn open_channel(
+        &self,
+        user_id: UserId,
+        name: &str,
+        config: ChannelConfig,
+    ) -> Result<(), NetworkError> {
+        let negotiation = self.negotiation(user_id)?;
+        let data_channel = negotiation
+            .peer_connection()
+            .create_data_channel(name, &config)
+            .await?;
+        self.add_channel(user_id, data_channel);
+        // browsers only need negotiation for the first data channel of a connection,
+        // so it's started explicitly instead of waiting for negotiationneeded
+        websocket_handler::send_offer(self, user_id).await
+    }
+
+    /// Starts handling a channel opened with [NetworkManager::open_channel] by either peer
+    pub(crate) fn add_channel(&self, user_id: UserId, data_channel: DataChannel) {
+        let name = data_channel.label();
+        let handler = self.inner.borrow().channel_handlers.get(&name).cloned();
+        if let Some(handler) = handler {
+            set_data_channel_on_message(
+                &data_channel,
+                user_id,
+                self.clone(),
+                move |user_id, message| handler.call((user_id, message)),
+            );
+        } else {
+            if let Some(on_message) = self.inner.borrow().on_message.clone() {
+                set_data_channel_on_message(
+                    &data_channel,
+                    user_id,
+                    self.clone(),
+                    move |user_id, message| on_message.call((user_id, message)),
+                );
+            }
+            let network_manager = self.clone();
+            let name = name.clone();
+            data_channel.on_open(move || {
+                debug!("channel {} with {:?} is now open", name, user_id);
+                let on_channel_open = network_manager.inner.borrow().on_channel_open.clone();
+                if let Some(on_channel_open) = on_channel_open {
+                    on_channel_open.call((user_id, name.clone()));
+                }
+                network_manager.emit(NetworkEvent::ChannelOpened(user_id, name.clone()));
+            });
+        }
+
+        {
+            let network_manager = self.clone();
+            let closed = data_channel.clone();
+            data_channel.on_close(move || {
+                debug!("channel {} with {:?} closed", closed.label(), user_id);
+                network_manager.remove_channel(user_id, &closed);
+            });
+        }
+
+        let replaced = match self.inner.borrow_mut().connections.get_mut(&user_id) {
+            Some(connection) => connection.channels.insert(name, data_channel),
+            // peer disconnected in the meantime
+            None => Some(data_channel),
+        };
+        if let Some(replaced) = replaced {
+            replaced.close();
+        }
+    }
+
+    /// Forgets a channel closed by the peer, along with its handler
+    /// once no other peer has a channel with the same name
+    fn remove_channel(&self, user_id: UserId, data_channel: &DataChannel) {
+        let name = data_channel.label();
         {
-            // TODO: some may fail, should we return a list results?
-            let _ = data_channel
-                // this is an ugly fix to the fact, that if you send empty string as message
-                // webrtc fails with a cryptic "The operation failed for an operation-specific reason"
-                // message
-                .send_with_str(&format!("x{}", message));
+            let mut inner = self.inner.borrow_mut();
+            if let Some(connection) = inner.connections.get_mut(&user_id) {
+                if connection
+                    .channels
+                    .get(&name)
+                    .is_some_and(|channel| channel.is_same(data_channel))
+                {
+                    connection.channels.remove(&name);
+                }
+            }
+            let in_use = inner
+                .connections
+                .values()
+                .any(|connection| connection.channels.contains_key(&name));
+            if !in_use {
+                inner.channel_handlers.remove(&name);
+            }
         }
+        // frees callbacks of the channel, outside of the one that's running
+        let data_channel = data_channel.clone();
+        platform::spawn_local(async move { data_channel.close() });
+    }
+
+    /// Queues the candidate until remote description of the peer is set,
+    /// returns it if it can be added right away
+    pub(crate) fn push_pending_candidate(
+        &self,
+        user_id: UserId,
+        candidate: IceCandidate,
+    ) -> Option<IceCandidate> {
+        self.inner
+            .borrow_mut()
+            .pending_candidates
+            .entry(user_id)
+            .or_default()
+            .push(candidate)
+    }
+
+    pub(crate) fn negotiation(&self, user_id: UserId) -> Result<Negotiation, NetworkError> {
+        Ok(self
+            .inner
+            .borrow()
+            .connections
+            .get(&user_id)
+            .ok_or(NetworkError::NoConnectionForUser(user_id))?
+            .negotiation
+            .clone())
+    }
+
+    pub(crate) fn pop_pending_candidate(&self, user_id: UserId) -> Option<IceCandidate> {
+        self.inner
+            .borrow_mut()
+            .pending_candidates
+            .entry(user_id)
+            .or_default()
+            .pop()
     }
 }
 
@@ -218,7 +940,7 @@ impl NetworkManager {
 /// This class is a cloneable pointer to the underlying resource and can be cloned freely.
 #[derive(Debug, Clone)]
 pub struct MiniServer {
-    inner: NetworkManager,
+    pub(crate) inner: NetworkManager,
 }
 
 impl MiniServer {
@@ -229,9 +951,15 @@ impl MiniServer {
         signaling_server_url: &str,
         session_id: SessionId,
         connection_type: ConnectionType,
-    ) -> Result<Self, JsValue> {
+    ) -> Result<Self, NetworkError> {
         Ok(MiniServer {
-            inner: NetworkManager::new(signaling_server_url, session_id, connection_type, true)?,
+            inner: NetworkManager::new(
+                signaling_server_url,
+                session_id,
+                connection_type,
+                Topology::OneToMany,
+                true,
+            )?,
         })
     }
 
@@ -243,27 +971,257 @@ impl MiniServer {
         &mut self,
         on_open_callback: impl FnMut(UserId) + Clone + 'static,
         on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
-    ) -> Result<(), JsValue> {
+    ) -> Result<(), NetworkError> {
         self.inner.start(on_open_callback, on_message_callback)
     }
 
+    /// Same as [MiniServer::start], but waits until the first client-peer connects.
+    /// Fails if signaling or ICE fails, or if no client connects within the `timeout`.
+    pub async fn connect(
+        &mut self,
+        on_open_callback: impl FnMut(UserId) + Clone + 'static,
+        on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
+        timeout: Duration,
+    ) -> Result<Connected, NetworkError> {
+        self.inner
+            .connect(on_open_callback, on_message_callback, timeout)
+            .await
+    }
+
+    /// Same as [MiniServer::start], but instead of calling callbacks
+    /// everything that happens on the network is delivered as [NetworkEvents].
+    pub fn start_with_events(&mut self) -> Result<NetworkEvents, NetworkError> {
+        self.inner.start_with_events()
+    }
+
+    /// Same as [MiniServer::start_with_events], but events are kept in a queue
+    /// of at most `capacity` events, drained with [MiniServer::poll_events],
+    /// e.g. once per frame of a game loop.
+    /// Events arriving when the queue is full are handled according to the `drop_policy`.
+    pub fn start_with_event_queue(
+        &mut self,
+        capacity: usize,
+        drop_policy: DropPolicy,
+    ) -> Result<(), NetworkError> {
+        self.inner.start_with_event_queue(capacity, drop_policy)
+    }
+
+    /// Takes all events queued since the last call, oldest first.
+    /// Yields nothing unless started with [MiniServer::start_with_event_queue].
+    pub fn poll_events(&self) -> impl Iterator<Item = NetworkEvent> {
+        self.inner.poll_events()
+    }
+
     /// Sends message over established data channel with a single client-peer represented by
     /// the [UserId] returned by signaling server during connection establishment.
-    pub fn send_message(&self, user_id: UserId, message: &str) -> Result<(), JsValue> {
+    /// Fails with [NetworkError::WouldBlock] while more than the high-water mark
+    /// is waiting to be sent, see [MiniServer::send_message_async].
+    pub fn send_message(&self, user_id: UserId, message: &str) -> Result<(), NetworkError> {
         self.inner.send_message(user_id, message)
     }
 
+    /// Same as [MiniServer::send_message], but sends the bytes as a binary message,
+    /// which the client-peer receives with the callback set by [MiniClient::on_binary_message]
+    pub fn send_binary_message(&self, user_id: UserId, message: &[u8]) -> Result<(), NetworkError> {
+        self.inner.send_binary_message(user_id, message)
+    }
+
+    /// Sets a callback called with every binary message a client-peer sends, over any channel.
+    /// Binary messages are never passed to `on_message_callback` nor delivered as [NetworkEvents],
+    /// without this callback they are dropped.
+    pub fn on_binary_message(&self, callback: impl FnMut(UserId, Vec<u8>) + 'static) {
+        self.inner.on_binary_message(callback)
+    }
+
     /// Convenience function that sends the same message to all connected client-peers.
-    pub fn send_message_to_all(&self, message: &str) {
+    /// Returns the outcome of sending to each of them, as some may fail while others succeed.
+    pub fn send_message_to_all(&self, message: &str) -> HashMap<UserId, Result<(), NetworkError>> {
         self.inner.send_message_to_all(message)
     }
+
+    /// Same as [MiniServer::send_message], but instead of failing with
+    /// [NetworkError::WouldBlock] waits until the data channel drains below the high-water mark.
+    /// Messages are sent in the order this method was called.
+    pub async fn send_message_async(
+        &self,
+        user_id: UserId,
+        message: &str,
+    ) -> Result<(), NetworkError> {
+        self.inner.send_message_async(user_id, message).await
+    }
+
+    /// Same as [MiniServer::send_message_to_all], but waits for each client-peer
+    /// like [MiniServer::send_message_async], without a slow one holding back the others.
+    pub async fn send_message_to_all_async(
+        &self,
+        message: &str,
+    ) -> HashMap<UserId, Result<(), NetworkError>> {
+        self.inner.send_message_to_all_async(message).await
+    }
+
+    /// Number of bytes that may wait to be sent over a data channel before sending
+    /// fails with [NetworkError::WouldBlock], [DEFAULT_HIGH_WATER_MARK] unless changed
+    pub fn set_high_water_mark(&self, high_water_mark: usize) {
+        self.inner.set_high_water_mark(high_water_mark)
+    }
+
+    /// Compresses messages longer than `threshold` bytes, `None` (the default) disables compression.
+    /// Compression is used with client-peers that enabled it too, which is agreed on
+    /// when connecting, so it has to be set before client-peers join.
+    /// Received messages are decompressed before they reach `on_message_callback`.
+    pub fn set_compression_threshold(&self, threshold: Option<usize>) {
+        self.inner.set_compression_threshold(threshold)
+    }
+
+    /// How well messages sent so far to all client-peers compressed
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.inner.compression_stats()
+    }
+
+    /// Queues the message for the client-peer, so that it's sent once queued messages
+    /// of the same or higher [Priority] are, e.g. a critical "player died" event
+    /// overtakes map chunks queued as bulk traffic.
+    /// Fails right away only if the message can't be sent at all,
+    /// errors of sending it later on are reported as [NetworkEvent::Error].
+    pub fn send_message_with_priority(
+        &self,
+        user_id: UserId,
+        message: &str,
+        priority: Priority,
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .schedule_message(user_id, message, priority, None)
+    }
+
+    /// Same as [MiniServer::send_message_with_priority], but for state where only
+    /// the latest value matters: a message queued earlier under the same `key`
+    /// and [Priority] that wasn't sent yet is dropped, as the new one supersedes it.
+    pub fn send_state_update(
+        &self,
+        user_id: UserId,
+        key: &str,
+        message: &str,
+        priority: Priority,
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .schedule_message(user_id, message, priority, Some(key))
+    }
+
+    /// Limits how many bytes per second of messages sent with
+    /// [MiniServer::send_message_with_priority] go to the client-peer,
+    /// except [Priority::Critical] ones, which are never held back. `None` removes the limit.
+    pub fn set_bandwidth_budget(
+        &self,
+        user_id: UserId,
+        bytes_per_second: Option<usize>,
+    ) -> Result<(), NetworkError> {
+        self.inner.set_bandwidth_budget(user_id, bytes_per_second)
+    }
+
+    /// Opens an additional data channel with the client-peer on an established connection,
+    /// e.g. an unreliable one for frequent state updates, and renegotiates the connection.
+    /// Opening a channel under a name that's already in use replaces the old channel,
+    /// which is how parameters of a channel are changed.
+    pub async fn open_channel(
+        &self,
+        user_id: UserId,
+        name: &str,
+        config: ChannelConfig,
+    ) -> Result<(), NetworkError> {
+        self.inner.open_channel(user_id, name, config).await
+    }
+
+    /// Sets a callback called with the name of every channel opened with
+    /// [MiniServer::open_channel] or [MiniClient::open_channel_to_host],
+    /// on both ends of the connection, once it opens.
+    /// Messages received on such channels are passed to the same `on_message_callback`.
+    pub fn on_channel_open(&self, callback: impl FnMut(UserId, String) + 'static) {
+        self.inner.on_channel_open(callback)
+    }
+
+    /// Sends message over a channel opened with the client-peer by either side.
+    /// Fails with [NetworkError::UnknownChannel] if there is no such channel.
+    pub fn send_message_on_channel(
+        &self,
+        user_id: UserId,
+        channel: &str,
+        message: &str,
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .send_message_on_channel(user_id, channel, message)
+    }
+
+    /// Largest message the client-peer accepts in one piece, as stated in its session description,
+    /// `usize::MAX` if it accepts any size. Larger messages can still be sent,
+    /// they are split into fragments and put back together by the client-peer.
+    /// Fails with [NetworkError::DataChannelNotOpen] until the connection is negotiated.
+    pub fn max_message_size(&self, user_id: UserId) -> Result<usize, NetworkError> {
+        self.inner.max_message_size(user_id)
+    }
+
+    /// Round-trip time, traffic of each channel, types of the candidates in use
+    /// (telling whether the connection is relayed through a TURN server)
+    /// and state of ICE of the connection with the client-peer.
+    /// Fails with [NetworkError::NoConnectionForUser] if there is no such connection.
+    pub async fn stats(&self, user_id: UserId) -> Result<ConnectionStats, NetworkError> {
+        self.inner.stats(user_id).await
+    }
+
+    /// How often client-peers are pinged to measure latency, [DEFAULT_PING_INTERVAL]
+    /// unless changed, `None` stops pinging. Pings of client-peers are answered regardless.
+    pub fn set_ping_interval(&self, interval: Option<Duration>) {
+        self.inner.set_ping_interval(interval)
+    }
+
+    /// Smoothed round-trip time, jitter and packet loss of the connection with the client-peer,
+    /// measured with pings. `None` until the first pong arrives, or if there is no such connection.
+    pub fn latency(&self, user_id: UserId) -> Option<LatencyStats> {
+        self.inner.latency(user_id)
+    }
+
+    /// Sets a callback called with [LatencyStats] of a client-peer whenever they change,
+    /// that is when a pong arrives or a ping goes unanswered, e.g. to show ping of each player
+    pub fn on_latency_update(&self, callback: impl FnMut(UserId, LatencyStats) + 'static) {
+        self.inner.on_latency_update(callback)
+    }
+
+    /// Time of the clock client-peers synchronize to, which is the clock of the host,
+    /// so it's always known. See [ClockOffset] for what the time means.
+    pub fn estimated_remote_time(&self) -> Duration {
+        self.inner
+            .estimated_remote_time()
+            .unwrap_or_else(platform::now)
+    }
+
+    /// Offset of the clock client-peers synchronize to, always zero for the host
+    pub fn clock_offset(&self) -> ClockOffset {
+        self.inner.clock_offset().unwrap_or_default()
+    }
+
+    /// Closes the connection with a single client-peer, which sees its data channel close.
+    /// Fails with [NetworkError::NoConnectionForUser] if there is no such connection.
+    pub fn disconnect(&self, user_id: UserId) -> Result<(), NetworkError> {
+        self.inner.disconnect(user_id)
+    }
+
+    /// Closes connections with all client-peers and leaves the signaling session.
+    /// All callbacks are dropped and [NetworkEvents] end, the instance can't be used afterwards.
+    pub fn close(&self) {
+        self.inner.close()
+    }
+
+    /// Last error reported by the signaling server, e.g. [SignalingError::UnsupportedVersion]
+    /// when it doesn't speak the protocol version of this library.
+    pub fn signaling_error(&self) -> Option<SignalingError> {
+        self.inner.signaling_error()
+    }
 }
 
 /// Abstraction over WebRTC peer-to-peer connection.
 /// Same as [MiniServer], but representing clients in client-server topology.
 #[derive(Debug, Clone)]
 pub struct MiniClient {
-    inner: NetworkManager,
+    pub(crate) inner: NetworkManager,
 }
 
 impl MiniClient {
@@ -272,9 +1230,15 @@ impl MiniClient {
         signaling_server_url: &str,
         session_id: SessionId,
         connection_type: ConnectionType,
-    ) -> Result<Self, JsValue> {
+    ) -> Result<Self, NetworkError> {
         Ok(MiniClient {
-            inner: NetworkManager::new(signaling_server_url, session_id, connection_type, false)?,
+            inner: NetworkManager::new(
+                signaling_server_url,
+                session_id,
+                connection_type,
+                Topology::OneToMany,
+                false,
+            )?,
         })
     }
 
@@ -284,14 +1248,200 @@ impl MiniClient {
         // FIXME: MiniServer callbacks should take UserId as argument, it will always be host's.
         on_open_callback: impl FnMut(UserId) + Clone + 'static,
         on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
-    ) -> Result<(), JsValue> {
+    ) -> Result<(), NetworkError> {
         self.inner.start(on_open_callback, on_message_callback)
     }
 
+    /// Same as [MiniServer::connect], but waits until the connection with the host opens
+    pub async fn connect(
+        &mut self,
+        on_open_callback: impl FnMut(UserId) + Clone + 'static,
+        on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
+        timeout: Duration,
+    ) -> Result<Connected, NetworkError> {
+        self.inner
+            .connect(on_open_callback, on_message_callback, timeout)
+            .await
+    }
+
+    /// Same as [MiniServer::start_with_events]
+    pub fn start_with_events(&mut self) -> Result<NetworkEvents, NetworkError> {
+        self.inner.start_with_events()
+    }
+
+    /// Same as [MiniServer::start_with_event_queue]
+    pub fn start_with_event_queue(
+        &mut self,
+        capacity: usize,
+        drop_policy: DropPolicy,
+    ) -> Result<(), NetworkError> {
+        self.inner.start_with_event_queue(capacity, drop_policy)
+    }
+
+    /// Same as [MiniServer::poll_events]
+    pub fn poll_events(&self) -> impl Iterator<Item = NetworkEvent> {
+        self.inner.poll_events()
+    }
+
     /// Way of communicating with peer-server
-    pub fn send_message_to_host(&self, message: &str) -> Result<(), JsValue> {
-        self.inner.send_message_to_all(message);
-        // TODO: we always return success, but this is subject to change
-        Ok(())
+    /// Fails with [NetworkError::DataChannelNotOpen] until the connection with the host opens.
+    pub fn send_message_to_host(&self, message: &str) -> Result<(), NetworkError> {
+        // host is the only peer a client connects with
+        self.inner
+            .send_message_to_all(message)
+            .into_values()
+            .next()
+            .unwrap_or(Err(NetworkError::DataChannelNotOpen))
+    }
+
+    /// Same as [MiniServer::send_message_async], but with the host
+    pub async fn send_message_to_host_async(&self, message: &str) -> Result<(), NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner.send_message_async(host_id, message).await
+    }
+
+    /// Same as [MiniServer::send_binary_message], but with the host
+    pub fn send_binary_message_to_host(&self, message: &[u8]) -> Result<(), NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner.send_binary_message(host_id, message)
+    }
+
+    /// Same as [MiniServer::on_binary_message], called with [UserId] of the host
+    pub fn on_binary_message(&self, callback: impl FnMut(UserId, Vec<u8>) + 'static) {
+        self.inner.on_binary_message(callback)
+    }
+
+    /// Same as [MiniServer::set_high_water_mark]
+    pub fn set_high_water_mark(&self, high_water_mark: usize) {
+        self.inner.set_high_water_mark(high_water_mark)
+    }
+
+    /// Same as [MiniServer::set_compression_threshold], has to be set before connecting
+    pub fn set_compression_threshold(&self, threshold: Option<usize>) {
+        self.inner.set_compression_threshold(threshold)
+    }
+
+    /// Same as [MiniServer::compression_stats]
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.inner.compression_stats()
+    }
+
+    /// Same as [MiniServer::send_message_with_priority], but with the host
+    pub fn send_message_to_host_with_priority(
+        &self,
+        message: &str,
+        priority: Priority,
+    ) -> Result<(), NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner
+            .schedule_message(host_id, message, priority, None)
+    }
+
+    /// Same as [MiniServer::send_state_update], but with the host
+    pub fn send_state_update_to_host(
+        &self,
+        key: &str,
+        message: &str,
+        priority: Priority,
+    ) -> Result<(), NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner
+            .schedule_message(host_id, message, priority, Some(key))
+    }
+
+    /// Same as [MiniServer::set_bandwidth_budget], but with the host
+    pub fn set_host_bandwidth_budget(
+        &self,
+        bytes_per_second: Option<usize>,
+    ) -> Result<(), NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner.set_bandwidth_budget(host_id, bytes_per_second)
+    }
+
+    /// Same as [MiniServer::open_channel], but with the host.
+    /// Fails with [NetworkError::DataChannelNotOpen] until the connection with the host opens.
+    pub async fn open_channel_to_host(
+        &self,
+        name: &str,
+        config: ChannelConfig,
+    ) -> Result<(), NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner.open_channel(host_id, name, config).await
+    }
+
+    /// Same as [MiniServer::on_channel_open]
+    pub fn on_channel_open(&self, callback: impl FnMut(UserId, String) + 'static) {
+        self.inner.on_channel_open(callback)
+    }
+
+    /// Same as [MiniServer::send_message_on_channel], but with the host
+    pub fn send_message_to_host_on_channel(
+        &self,
+        channel: &str,
+        message: &str,
+    ) -> Result<(), NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner
+            .send_message_on_channel(host_id, channel, message)
+    }
+
+    /// Same as [MiniServer::max_message_size], but of the host
+    pub fn max_message_size(&self) -> Result<usize, NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner.max_message_size(host_id)
+    }
+
+    /// Same as [MiniServer::stats], but of the connection with the host.
+    /// Fails with [NetworkError::DataChannelNotOpen] until the connection with the host opens.
+    pub async fn stats(&self) -> Result<ConnectionStats, NetworkError> {
+        let host_id = self.host_id()?;
+        self.inner.stats(host_id).await
+    }
+
+    /// Same as [MiniServer::set_ping_interval], but for pinging the host
+    pub fn set_ping_interval(&self, interval: Option<Duration>) {
+        self.inner.set_ping_interval(interval)
+    }
+
+    /// Same as [MiniServer::latency], but of the connection with the host
+    pub fn latency(&self) -> Option<LatencyStats> {
+        let host_id = self.host_id().ok()?;
+        self.inner.latency(host_id)
+    }
+
+    /// Same as [MiniServer::on_latency_update], called with [UserId] of the host
+    pub fn on_latency_update(&self, callback: impl FnMut(UserId, LatencyStats) + 'static) {
+        self.inner.on_latency_update(callback)
+    }
+
+    /// Time of the clock of the host, estimated from the local clock and [MiniClient::clock_offset].
+    /// `None` until the first pong of the host arrives, which requires pinging the host.
+    pub fn estimated_remote_time(&self) -> Option<Duration> {
+        self.inner.estimated_remote_time()
+    }
+
+    /// Offset of the clock of the host, refined with every pong of the host.
+    /// `None` until the first one arrives.
+    pub fn clock_offset(&self) -> Option<ClockOffset> {
+        self.inner.clock_offset()
+    }
+
+    pub(crate) fn host_id(&self) -> Result<UserId, NetworkError> {
+        // host is the only peer a client connects with
+        self.inner
+            .user_ids()
+            .into_iter()
+            .next()
+            .ok_or(NetworkError::DataChannelNotOpen)
+    }
+
+    /// Same as [MiniServer::close]
+    pub fn close(&self) {
+        self.inner.close()
+    }
+
+    /// Same as [MiniServer::signaling_error]
+    pub fn signaling_error(&self) -> Option<SignalingError> {
+        self.inner.signaling_error()
     }
 }
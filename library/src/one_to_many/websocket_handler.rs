@@ -1,191 +1,213 @@
+use crate::candidates::add_pending_candidates;
+use crate::negotiation::Negotiation;
 use crate::one_to_many::callbacks::{
-    set_data_channel_on_error, set_data_channel_on_message, set_data_channel_on_open,
+    set_data_channel_on_close, set_data_channel_on_message, set_data_channel_on_open,
     set_peer_connection_on_data_channel, set_peer_connection_on_ice_candidate,
-    set_peer_connection_on_ice_connection_state_change,
-    set_peer_connection_on_ice_gathering_state_change, set_peer_connection_on_negotiation_needed,
+    set_peer_connection_on_ice_connection_failed, set_peer_connection_on_negotiation_needed,
 };
 use crate::one_to_many::{Connection, NetworkManager};
-use crate::utils::{create_peer_connection, create_sdp_answer, create_sdp_offer, IceCandidate};
+use crate::platform::{PeerConnection, SignalingSocket};
+use crate::{ChannelConfig, NetworkError, SignalingError};
 use log::{debug, error, info};
-use rusty_games_protocol::one_to_many::SignalMessage;
-use rusty_games_protocol::UserId;
-use wasm_bindgen::JsValue;
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{
-    RtcIceCandidate, RtcIceCandidateInit, RtcSdpType, RtcSessionDescriptionInit, WebSocket,
-};
+use rusty_games_protocol::signal::{Encoding, SignalMessage};
+use rusty_games_protocol::{SessionId, UserId};
 
 /// Basically a state automata spread across host, client and signaling server
 /// handling each step in session and then WebRTC setup.
 pub(crate) async fn handle_websocket_message(
     network_manager: NetworkManager,
     message: SignalMessage,
-    websocket: WebSocket,
+    signaling_socket: SignalingSocket,
     on_open_callback: impl FnMut(UserId) + Clone + 'static,
     on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
     is_host: bool,
-) -> Result<(), JsValue> {
+) -> Result<(), NetworkError> {
     match message {
-        SignalMessage::SessionJoin(_session_id, _user_id) => {
-            error!("error, SessionStartOrJoin should only be sent by peers to signaling server");
+        SignalMessage::Hello { .. }
+        | SignalMessage::SessionJoin(..)
+        | SignalMessage::SessionLeave(..) => {
+            error!("error, Hello, SessionJoin and SessionLeave should only be sent by peers to signaling server");
+        }
+        SignalMessage::Welcome {
+            protocol_version,
+            capabilities,
+            ..
+        } => {
+            debug!(
+                "signaling server accepted protocol version {} with capabilities: {:?}",
+                protocol_version, capabilities
+            );
+            signaling_socket.set_encoding(Encoding::negotiated(&capabilities));
         }
-        SignalMessage::SessionReady(session_id, peer_id) => {
+        SignalMessage::SessionReady(session_id, peer_id, _) => {
             info!(
                 "peer received info that session with {:?} is ready {:?}",
                 peer_id, session_id
             );
-            let peer_connection =
-                create_peer_connection(&network_manager.inner.borrow().connection_type).unwrap();
-            set_peer_connection_on_data_channel(
-                &peer_connection,
+            let peer_connection = create_peer_connection(
+                &network_manager,
                 peer_id,
-                network_manager.clone(),
+                &signaling_socket,
+                &session_id,
                 on_open_callback.clone(),
                 on_message_callback.clone(),
-            );
-            set_peer_connection_on_ice_candidate(
-                &peer_connection,
-                peer_id,
-                websocket.clone(),
-                session_id.clone(),
-            );
-            set_peer_connection_on_ice_connection_state_change(&peer_connection);
-            set_peer_connection_on_ice_gathering_state_change(&peer_connection);
-            set_peer_connection_on_negotiation_needed(&peer_connection);
+            )
+            .await?;
 
-            let data_channel =
-                peer_connection.create_data_channel(&format!("{}-{}", session_id, peer_id));
+            let data_channel = peer_connection
+                .create_data_channel(
+                    &format!("{}-{}", session_id, peer_id),
+                    &ChannelConfig::default(),
+                )
+                .await?;
             set_data_channel_on_open(&data_channel, peer_id, on_open_callback.clone());
-            set_data_channel_on_error(&data_channel);
-            set_data_channel_on_message(&data_channel, peer_id, on_message_callback.clone());
+            set_data_channel_on_message(
+                &data_channel,
+                peer_id,
+                network_manager.clone(),
+                on_message_callback.clone(),
+            );
+            set_data_channel_on_close(&data_channel, peer_id, network_manager.clone());
 
-            let offer = create_sdp_offer(&peer_connection).await?;
-            let signal_message = SignalMessage::SdpOffer(session_id, peer_id, offer);
-            let signal_message = serde_json_wasm::to_string(&signal_message)
-                .expect("failed to serialize SignalMessage");
-            websocket.send_with_str(&signal_message)?;
+            // peer told to create the offer is the impolite one
+            let compression = network_manager.inner.borrow().compression.clone();
             network_manager.inner.borrow_mut().connections.insert(
                 peer_id,
-                Connection::new(peer_connection.clone(), Some(data_channel.clone())),
+                Connection::new(
+                    Negotiation::new(peer_connection, false, compression),
+                    Some(data_channel.clone()),
+                ),
             );
+            send_offer(&network_manager, peer_id).await?;
             debug!(
                 "(is_host: {}) sent an offer to {:?} successfully",
                 is_host, peer_id
             );
         }
         SignalMessage::SdpOffer(session_id, user_id, offer) => {
-            // non-host peer received an offer
-            let peer_connection =
-                create_peer_connection(&network_manager.inner.borrow().connection_type).unwrap();
-            set_peer_connection_on_data_channel(
-                &peer_connection,
-                user_id,
-                network_manager.clone(),
-                on_open_callback.clone(),
-                on_message_callback.clone(),
-            );
-            set_peer_connection_on_ice_candidate(
-                &peer_connection,
-                user_id,
-                websocket.clone(),
-                session_id.clone(),
-            );
-            set_peer_connection_on_ice_connection_state_change(&peer_connection);
-            set_peer_connection_on_ice_gathering_state_change(&peer_connection);
-            set_peer_connection_on_negotiation_needed(&peer_connection);
-
-            network_manager
-                .inner
-                .borrow_mut()
-                .connections
-                .insert(user_id, Connection::new(peer_connection.clone(), None));
-            debug!(
-                "(is_host: {}) added connection for {:?} successfully",
-                is_host, user_id
-            );
+            let negotiation = match network_manager.negotiation(user_id) {
+                // renegotiation of an established connection
+                Ok(negotiation) => negotiation,
+                Err(_) => {
+                    let peer_connection = create_peer_connection(
+                        &network_manager,
+                        user_id,
+                        &signaling_socket,
+                        &session_id,
+                        on_open_callback.clone(),
+                        on_message_callback.clone(),
+                    )
+                    .await?;
+                    // peer answering the first offer is the polite one
+                    let compression = network_manager.inner.borrow().compression.clone();
+                    let negotiation = Negotiation::new(peer_connection, true, compression);
+                    network_manager
+                        .inner
+                        .borrow_mut()
+                        .connections
+                        .insert(user_id, Connection::new(negotiation.clone(), None));
+                    debug!(
+                        "(is_host: {}) added connection for {:?} successfully",
+                        is_host, user_id
+                    );
+                    negotiation
+                }
+            };
 
-            let answer = create_sdp_answer(&peer_connection, offer)
-                .await
-                .expect("failed to create SDP answer");
-            debug!(
-                "received an offer from {:?} and created an answer: {}",
-                user_id, answer
-            );
-            let signal_message = SignalMessage::SdpAnswer(session_id, user_id, answer);
-            let signal_message = serde_json_wasm::to_string(&signal_message)
-                .expect("failed to serialize SignalMessage");
-            websocket
-                .send_with_str(&signal_message)
-                .expect("failed to send SPD answer to signaling server");
+            if let Some(answer) = negotiation.accept_offer(offer).await? {
+                debug!(
+                    "received an offer from {:?} and created an answer: {:?}",
+                    user_id, answer
+                );
+                let signal_message = SignalMessage::SdpAnswer(session_id, user_id, answer);
+                signaling_socket.send(&signal_message)?;
+                add_pending_candidates(&negotiation, || {
+                    network_manager.pop_pending_candidate(user_id)
+                })
+                .await?;
+            }
         }
         SignalMessage::SdpAnswer(session_id, user_id, answer) => {
-            let peer_connection = network_manager
-                .inner
-                .borrow()
-                .connections
-                .get(&user_id)
-                .unwrap_or_else(|| {
-                    panic!(
-                        "(is_host: {}) no connection to send answer for given user_id: {:?}",
-                        is_host, &user_id
-                    )
-                })
-                .peer_connection
-                .clone();
-            let mut remote_session_description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
-            remote_session_description.sdp(&answer);
-            JsFuture::from(peer_connection.set_remote_description(&remote_session_description))
-                .await
-                .expect("failed to set remote description");
+            let negotiation = network_manager.negotiation(user_id)?;
             debug!(
-                "received answer from peer and set remote description: {}, {:?}",
+                "received answer from peer, setting remote description: {:?}, {:?}",
                 answer, session_id
             );
+            negotiation.accept_answer(answer).await?;
+            add_pending_candidates(&negotiation, || {
+                network_manager.pop_pending_candidate(user_id)
+            })
+            .await?;
         }
         SignalMessage::IceCandidate(_session_id, user_id, ice_candidate) => {
-            let peer_connection = network_manager
-                .inner
-                .borrow()
-                .connections
-                .get(&user_id)
-                .unwrap_or_else(|| {
-                    panic!(
-                        "no connection to send ice candidate to for given user_id: {:?}",
-                        &user_id
-                    )
-                })
-                .peer_connection
-                .clone();
-            debug!("peer received ice candidate: {}", &ice_candidate);
-            // TODO: IceCandidate should already be struct inside signal message
-            let ice_candidate = serde_json_wasm::from_str::<IceCandidate>(&ice_candidate)
-                .expect("failed to deserialize IceCandidate");
-
-            let mut rtc_candidate = RtcIceCandidateInit::new("");
-            rtc_candidate.candidate(&ice_candidate.candidate);
-            rtc_candidate.sdp_m_line_index(ice_candidate.sdp_m_line_index);
-            rtc_candidate.sdp_mid(ice_candidate.sdp_mid.as_deref());
-
-            let rtc_candidate =
-                RtcIceCandidate::new(&rtc_candidate).expect("failed to create new RtcIceCandidate");
-            JsFuture::from(
-                peer_connection.add_ice_candidate_with_opt_rtc_ice_candidate(Some(&rtc_candidate)),
-            )
-            .await
-            .expect("failed to add ICE candidate");
-            debug!("added ice candidate {:?}", ice_candidate);
+            debug!("peer received ice candidate: {:?}", &ice_candidate);
+            if let Some(ice_candidate) =
+                network_manager.push_pending_candidate(user_id, ice_candidate)
+            {
+                let negotiation = network_manager.negotiation(user_id)?;
+                negotiation.add_ice_candidate(ice_candidate.clone()).await?;
+                debug!("added ice candidate {:?}", ice_candidate);
+            }
         }
-        SignalMessage::Error(session_id, error) => {
-            error!(
-                "signaling server returned error: session id: {:?}, error: {}",
-                session_id, error
-            );
+        SignalMessage::Error(session_id, code, description) => {
+            let error = SignalingError::new(session_id, code, description);
+            error!("{}", error);
+            network_manager.inner.borrow_mut().signaling_error = Some(error.clone());
+            network_manager.report_error(error.into());
         }
     }
 
     Ok(())
 }
 
+async fn create_peer_connection(
+    network_manager: &NetworkManager,
+    user_id: UserId,
+    signaling_socket: &SignalingSocket,
+    session_id: &SessionId,
+    on_open_callback: impl FnMut(UserId) + Clone + 'static,
+    on_message_callback: impl FnMut(UserId, String) + Clone + 'static,
+) -> Result<PeerConnection, NetworkError> {
+    let connection_type = network_manager.inner.borrow().connection_type.clone();
+    let peer_connection = PeerConnection::new(&connection_type).await?;
+    set_peer_connection_on_data_channel(
+        &peer_connection,
+        user_id,
+        network_manager.clone(),
+        on_open_callback,
+        on_message_callback,
+    );
+    set_peer_connection_on_ice_candidate(
+        &peer_connection,
+        user_id,
+        signaling_socket.clone(),
+        session_id.clone(),
+    );
+    set_peer_connection_on_ice_connection_failed(
+        &peer_connection,
+        user_id,
+        network_manager.clone(),
+    );
+    set_peer_connection_on_negotiation_needed(&peer_connection, user_id, network_manager.clone());
+    Ok(peer_connection)
+}
+
+/// Starts a new offer/answer exchange with the peer, unless one is already in progress
+pub(crate) async fn send_offer(
+    network_manager: &NetworkManager,
+    user_id: UserId,
+) -> Result<(), NetworkError> {
+    let negotiation = network_manager.negotiation(user_id)?;
+    if let Some(offer) = negotiation.create_offer().await? {
+        let (session_id, signaling_socket) = {
+            let inner = network_manager.inner.borrow();
+            (inner.session_id.clone(), inner.signaling_socket.clone())
+        };
+        signaling_socket.send(&SignalMessage::SdpOffer(session_id, user_id, offer))?;
+    }
+    Ok(())
+}
+
 // #[cfg(test)]
 // mod test {
 //     use super::*;
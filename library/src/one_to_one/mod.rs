@@ -8,18 +8,24 @@ with [NetworkManager::send_message] method.
 
 This example shows two peers sending `ping` and `pong` messages to each other.
 
-```
-use rusty_games_library::ConnectionType;
+```no_run
 use rusty_games_library::one_to_one::NetworkManager;
+use rusty_games_library::{ConnectionType, SessionId};
+use std::cell::RefCell;
+use std::rc::Rc;
 use web_sys::console;
 
-const SIGNALING_SERVER_URL: &str = "ws://0.0.0.0:9001/one-to-one";
+const SIGNALING_SERVER_URL: &str = "ws://0.0.0.0:9001/signal";
+const STUN_SERVER_URLS: &str = "stun:openrelay.metered.ca:80";
+
+let server_received_message = Rc::new(RefCell::new(false));
+let client_received_message = Rc::new(RefCell::new(false));
 
 let session_id = SessionId::new("some-session-id".to_string());
 let mut server = NetworkManager::new(
     SIGNALING_SERVER_URL,
     session_id.clone(),
-    ConnectionType::Stun,
+    ConnectionType::Stun { urls: STUN_SERVER_URLS.to_string() },
 )
 .unwrap();
 
@@ -27,7 +33,7 @@ let server_clone = server.clone();
 let server_on_open = move || server_clone.send_message("ping!").unwrap();
 let server_on_message = {
     let server_received_message = server_received_message.clone();
-    move |message| {
+    move |message: String| {
         console::log_1(&format!("server received message: {}", message).into());
         *server_received_message.borrow_mut() = true;
     }
@@ -37,14 +43,14 @@ server.start(server_on_open, server_on_message).unwrap();
 let mut client = NetworkManager::new(
     SIGNALING_SERVER_URL,
     session_id,
-    ConnectionType::Stun,
+    ConnectionType::Stun { urls: STUN_SERVER_URLS.to_string() },
 )
 .unwrap();
 let client_on_open = || { /* do nothing */ };
 let client_clone = client.clone();
 let client_on_message = {
     let client_received_message = client_received_message.clone();
-    move |message| {
+    move |message: String| {
         console::log_1(&format!("client received message: {}", message).into());
         client_clone.send_message("pong!").unwrap();
         *client_received_message.borrow_mut() = true;
@@ -54,31 +60,102 @@ client.start(client_on_open, client_on_message).unwrap();
 ```
 */
 
+use crate::candidates::PendingCandidates;
+use crate::channel::SharedCallback;
+use crate::clock::ClockOffset;
+use crate::compression::Compression;
+use crate::connect::ConnectWaiter;
+use crate::events::{EventQueue, EventSink};
+use crate::framing::{Frame, Pong};
+use crate::latency::LatencyTracker;
+use crate::negotiation::Negotiation;
 use crate::one_to_one::callbacks::{
-    set_data_channel_on_error, set_data_channel_on_message, set_data_channel_on_open,
+    set_data_channel_on_close, set_data_channel_on_message, set_data_channel_on_open,
     set_peer_connection_on_data_channel, set_peer_connection_on_ice_candidate,
-    set_peer_connection_on_ice_connection_state_change,
-    set_peer_connection_on_ice_gathering_state_change, set_peer_connection_on_negotiation_needed,
-    set_websocket_on_message, set_websocket_on_open,
+    set_peer_connection_on_ice_connection_failed, set_peer_connection_on_negotiation_needed,
+    set_websocket_on_close, set_websocket_on_message, set_websocket_on_open,
 };
-use crate::utils::{create_peer_connection, ConnectionType};
-use log::debug;
-use rusty_games_protocol::SessionId;
+use crate::platform::{self, spawn_local, DataChannel, PeerConnection, SignalingSocket};
+use crate::send_queue::{OutgoingMessage, DEFAULT_HIGH_WATER_MARK};
+use crate::timer::IntervalTimer;
+use crate::utils::ConnectionType;
+use crate::{
+    ChannelConfig, CompressionStats, Connected, ConnectionStats, DropPolicy, LatencyStats,
+    NetworkError, NetworkEvent, NetworkEvents, SignalingError, DEFAULT_PING_INTERVAL,
+};
+use log::{debug, error};
+use rusty_games_protocol::signal::SignalMessage;
+use rusty_games_protocol::{SessionId, UserId};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
-use wasm_bindgen::JsValue;
-use web_sys::RtcPeerConnection;
-use web_sys::{RtcDataChannel, WebSocket};
+use std::time::Duration;
 
 mod callbacks;
 mod websocket_handler;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub(crate) struct NetworkManagerInner {
     session_id: SessionId,
-    websocket: WebSocket,
-    peer_connection: RtcPeerConnection,
-    pub(crate) data_channel: Option<RtcDataChannel>,
+    signaling_socket: SignalingSocket,
+    connection_type: ConnectionType,
+    pub(crate) negotiation: Option<Negotiation>,
+    pub(crate) data_channel: Option<DataChannel>,
+    /// Channels this peer created on start, replaced by the ones the other peer created,
+    /// kept so that they're closed along with the manager
+    pub(crate) replaced_data_channels: Vec<DataChannel>,
+    /// Channels opened with [NetworkManager::open_channel] by either peer, by name
+    channels: HashMap<String, DataChannel>,
+    pub(crate) peer_id: Option<UserId>,
+    pub(crate) pending_candidates: PendingCandidates,
+    pub(crate) signaling_error: Option<SignalingError>,
+    pub(crate) connect_waiter: ConnectWaiter,
+    event_sink: Option<EventSink>,
+    on_message: Option<SharedCallback<String>>,
+    on_channel_open: Option<SharedCallback<String>>,
+    on_binary_message: Option<SharedCallback<Vec<u8>>>,
+    /// Channels used by the library itself, e.g. for rollback, by name,
+    /// their messages go to the handler instead of `on_message`
+    channel_handlers: HashMap<String, SharedCallback<String>>,
+    high_water_mark: usize,
+    compression: Rc<Compression>,
+    latency: LatencyTracker,
+    ping_timer: Rc<IntervalTimer>,
+    on_latency_update: Option<SharedCallback<LatencyStats>>,
+    /// Learned from pongs, as only the other peer is told the id
+    own_id: Option<UserId>,
+}
+
+impl NetworkManagerInner {
+    fn max_message_size(&self) -> Option<usize> {
+        self.negotiation
+            .as_ref()
+            .and_then(Negotiation::max_message_size)
+    }
+
+    /// Encodes the message for the default data channel, or the one with the given name
+    fn outgoing_message(
+        &self,
+        channel: Option<&str>,
+        frame: Frame,
+    ) -> Result<OutgoingMessage, NetworkError> {
+        let data_channel = match channel {
+            None => self
+                .data_channel
+                .as_ref()
+                .ok_or(NetworkError::DataChannelNotOpen)?,
+            Some(channel) => self
+                .channels
+                .get(channel)
+                .ok_or_else(|| NetworkError::UnknownChannel(channel.to_string()))?,
+        };
+        OutgoingMessage::new(
+            data_channel,
+            frame,
+            self.negotiation.as_ref(),
+            self.high_water_mark,
+        )
+    }
 }
 
 /// Abstraction over WebRTC peer-to-peer connection.
@@ -109,18 +186,33 @@ impl NetworkManager {
         signaling_server_url: &str,
         session_id: SessionId,
         connection_type: ConnectionType,
-    ) -> Result<Self, JsValue> {
-        let peer_connection = create_peer_connection(&connection_type)?;
-
-        let websocket = WebSocket::new(signaling_server_url)?;
-        websocket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+    ) -> Result<Self, NetworkError> {
+        let signaling_socket = SignalingSocket::new(signaling_server_url)?;
 
         Ok(NetworkManager {
             inner: Rc::new(RefCell::new(NetworkManagerInner {
                 session_id,
-                websocket,
-                peer_connection,
+                signaling_socket,
+                connection_type,
+                negotiation: None,
                 data_channel: None,
+                replaced_data_channels: Vec::new(),
+                channels: HashMap::new(),
+                peer_id: None,
+                pending_candidates: PendingCandidates::default(),
+                signaling_error: None,
+                connect_waiter: ConnectWaiter::default(),
+                event_sink: None,
+                high_water_mark: DEFAULT_HIGH_WATER_MARK,
+                compression: Rc::default(),
+                on_message: None,
+                on_channel_open: None,
+                on_binary_message: None,
+                channel_handlers: HashMap::new(),
+                latency: LatencyTracker::default(),
+                ping_timer: Rc::new(IntervalTimer::new(Some(DEFAULT_PING_INTERVAL))),
+                on_latency_update: None,
+                own_id: None,
             })),
         })
     }
@@ -132,25 +224,156 @@ impl NetworkManager {
         &mut self,
         on_open_callback: impl FnMut() + Clone + 'static,
         on_message_callback: impl FnMut(String) + Clone + 'static,
-    ) -> Result<(), JsValue> {
-        let NetworkManagerInner {
-            websocket,
-            peer_connection,
-            session_id,
-            ..
-        } = self.inner.borrow().clone();
+    ) -> Result<(), NetworkError> {
+        let network_manager = self.clone();
+        spawn_local(async move {
+            if let Err(error) = network_manager
+                .setup(on_open_callback, on_message_callback)
+                .await
+            {
+                error!("failed to start network manager: {:?}", error);
+                network_manager.report_error(error);
+            }
+        });
+        self.start_pinging();
+        Ok(())
+    }
 
-        let data_channel = peer_connection.create_data_channel(&session_id.clone().into_inner());
-        debug!(
-            "data_channel created with label: {:?}",
-            data_channel.label()
-        );
+    /// Same as [NetworkManager::start], but waits until the connection with the other peer opens.
+    /// Fails if signaling or ICE fails, or if the connection doesn't open within the `timeout`.
+    pub async fn connect(
+        &mut self,
+        on_open_callback: impl FnMut() + Clone + 'static,
+        on_message_callback: impl FnMut(String) + Clone + 'static,
+        timeout: Duration,
+    ) -> Result<Connected, NetworkError> {
+        let connect_waiter = self.inner.borrow().connect_waiter.clone();
+        connect_waiter
+            .wait(timeout, || {
+                self.start(on_open_callback, on_message_callback)
+            })
+            .await
+    }
+
+    /// Same as [NetworkManager::start], but instead of calling callbacks
+    /// everything that happens on the network is delivered as [NetworkEvents].
+    pub fn start_with_events(&mut self) -> Result<NetworkEvents, NetworkError> {
+        let (event_sender, events) = NetworkEvents::channel();
+        self.start_with_sink(EventSink::Stream(event_sender))?;
+        Ok(events)
+    }
+
+    /// Same as [NetworkManager::start_with_events], but events are kept in a queue
+    /// of at most `capacity` events, drained with [NetworkManager::poll_events],
+    /// e.g. once per frame of a game loop.
+    /// Events arriving when the queue is full are handled according to the `drop_policy`.
+    pub fn start_with_event_queue(
+        &mut self,
+        capacity: usize,
+        drop_policy: DropPolicy,
+    ) -> Result<(), NetworkError> {
+        self.start_with_sink(EventSink::Queue(EventQueue::new(capacity, drop_policy)))
+    }
+
+    /// Takes all events queued since the last call, oldest first.
+    /// Yields nothing unless started with [NetworkManager::start_with_event_queue].
+    pub fn poll_events(&self) -> impl Iterator<Item = NetworkEvent> {
+        self.inner
+            .borrow_mut()
+            .event_sink
+            .as_mut()
+            .map(EventSink::drain)
+            .into_iter()
+            .flatten()
+    }
+
+    fn start_with_sink(&mut self, event_sink: EventSink) -> Result<(), NetworkError> {
+        self.inner.borrow_mut().event_sink = Some(event_sink);
+
+        let on_open_callback = {
+            let network_manager = self.clone();
+            move || {
+                let peer_id = network_manager.inner.borrow().peer_id;
+                if let Some(peer_id) = peer_id {
+                    network_manager.emit(NetworkEvent::PeerConnected(peer_id));
+                }
+            }
+        };
+        let on_message_callback = {
+            let network_manager = self.clone();
+            move |message| {
+                let peer_id = network_manager.inner.borrow().peer_id;
+                if let Some(peer_id) = peer_id {
+                    network_manager.emit(NetworkEvent::Message(peer_id, message));
+                }
+            }
+        };
+        self.start(on_open_callback, on_message_callback)
+    }
+
+    /// Fails pending [NetworkManager::connect] call, if any
+    pub(crate) fn report_error(&self, error: NetworkError) {
+        let connect_waiter = self.inner.borrow().connect_waiter.clone();
+        connect_waiter.resolve(Err(error.clone()));
+        self.emit(NetworkEvent::Error(error));
+    }
+
+    /// Delivers the event if [NetworkManager::start_with_events]
+    /// or [NetworkManager::start_with_event_queue] was used
+    pub(crate) fn emit(&self, event: NetworkEvent) {
+        if let Some(event_sink) = &mut self.inner.borrow_mut().event_sink {
+            event_sink.send(event);
+        }
+    }
+
+    async fn setup(
+        &self,
+        mut on_open_callback: impl FnMut() + Clone + 'static,
+        on_message_callback: impl FnMut(String) + Clone + 'static,
+    ) -> Result<(), NetworkError> {
+        let (signaling_socket, session_id, connection_type) = {
+            let inner = self.inner.borrow();
+            (
+                inner.signaling_socket.clone(),
+                inner.session_id.clone(),
+                inner.connection_type.clone(),
+            )
+        };
+        let on_open_callback = {
+            let network_manager = self.clone();
+            move || {
+                let (peer_id, connect_waiter) = {
+                    let inner = network_manager.inner.borrow();
+                    (inner.peer_id, inner.connect_waiter.clone())
+                };
+                if let Some(peer_id) = peer_id {
+                    connect_waiter.resolve(Ok(Connected { peer_id }));
+                }
+                on_open_callback();
+            }
+        };
+
+        self.inner.borrow_mut().on_message = Some(SharedCallback::new(on_message_callback.clone()));
+
+        let peer_connection = PeerConnection::new(&connection_type).await?;
+        let data_channel = peer_connection
+            .create_data_channel(&session_id.clone().into_inner(), &ChannelConfig::default())
+            .await?;
 
         set_data_channel_on_open(&data_channel, on_open_callback.clone());
-        set_data_channel_on_error(&data_channel);
-        set_data_channel_on_message(&data_channel, on_message_callback.clone());
+        set_data_channel_on_message(&data_channel, self.clone(), on_message_callback.clone());
+        set_data_channel_on_close(&data_channel, self.clone());
 
-        self.inner.borrow_mut().data_channel = Some(data_channel);
+        {
+            let mut inner = self.inner.borrow_mut();
+            inner.data_channel = Some(data_channel);
+            // the role is decided once signaling server pairs the peers
+            inner.negotiation = Some(Negotiation::new(
+                peer_connection.clone(),
+                false,
+                inner.compression.clone(),
+            ));
+        }
         set_peer_connection_on_data_channel(
             &peer_connection,
             self.clone(),
@@ -160,14 +383,15 @@ impl NetworkManager {
 
         set_peer_connection_on_ice_candidate(
             &peer_connection,
-            websocket.clone(),
+            self.clone(),
+            signaling_socket.clone(),
             session_id.clone(),
         );
-        set_peer_connection_on_ice_connection_state_change(&peer_connection);
-        set_peer_connection_on_ice_gathering_state_change(&peer_connection);
-        set_peer_connection_on_negotiation_needed(&peer_connection);
-        set_websocket_on_open(&websocket, session_id);
-        set_websocket_on_message(&websocket, peer_connection);
+        set_peer_connection_on_ice_connection_failed(&peer_connection, self.clone());
+        set_peer_connection_on_negotiation_needed(&peer_connection, self.clone());
+        set_websocket_on_open(&signaling_socket, session_id, self.clone());
+        set_websocket_on_message(&signaling_socket, self.clone());
+        set_websocket_on_close(&signaling_socket, self.clone());
 
         Ok(())
     }
@@ -176,16 +400,394 @@ impl NetworkManager {
     /// It might fail if the connection is not yet set up
     /// and thus should only be called after `on_open_callback` triggers.
     /// Otherwise it will result in an error.
-    pub fn send_message(&self, message: &str) -> Result<(), JsValue> {
+    /// Messages larger than [NetworkManager::max_message_size] are sent in fragments.
+    /// Fails with [NetworkError::WouldBlock] while more than the high-water mark
+    /// is waiting to be sent, see [NetworkManager::send_message_async].
+    pub fn send_message(&self, message: &str) -> Result<(), NetworkError> {
         debug!("server will try to send a message: {:?}", &message);
+        self.inner
+            .borrow()
+            .outgoing_message(None, Frame::text(message))?
+            .try_send()
+    }
+
+    /// Same as [NetworkManager::send_message], but instead of failing with
+    /// [NetworkError::WouldBlock] waits until the data channel drains below the high-water mark.
+    /// Messages are sent in the order this method was called.
+    pub async fn send_message_async(&self, message: &str) -> Result<(), NetworkError> {
+        let outgoing_message = self
+            .inner
+            .borrow()
+            .outgoing_message(None, Frame::text(message))?;
+        outgoing_message.send().await
+    }
+
+    /// Same as [NetworkManager::send_message], but sends the bytes as a binary message,
+    /// which the other peer receives with the callback set by [NetworkManager::on_binary_message]
+    pub fn send_binary_message(&self, message: &[u8]) -> Result<(), NetworkError> {
+        self.inner
+            .borrow()
+            .outgoing_message(None, Frame::binary(message.to_vec()))?
+            .try_send()
+    }
+
+    /// Number of bytes that may wait to be sent over a data channel before sending
+    /// fails with [NetworkError::WouldBlock], [DEFAULT_HIGH_WATER_MARK] unless changed
+    pub fn set_high_water_mark(&self, high_water_mark: usize) {
+        self.inner.borrow_mut().high_water_mark = high_water_mark;
+    }
+
+    /// Compresses messages longer than `threshold` bytes, `None` (the default) disables compression.
+    /// Compression is used only if the other peer enabled it too, which they learn when
+    /// the connection is negotiated, so it has to be set before connecting.
+    /// Received messages are decompressed before they reach `on_message_callback`.
+    pub fn set_compression_threshold(&self, threshold: Option<usize>) {
+        self.inner.borrow().compression.set_threshold(threshold);
+    }
+
+    /// How well messages sent so far compressed
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.inner.borrow().compression.stats()
+    }
+
+    /// Send message over a channel opened with [NetworkManager::open_channel] by either peer.
+    /// Fails with [NetworkError::UnknownChannel] if there is no such channel.
+    pub fn send_message_on_channel(
+        &self,
+        channel: &str,
+        message: &str,
+    ) -> Result<(), NetworkError> {
+        self.inner
+            .borrow()
+            .outgoing_message(Some(channel), Frame::text(message))?
+            .try_send()
+    }
+
+    /// Largest message the peer accepts in one piece, as stated in its session description,
+    /// `usize::MAX` if it accepts any size. Larger messages can still be sent,
+    /// they are split into fragments and put back together by the peer.
+    /// Fails with [NetworkError::DataChannelNotOpen] until the connection is negotiated.
+    pub fn max_message_size(&self) -> Result<usize, NetworkError> {
+        self.inner
+            .borrow()
+            .max_message_size()
+            .ok_or(NetworkError::DataChannelNotOpen)
+    }
+
+    /// Round-trip time, traffic of each channel, types of the candidates in use
+    /// (telling whether the connection is relayed through a TURN server)
+    /// and state of ICE of the connection with the peer.
+    /// Fails with [NetworkError::DataChannelNotOpen] if the manager wasn't started yet.
+    pub async fn stats(&self) -> Result<ConnectionStats, NetworkError> {
+        let negotiation = self
+            .inner
+            .borrow()
+            .negotiation
+            .clone()
+            .ok_or(NetworkError::DataChannelNotOpen)?;
+        Ok(negotiation.peer_connection().stats().await?)
+    }
+
+    fn start_pinging(&self) {
+        let ping_timer = self.inner.borrow().ping_timer.clone();
+        let network_manager = self.clone();
+        ping_timer.start(move || network_manager.send_pings());
+    }
+
+    /// Pings the peer over the data channel the connection was opened with
+    fn send_pings(&self) {
+        let now = platform::now();
+        let expired = {
+            let mut inner = self.inner.borrow_mut();
+            let Some(data_channel) = inner.data_channel.clone() else {
+                return;
+            };
+            let ping_id = inner.latency.next_ping_id();
+            if data_channel.send(&Frame::ping(ping_id).encode()).is_ok() {
+                inner.latency.sent(ping_id, now);
+            }
+            inner.latency.expire(now)
+        };
+        if expired {
+            self.latency_updated();
+        }
+    }
+
+    pub(crate) fn send_pong(&self, ping_id: u32) {
+        let (data_channel, peer_id) = {
+            let inner = self.inner.borrow();
+            (inner.data_channel.clone(), inner.peer_id)
+        };
+        let result = data_channel
+            .zip(peer_id)
+            .ok_or(NetworkError::DataChannelNotOpen)
+            .and_then(|(data_channel, peer_id)| {
+                let pong = Pong {
+                    ping_id,
+                    time: platform::now().as_micros() as u64,
+                    pinging_peer: peer_id,
+                };
+                data_channel.send(&Frame::pong(&pong).encode())
+            });
+        if let Err(error) = result {
+            debug!("failed to answer ping: {}", error);
+        }
+    }
+
+    pub(crate) fn receive_pong(&self, pong: Pong) {
+        let updated = {
+            let mut inner = self.inner.borrow_mut();
+            inner.own_id = Some(pong.pinging_peer);
+            inner
+                .latency
+                .pong(
+                    pong.ping_id,
+                    Duration::from_micros(pong.time),
+                    platform::now(),
+                )
+                .is_some()
+        };
+        if updated {
+            self.latency_updated();
+        }
+    }
+
+    fn latency_updated(&self) {
+        let (stats, on_latency_update) = {
+            let inner = self.inner.borrow();
+            (inner.latency.stats(), inner.on_latency_update.clone())
+        };
+        if let (Some(stats), Some(on_latency_update)) = (stats, on_latency_update) {
+            on_latency_update.call(stats);
+        }
+    }
+
+    /// How often the other peer is pinged to measure latency,
+    /// [DEFAULT_PING_INTERVAL] unless changed,
+    /// `None` stops pinging. Pings of the other peer are answered regardless.
+    pub fn set_ping_interval(&self, interval: Option<Duration>) {
+        let ping_timer = self.inner.borrow().ping_timer.clone();
+        ping_timer.set_interval(interval);
+        if ping_timer.is_started() {
+            self.start_pinging();
+        }
+    }
+
+    /// Smoothed round-trip time, jitter and packet loss of the connection,
+    /// measured with pings. `None` until the first pong arrives.
+    pub fn latency(&self) -> Option<LatencyStats> {
+        self.inner.borrow().latency.stats()
+    }
+
+    /// Offset of the clock both peers synchronize to, which is the clock of the peer
+    /// with the lower [UserId], refined with every pong of the other peer.
+    /// Zero if that's the local clock, `None` until it's known which one it is.
+    pub fn clock_offset(&self) -> Option<ClockOffset> {
+        let inner = self.inner.borrow();
+        let (own_id, peer_id) = (inner.own_id?, inner.peer_id?);
+        if own_id.into_inner() < peer_id.into_inner() {
+            return Some(ClockOffset::default());
+        }
+        inner.latency.clock_offset()
+    }
+
+    /// Time of the clock both peers synchronize to, estimated from the local clock
+    /// and [NetworkManager::clock_offset]. See [ClockOffset] for what the time means.
+    pub fn estimated_remote_time(&self) -> Option<Duration> {
+        self.clock_offset()
+            .map(|clock_offset| clock_offset.to_remote(platform::now()))
+    }
+
+    /// Sets a callback called with [LatencyStats] whenever they change,
+    /// that is when a pong arrives or a ping goes unanswered, e.g. to show ping in the HUD
+    pub fn on_latency_update(&self, callback: impl FnMut(LatencyStats) + 'static) {
+        self.inner.borrow_mut().on_latency_update = Some(SharedCallback::new(callback));
+    }
+
+    /// Sets a callback called with the name of every channel opened with
+    /// [NetworkManager::open_channel], on both ends of the connection, once it opens.
+    /// Messages received on such channels are passed to the same `on_message_callback`.
+    pub fn on_channel_open(&self, callback: impl FnMut(String) + 'static) {
+        self.inner.borrow_mut().on_channel_open = Some(SharedCallback::new(callback));
+    }
+
+    /// Sets a callback called with every binary message the other peer sends, over any channel.
+    /// Binary messages are never passed to `on_message_callback` nor delivered as [NetworkEvents],
+    /// without this callback they are dropped.
+    pub fn on_binary_message(&self, callback: impl FnMut(Vec<u8>) + 'static) {
+        self.inner.borrow_mut().on_binary_message = Some(SharedCallback::new(callback));
+    }
+
+    pub(crate) fn receive_binary(&self, message: Vec<u8>) {
+        let on_binary_message = self.inner.borrow().on_binary_message.clone();
+        match on_binary_message {
+            Some(on_binary_message) => on_binary_message.call(message),
+            None => debug!("dropping binary message without a callback for it"),
+        }
+    }
+
+    /// Opens an additional data channel with the other peer on an established connection,
+    /// e.g. an unreliable one for frequent state updates, and renegotiates the connection.
+    /// Opening a channel under a name that's already in use replaces the old channel,
+    /// which is how parameters of a channel are changed.
+    pub async fn open_channel(
+        &self,
+        name: &str,
+        config: ChannelConfig,
+    ) -> Result<(), NetworkError> {
+        let negotiation = self
+            .inner
+            .borrow()
+            .negotiation
+            .clone()
+            .ok_or(NetworkError::DataChannelNotOpen)?;
+        let data_channel = negotiation
+            .peer_connection()
+            .create_data_channel(name, &config)
+            .await?;
+        self.add_channel(data_channel);
+        // browsers only need negotiation for the first data channel of a connection,
+        // so it's started explicitly instead of waiting for negotiationneeded
+        websocket_handler::send_offer(self).await
+    }
+
+    /// Messages of channels named `name` are passed to the handler instead of `on_message`,
+    /// and their opening isn't announced to the application
+    pub(crate) fn handle_channel(&self, name: &str, handler: impl FnMut(String) + 'static) {
+        self.inner
+            .borrow_mut()
+            .channel_handlers
+            .insert(name.to_string(), SharedCallback::new(handler));
+    }
+
+    pub(crate) fn has_channel(&self, name: &str) -> bool {
+        self.inner.borrow().channels.contains_key(name)
+    }
+
+    /// Whether the data channel the connection was opened with is open
+    pub(crate) fn is_open(&self) -> bool {
         self.inner
             .borrow()
             .data_channel
             .as_ref()
-            .ok_or_else(|| JsValue::from_str("no data channel set on instance yet"))?
-            // this is an ugly fix to the fact, that if you send empty string as message
-            // webrtc fails with a cryptic "The operation failed for an operation-specific reason"
-            // message
-            .send_with_str(&format!("x{}", message))
+            .is_some_and(DataChannel::is_open)
+    }
+
+    /// Starts handling a channel opened with [NetworkManager::open_channel] by either peer
+    pub(crate) fn add_channel(&self, data_channel: DataChannel) {
+        let name = data_channel.label();
+        let handler = self.inner.borrow().channel_handlers.get(&name).cloned();
+        if let Some(handler) = handler {
+            set_data_channel_on_message(&data_channel, self.clone(), move |message| {
+                handler.call(message)
+            });
+        } else {
+            if let Some(on_message) = self.inner.borrow().on_message.clone() {
+                set_data_channel_on_message(&data_channel, self.clone(), move |message| {
+                    on_message.call(message)
+                });
+            }
+            let network_manager = self.clone();
+            let name = name.clone();
+            data_channel.on_open(move || {
+                debug!("channel {} is now open", name);
+                let (on_channel_open, peer_id) = {
+                    let inner = network_manager.inner.borrow();
+                    (inner.on_channel_open.clone(), inner.peer_id)
+                };
+                if let Some(on_channel_open) = on_channel_open {
+                    on_channel_open.call(name.clone());
+                }
+                if let Some(peer_id) = peer_id {
+                    network_manager.emit(NetworkEvent::ChannelOpened(peer_id, name.clone()));
+                }
+            });
+        }
+
+        {
+            let network_manager = self.clone();
+            let closed = data_channel.clone();
+            data_channel.on_close(move || {
+                debug!("channel {} closed", closed.label());
+                network_manager.remove_channel(&closed);
+            });
+        }
+
+        let replaced = self.inner.borrow_mut().channels.insert(name, data_channel);
+        if let Some(replaced) = replaced {
+            replaced.close();
+        }
+    }
+
+    /// Forgets a channel closed by the other peer, along with its handler
+    fn remove_channel(&self, data_channel: &DataChannel) {
+        let name = data_channel.label();
+        {
+            let mut inner = self.inner.borrow_mut();
+            if inner
+                .channels
+                .get(&name)
+                .is_some_and(|channel| channel.is_same(data_channel))
+            {
+                inner.channels.remove(&name);
+                inner.channel_handlers.remove(&name);
+            }
+        }
+        // frees callbacks of the channel, outside of the one that's running
+        let data_channel = data_channel.clone();
+        spawn_local(async move { data_channel.close() });
+    }
+
+    /// Closes the connection with the other peer and leaves the signaling session.
+    /// All callbacks are dropped and [NetworkEvents] end, the instance can't be used afterwards.
+    pub fn close(&self) {
+        let (
+            session_id,
+            signaling_socket,
+            negotiation,
+            data_channel,
+            replaced_data_channels,
+            channels,
+            connect_waiter,
+        ) = {
+            let mut inner = self.inner.borrow_mut();
+            inner.event_sink = None;
+            inner.on_message = None;
+            inner.on_channel_open = None;
+            inner.on_binary_message = None;
+            inner.channel_handlers.clear();
+            inner.on_latency_update = None;
+            inner.pending_candidates = PendingCandidates::default();
+            inner.ping_timer.stop();
+            (
+                inner.session_id.clone(),
+                inner.signaling_socket.clone(),
+                inner.negotiation.take(),
+                inner.data_channel.take(),
+                std::mem::take(&mut inner.replaced_data_channels),
+                std::mem::take(&mut inner.channels),
+                inner.connect_waiter.clone(),
+            )
+        };
+        for data_channel in data_channel.iter().chain(&replaced_data_channels) {
+            data_channel.close();
+        }
+        for data_channel in channels.values() {
+            data_channel.close();
+        }
+        if let Some(negotiation) = negotiation {
+            negotiation.peer_connection().close();
+        }
+        if let Err(error) = signaling_socket.send(&SignalMessage::SessionLeave(session_id)) {
+            debug!("failed to leave signaling session: {}", error);
+        }
+        signaling_socket.close();
+        connect_waiter.resolve(Err(NetworkError::SignalingClosed));
+    }
+
+    /// Last error reported by the signaling server, e.g. [SignalingError::UnsupportedVersion]
+    /// when it doesn't speak the protocol version of this library.
+    pub fn signaling_error(&self) -> Option<SignalingError> {
+        self.inner.borrow().signaling_error.clone()
     }
 }
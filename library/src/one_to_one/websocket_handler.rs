@@ -1,95 +1,126 @@
-use crate::utils::{create_sdp_answer, create_sdp_offer, IceCandidate};
+use crate::candidates::add_pending_candidates;
+use crate::one_to_one::NetworkManager;
+use crate::platform::{PlatformError, SignalingSocket};
+use crate::{NetworkError, SignalingError};
 use ::log::{debug, error, info};
-use rusty_games_protocol::one_to_one::SignalMessage;
-use wasm_bindgen::JsValue;
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{
-    RtcIceCandidate, RtcIceCandidateInit, RtcPeerConnection, RtcSdpType, RtcSessionDescriptionInit,
-    WebSocket,
-};
+use rusty_games_protocol::signal::{Encoding, SignalMessage};
 
 /// Basically a state automata spread across host, client and signaling server,
 /// handling each step in session and then WebRTC setup.
 pub(crate) async fn handle_websocket_message(
     message: SignalMessage,
-    peer_connection: RtcPeerConnection,
-    websocket: WebSocket,
-) -> Result<(), JsValue> {
+    network_manager: NetworkManager,
+    signaling_socket: SignalingSocket,
+) -> Result<(), NetworkError> {
+    let negotiation = network_manager
+        .inner
+        .borrow()
+        .negotiation
+        .clone()
+        .ok_or_else(|| PlatformError::from("no peer connection set on instance yet"))?;
     match message {
-        SignalMessage::SessionJoin(_session_id) => {
-            error!("error, SessionStartOrJoin should only be sent by peers to signaling server");
+        SignalMessage::Hello { .. }
+        | SignalMessage::SessionJoin(..)
+        | SignalMessage::SessionLeave(..) => {
+            error!("error, Hello, SessionJoin and SessionLeave should only be sent by peers to signaling server");
         }
-        SignalMessage::SessionReady(session_id, is_host) => {
+        SignalMessage::Welcome {
+            protocol_version,
+            capabilities,
+            ..
+        } => {
+            debug!(
+                "signaling server accepted protocol version {} with capabilities: {:?}",
+                protocol_version, capabilities
+            );
+            signaling_socket.set_encoding(Encoding::negotiated(&capabilities));
+        }
+        SignalMessage::SessionReady(session_id, peer_id, is_host) => {
             info!("peer received info that session is ready {:?}", session_id);
+            network_manager.inner.borrow_mut().peer_id = Some(peer_id);
+            negotiation.set_polite(!is_host);
             if is_host {
-                let offer = create_sdp_offer(&peer_connection).await?;
-                let signal_message = SignalMessage::SdpOffer(session_id.clone(), offer);
-                let signal_message = serde_json_wasm::to_string(&signal_message)
-                    .expect("failed to serialize SignalMessage");
-                websocket.send_with_str(&signal_message)?;
+                send_offer(&network_manager).await?;
                 debug!("(is_host: {}) sent an offer successfully", is_host);
             }
         }
-        SignalMessage::SdpOffer(session_id, offer) => {
-            let answer = create_sdp_answer(&peer_connection, offer)
-                .await
-                .expect("failed to create SDP answer");
-            debug!("received an offer and created an answer: {}", answer);
-            let signal_message = SignalMessage::SdpAnswer(session_id, answer);
-            let signal_message = serde_json_wasm::to_string(&signal_message)
-                .expect("failed to serialize SignalMessage");
-            websocket
-                .send_with_str(&signal_message)
-                .expect("failed to send SPD answer to signaling server");
+        SignalMessage::SdpOffer(session_id, peer_id, offer) => {
+            network_manager.inner.borrow_mut().peer_id = Some(peer_id);
+            if let Some(answer) = negotiation.accept_offer(offer).await? {
+                debug!("received an offer and created an answer: {:?}", answer);
+                let signal_message = SignalMessage::SdpAnswer(session_id, peer_id, answer);
+                signaling_socket.send(&signal_message)?;
+                add_pending_candidates(&negotiation, || {
+                    network_manager.inner.borrow_mut().pending_candidates.pop()
+                })
+                .await?;
+            }
         }
-        SignalMessage::SdpAnswer(session_id, answer) => {
-            let mut remote_session_description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
-            remote_session_description.sdp(&answer);
-            JsFuture::from(peer_connection.set_remote_description(&remote_session_description))
-                .await
-                .expect("failed to set remote descripiton");
+        SignalMessage::SdpAnswer(session_id, _peer_id, answer) => {
             debug!(
-                "received answer from peer and set remote description: {}, {:?}",
+                "received answer from peer, setting remote description: {:?}, {:?}",
                 answer, session_id
             );
+            negotiation.accept_answer(answer).await?;
+            add_pending_candidates(&negotiation, || {
+                network_manager.inner.borrow_mut().pending_candidates.pop()
+            })
+            .await?;
         }
-        SignalMessage::IceCandidate(_session_id, ice_candidate) => {
-            debug!("peer received ice candidate: {}", &ice_candidate);
-            let ice_candidate = serde_json_wasm::from_str::<IceCandidate>(&ice_candidate)
-                .expect("failed to deserialize IceCandidate");
-
-            let mut rtc_candidate = RtcIceCandidateInit::new("");
-            rtc_candidate.candidate(&ice_candidate.candidate);
-            rtc_candidate.sdp_m_line_index(ice_candidate.sdp_m_line_index);
-            rtc_candidate.sdp_mid(ice_candidate.sdp_mid.as_deref());
-
-            let rtc_candidate =
-                RtcIceCandidate::new(&rtc_candidate).expect("failed to create new RtcIceCandidate");
-            JsFuture::from(
-                peer_connection.add_ice_candidate_with_opt_rtc_ice_candidate(Some(&rtc_candidate)),
-            )
-            .await
-            .expect("failed to add ICE candidate");
-            debug!("added ice candidate {:?}", ice_candidate);
+        SignalMessage::IceCandidate(_session_id, _peer_id, ice_candidate) => {
+            debug!("peer received ice candidate: {:?}", &ice_candidate);
+            let ice_candidate = network_manager
+                .inner
+                .borrow_mut()
+                .pending_candidates
+                .push(ice_candidate);
+            if let Some(ice_candidate) = ice_candidate {
+                negotiation.add_ice_candidate(ice_candidate.clone()).await?;
+                debug!("added ice candidate {:?}", ice_candidate);
+            }
         }
-        SignalMessage::Error(session_id, error) => {
-            error!(
-                "signaling server returned error: session id: {:?}, error:{}",
-                session_id, error
-            );
+        SignalMessage::Error(session_id, code, description) => {
+            let error = SignalingError::new(session_id, code, description);
+            error!("{}", error);
+            network_manager.inner.borrow_mut().signaling_error = Some(error.clone());
+            network_manager.report_error(error.into());
         }
     }
 
     Ok(())
 }
 
-#[cfg(test)]
+/// Starts a new offer/answer exchange with the other peer, unless one is already in progress
+pub(crate) async fn send_offer(network_manager: &NetworkManager) -> Result<(), NetworkError> {
+    let (negotiation, signaling_socket, session_id, peer_id) = {
+        let inner = network_manager.inner.borrow();
+        match (&inner.negotiation, inner.peer_id) {
+            (Some(negotiation), Some(peer_id)) => (
+                negotiation.clone(),
+                inner.signaling_socket.clone(),
+                inner.session_id.clone(),
+                peer_id,
+            ),
+            _ => {
+                debug!("the other peer is not known yet, not sending an offer");
+                return Ok(());
+            }
+        }
+    };
+    if let Some(offer) = negotiation.create_offer().await? {
+        signaling_socket.send(&SignalMessage::SdpOffer(session_id, peer_id, offer))?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, not(feature = "native")))]
 mod test {
     use super::*;
     use mockall::mock;
     use wasm_bindgen_test::wasm_bindgen_test_configure;
 
-    use rusty_games_protocol::SessionId;
+    use crate::ConnectionType;
+    use rusty_games_protocol::{SessionId, UserId};
 
     wasm_bindgen_test_configure!(run_in_browser);
 
@@ -98,23 +129,39 @@ mod test {
     }
 
     // #[wasm_bindgen_test]
+    #[allow(dead_code)]
     async fn test_handle_session_ready_signal_is_successful() {
-        let message =
-            SignalMessage::SessionReady(SessionId::new("dummy-session-id".to_string()), true);
-        let peer_connection = RtcPeerConnection::new().unwrap();
+        let session_id = SessionId::new("dummy-session-id".to_string());
+        let message = SignalMessage::SessionReady(session_id.clone(), UserId::new(1), true);
 
         // TODO: this should be mocked, but how do you pass a mock to a function expecting different type?
         //  I could introduce a trait, implement it for web_sys::WebSocket and MockWebSocket as well,
         //  but that's a lot of work...
         //  This is a integration test for now.
-        let websocket = WebSocket::new("ws://0.0.0.0:9001/ws")
-            .expect("local signaling server instance was not found");
-        websocket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+        let network_manager = NetworkManager::new(
+            "ws://0.0.0.0:9001/signal",
+            session_id,
+            ConnectionType::Local,
+        )
+        .expect("local signaling server instance was not found");
+        let signaling_socket = network_manager.inner.borrow().signaling_socket.clone();
+        let peer_connection = crate::platform::PeerConnection::new(&ConnectionType::Local)
+            .await
+            .unwrap();
+        network_manager.inner.borrow_mut().negotiation =
+            Some(crate::negotiation::Negotiation::new(
+                peer_connection.clone(),
+                false,
+                Default::default(),
+            ));
 
         // FIXME: this fails because peer_connection state gets modified in other tests
-        handle_websocket_message(message, peer_connection.clone(), websocket)
+        handle_websocket_message(message, network_manager, signaling_socket)
             .await
             .unwrap();
-        assert!(peer_connection.local_description().is_some());
+        assert!(peer_connection
+            .rtc_peer_connection()
+            .local_description()
+            .is_some());
     }
 }
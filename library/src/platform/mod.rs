@@ -0,0 +1,29 @@
+//! Thin wrappers around WebRTC and websocket implementations of the target platform,
+//! so that topologies can be written once for both of them.
+//!
+//! By default browser APIs from `web-sys` are used,
+//! with the `native` feature a pure Rust WebRTC stack and a tokio websocket client are used instead.
+//!
+//! Every platform provides the same set of types:
+//! * `PeerConnection` - handles SDP negotiation and ICE candidates, creates data channels
+//! * `DataChannel` - sends and receives messages to and from a single peer
+//! * `SignalingSocket` - websocket connection with the signaling server
+//! * `PlatformError` - error returned by all of the above
+//!
+//! as well as a `spawn_local` function that runs a future on the current thread.
+//! Callbacks registered on those types are called on the current thread as well,
+//! so they don't have to be `Send`.
+
+#[cfg(feature = "native")]
+mod native;
+#[cfg(not(feature = "native"))]
+mod web;
+
+#[cfg(feature = "native")]
+pub use native::PlatformError;
+#[cfg(feature = "native")]
+pub(crate) use native::*;
+#[cfg(not(feature = "native"))]
+pub use web::PlatformError;
+#[cfg(not(feature = "native"))]
+pub(crate) use web::*;
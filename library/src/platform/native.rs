@@ -0,0 +1,996 @@
+use crate::send_queue::{self, SendQueue};
+use crate::stats::{CandidatePairReport, StatsReport};
+use crate::{
+    CandidateType, ChannelConfig, ChannelStats, ConnectionStats, ConnectionType,
+    IceConnectionState, NetworkError, Reliability,
+};
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use log::{debug, error};
+use rusty_games_protocol::rtc::{IceCandidate, SdpAnswer, SdpOffer};
+use rusty_games_protocol::signal::{Encoding, SignalMessage};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::future::Future;
+use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::Message;
+use webrtc::api::APIBuilder;
+use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::ice::candidate::CandidatePairState;
+use webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
+use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::sdp::sdp_type::RTCSdpType;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::signaling_state::RTCSignalingState;
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::stats::StatsReportType;
+
+/// Error of the underlying platform, natively it's a description of what went wrong
+/// in the WebRTC stack or the websocket connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformError {
+    message: String,
+}
+
+impl fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for PlatformError {}
+
+impl From<String> for PlatformError {
+    fn from(message: String) -> Self {
+        PlatformError { message }
+    }
+}
+
+impl From<&str> for PlatformError {
+    fn from(message: &str) -> Self {
+        PlatformError::from(message.to_string())
+    }
+}
+
+impl From<webrtc::Error> for PlatformError {
+    fn from(error: webrtc::Error) -> Self {
+        PlatformError::from(error.to_string())
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for PlatformError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        PlatformError::from(error.to_string())
+    }
+}
+
+/// Requires to be called from within a [tokio::task::LocalSet]
+pub(crate) fn spawn_local(future: impl Future<Output = ()> + 'static) {
+    tokio::task::spawn_local(future);
+}
+
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// Time elapsed since the first call, it never goes back
+pub(crate) fn now() -> Duration {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+type Callback<A> = Box<dyn FnMut(A)>;
+
+/// Callback registered by the library, that can be replaced or cleared at any time
+struct Handler<A> {
+    callback: RefCell<Option<Callback<A>>>,
+    cleared: Cell<bool>,
+}
+
+impl<A> Handler<A> {
+    fn new() -> Self {
+        Handler {
+            callback: RefCell::new(None),
+            cleared: Cell::new(false),
+        }
+    }
+
+    fn set(&self, callback: impl FnMut(A) + 'static) {
+        self.cleared.set(false);
+        *self.callback.borrow_mut() = Some(Box::new(callback));
+    }
+
+    /// Drops the callback, along with everything it captured
+    fn clear(&self) {
+        self.cleared.set(true);
+        self.callback.borrow_mut().take();
+    }
+
+    fn call(&self, argument: A) {
+        // taken out for the duration of the call, so that the callback can replace or clear itself
+        let callback = self.callback.borrow_mut().take();
+        if let Some(mut callback) = callback {
+            callback(argument);
+            let mut slot = self.callback.borrow_mut();
+            if slot.is_none() && !self.cleared.get() {
+                *slot = Some(callback);
+            }
+        }
+    }
+}
+
+/// Events forwarded from the WebRTC and websocket tasks to the current thread.
+/// They are queued until the first handler is registered,
+/// so that nothing is lost in between creating an object and setting its callbacks.
+struct EventQueue<E>(RefCell<Option<UnboundedReceiver<E>>>);
+
+impl<E: 'static> EventQueue<E> {
+    fn new() -> (UnboundedSender<E>, Self) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (sender, EventQueue(RefCell::new(Some(receiver))))
+    }
+
+    fn from_receiver(receiver: UnboundedReceiver<E>) -> Self {
+        EventQueue(RefCell::new(Some(receiver)))
+    }
+
+    /// Starts handling events on the current thread, does nothing if already started
+    fn start(&self, mut handle: impl FnMut(E) + 'static) {
+        if let Some(mut receiver) = self.0.borrow_mut().take() {
+            spawn_local(async move {
+                while let Some(event) = receiver.recv().await {
+                    handle(event);
+                }
+            });
+        }
+    }
+}
+
+enum PeerConnectionEvent {
+    IceCandidate(IceCandidate),
+    DataChannel(Arc<RTCDataChannel>, UnboundedReceiver<DataChannelEvent>),
+    IceConnectionFailed,
+    NegotiationNeeded,
+}
+
+struct PeerConnectionHandlers {
+    events: EventQueue<PeerConnectionEvent>,
+    on_ice_candidate: Handler<IceCandidate>,
+    on_data_channel: Handler<DataChannel>,
+    on_ice_connection_failed: Handler<()>,
+    on_negotiation_needed: Handler<()>,
+}
+
+/// Cloneable pointer to a native `RTCPeerConnection`
+#[derive(Clone)]
+pub(crate) struct PeerConnection {
+    peer_connection: Arc<RTCPeerConnection>,
+    handlers: Rc<PeerConnectionHandlers>,
+}
+
+impl fmt::Debug for PeerConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerConnection")
+            .field("signaling_state", &self.peer_connection.signaling_state())
+            .field(
+                "ice_connection_state",
+                &self.peer_connection.ice_connection_state(),
+            )
+            .finish()
+    }
+}
+
+impl PeerConnection {
+    pub(crate) async fn new(connection_type: &ConnectionType) -> Result<Self, PlatformError> {
+        let api = APIBuilder::new().build();
+        let peer_connection = Arc::new(
+            api.new_peer_connection(create_configuration(connection_type))
+                .await?,
+        );
+        set_peer_connection_logging(&peer_connection);
+
+        let (events_sender, events) = EventQueue::new();
+        {
+            let events_sender = events_sender.clone();
+            peer_connection.on_negotiation_needed(Box::new(move || {
+                debug!("on negotiation needed event occurred");
+                let _ = events_sender.send(PeerConnectionEvent::NegotiationNeeded);
+                Box::pin(async {})
+            }));
+        }
+        {
+            let events_sender = events_sender.clone();
+            peer_connection.on_ice_connection_state_change(Box::new(
+                move |state: RTCIceConnectionState| {
+                    debug!("connection state change: {:?}", state);
+                    if state == RTCIceConnectionState::Failed {
+                        let _ = events_sender.send(PeerConnectionEvent::IceConnectionFailed);
+                    }
+                    Box::pin(async {})
+                },
+            ));
+        }
+        {
+            let events_sender = events_sender.clone();
+            peer_connection.on_ice_candidate(Box::new(
+                move |candidate: Option<RTCIceCandidate>| {
+                    // None means gathering is complete
+                    let candidate = match candidate.map(|candidate| candidate.to_json()) {
+                        Some(Ok(candidate)) => to_ice_candidate(candidate),
+                        Some(Err(error)) => {
+                            error!("failed to serialize ICE candidate: {}", error);
+                            return Box::pin(async {});
+                        }
+                        None => IceCandidate::end_of_candidates(),
+                    };
+                    let _ = events_sender.send(PeerConnectionEvent::IceCandidate(candidate));
+                    Box::pin(async {})
+                },
+            ));
+        }
+        peer_connection.on_data_channel(Box::new(move |data_channel: Arc<RTCDataChannel>| {
+            let events_sender = events_sender.clone();
+            Box::pin(async move {
+                // forwarding must be set up right away, otherwise first messages might be lost
+                let data_channel_events = forward_data_channel_events(&data_channel).await;
+                let _ = events_sender.send(PeerConnectionEvent::DataChannel(
+                    data_channel,
+                    data_channel_events,
+                ));
+            })
+        }));
+
+        Ok(PeerConnection {
+            peer_connection,
+            handlers: Rc::new(PeerConnectionHandlers {
+                events,
+                on_ice_candidate: Handler::new(),
+                on_data_channel: Handler::new(),
+                on_ice_connection_failed: Handler::new(),
+                on_negotiation_needed: Handler::new(),
+            }),
+        })
+    }
+
+    pub(crate) async fn create_data_channel(
+        &self,
+        label: &str,
+        config: &ChannelConfig,
+    ) -> Result<DataChannel, PlatformError> {
+        let (max_retransmits, max_packet_life_time) = match config.reliability {
+            Reliability::Reliable => (None, None),
+            Reliability::MaxRetransmits(max_retransmits) => (Some(max_retransmits), None),
+            Reliability::MaxPacketLifeTime(max_packet_life_time) => {
+                (None, Some(max_packet_life_time))
+            }
+        };
+        let data_channel_init = RTCDataChannelInit {
+            ordered: Some(config.ordered),
+            max_retransmits,
+            max_packet_life_time,
+            ..Default::default()
+        };
+        let data_channel = self
+            .peer_connection
+            .create_data_channel(label, Some(data_channel_init))
+            .await?;
+        debug!(
+            "data_channel created with label: {:?}",
+            data_channel.label()
+        );
+        let data_channel_events = forward_data_channel_events(&data_channel).await;
+        Ok(DataChannel::new(data_channel, data_channel_events))
+    }
+
+    pub(crate) fn ice_connection_state(&self) -> IceConnectionState {
+        match self.peer_connection.ice_connection_state() {
+            RTCIceConnectionState::Unspecified | RTCIceConnectionState::New => {
+                IceConnectionState::New
+            }
+            RTCIceConnectionState::Checking => IceConnectionState::Checking,
+            RTCIceConnectionState::Connected => IceConnectionState::Connected,
+            RTCIceConnectionState::Completed => IceConnectionState::Completed,
+            RTCIceConnectionState::Disconnected => IceConnectionState::Disconnected,
+            RTCIceConnectionState::Failed => IceConnectionState::Failed,
+            RTCIceConnectionState::Closed => IceConnectionState::Closed,
+        }
+    }
+
+    /// webrtc-rs doesn't measure round-trip time of candidate pairs, so it's never reported
+    pub(crate) async fn stats(&self) -> Result<ConnectionStats, PlatformError> {
+        let mut stats_report = StatsReport::default();
+        for report in self.peer_connection.get_stats().await.reports.into_values() {
+            match report {
+                StatsReportType::CandidatePair(pair) => {
+                    stats_report.candidate_pairs.push(CandidatePairReport {
+                        id: pair.id,
+                        local_candidate_id: pair.local_candidate_id,
+                        remote_candidate_id: pair.remote_candidate_id,
+                        succeeded: pair.state == CandidatePairState::Succeeded,
+                        nominated: pair.nominated,
+                        selected: false,
+                        current_round_trip_time: Some(pair.current_round_trip_time),
+                    })
+                }
+                StatsReportType::LocalCandidate(candidate)
+                | StatsReportType::RemoteCandidate(candidate) => {
+                    if let Some(candidate_type) =
+                        CandidateType::from_name(&candidate.candidate_type.to_string())
+                    {
+                        stats_report
+                            .candidate_types
+                            .insert(candidate.id, candidate_type);
+                    }
+                }
+                StatsReportType::DataChannel(data_channel) => {
+                    stats_report.channels.push(ChannelStats {
+                        label: data_channel.label,
+                        bytes_sent: data_channel.bytes_sent as u64,
+                        bytes_received: data_channel.bytes_received as u64,
+                        messages_sent: data_channel.messages_sent as u64,
+                        messages_received: data_channel.messages_received as u64,
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(stats_report.into_connection_stats(self.ice_connection_state()))
+    }
+
+    /// Create an offer and set it as local description
+    pub(crate) async fn create_offer(&self) -> Result<SdpOffer, PlatformError> {
+        let offer = self
+            .peer_connection
+            .create_offer(None)
+            .await
+            .map_err(|error| {
+                PlatformError::from(format!("failed to create an SDP offer: {}", error))
+            })?;
+        self.peer_connection
+            .set_local_description(offer.clone())
+            .await
+            .map_err(|error| {
+                PlatformError::from(format!("failed to set local description: {}", error))
+            })?;
+
+        Ok(SdpOffer { sdp: offer.sdp })
+    }
+
+    /// Set the offer as remote description, then create an answer and set it as local description
+    pub(crate) async fn create_answer(&self, offer: SdpOffer) -> Result<SdpAnswer, PlatformError> {
+        self.peer_connection
+            .set_remote_description(RTCSessionDescription::offer(offer.sdp)?)
+            .await?;
+
+        let answer = self.peer_connection.create_answer(None).await?;
+        self.peer_connection
+            .set_local_description(answer.clone())
+            .await?;
+
+        Ok(SdpAnswer { sdp: answer.sdp })
+    }
+
+    pub(crate) async fn set_remote_answer(&self, answer: SdpAnswer) -> Result<(), PlatformError> {
+        self.peer_connection
+            .set_remote_description(RTCSessionDescription::answer(answer.sdp)?)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether there is no offer/answer exchange in progress
+    pub(crate) fn is_stable(&self) -> bool {
+        self.peer_connection.signaling_state() == RTCSignalingState::Stable
+    }
+
+    /// Discards the local offer, that hasn't been answered yet.
+    /// webrtc-rs can't roll an offer back, so it's answered with the session description
+    /// the peer agreed to in the last exchange instead, which leaves the session as it was.
+    /// Only renegotiations can collide, so there always is one.
+    pub(crate) async fn rollback(&self) -> Result<(), PlatformError> {
+        let (Some(local), Some(remote)) = (
+            self.peer_connection.current_local_description().await,
+            self.peer_connection.current_remote_description().await,
+        ) else {
+            return Err(PlatformError::from(
+                "there is no negotiated session to roll back to",
+            ));
+        };
+        let mut sdp = remote.sdp;
+        if remote.sdp_type == RTCSdpType::Offer {
+            // the peer offered to take either DTLS role, the answer states the one it took
+            let setup = if local.sdp.contains("a=setup:active") {
+                "a=setup:passive"
+            } else {
+                "a=setup:active"
+            };
+            sdp = sdp.replace("a=setup:actpass", setup);
+        }
+        self.peer_connection
+            .set_remote_description(RTCSessionDescription::answer(sdp)?)
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn add_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<(), PlatformError> {
+        self.peer_connection
+            .add_ice_candidate(RTCIceCandidateInit {
+                candidate: ice_candidate.candidate,
+                sdp_mid: ice_candidate.sdp_mid,
+                sdp_mline_index: ice_candidate.sdp_m_line_index,
+                username_fragment: None,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Called for every gathered local ICE candidate
+    pub(crate) fn on_ice_candidate(&self, callback: impl FnMut(IceCandidate) + 'static) {
+        self.handlers.on_ice_candidate.set(callback);
+        self.start_handling_events();
+    }
+
+    /// Called for every data channel created by the other peer
+    pub(crate) fn on_data_channel(&self, callback: impl FnMut(DataChannel) + 'static) {
+        self.handlers.on_data_channel.set(callback);
+        self.start_handling_events();
+    }
+
+    /// Called when ICE fails to find a working pair of candidates
+    pub(crate) fn on_ice_connection_failed(&self, mut callback: impl FnMut() + 'static) {
+        self.handlers
+            .on_ice_connection_failed
+            .set(move |()| callback());
+        self.start_handling_events();
+    }
+
+    /// Called when changes to the connection require a new offer/answer exchange
+    pub(crate) fn on_negotiation_needed(&self, mut callback: impl FnMut() + 'static) {
+        self.handlers
+            .on_negotiation_needed
+            .set(move |()| callback());
+        self.start_handling_events();
+    }
+
+    /// Closes the connection along with all of its data channels and drops all callbacks
+    pub(crate) fn close(&self) {
+        self.handlers.on_ice_candidate.clear();
+        self.handlers.on_data_channel.clear();
+        self.handlers.on_ice_connection_failed.clear();
+        self.handlers.on_negotiation_needed.clear();
+        let peer_connection = self.peer_connection.clone();
+        spawn_local(async move {
+            if let Err(error) = peer_connection.close().await {
+                error!("failed to close peer connection: {}", error);
+            }
+        });
+    }
+
+    fn start_handling_events(&self) {
+        let handlers = self.handlers.clone();
+        self.handlers.events.start(move |event| match event {
+            PeerConnectionEvent::IceCandidate(candidate) => {
+                handlers.on_ice_candidate.call(candidate)
+            }
+            PeerConnectionEvent::IceConnectionFailed => handlers.on_ice_connection_failed.call(()),
+            PeerConnectionEvent::NegotiationNeeded => handlers.on_negotiation_needed.call(()),
+            PeerConnectionEvent::DataChannel(data_channel, data_channel_events) => handlers
+                .on_data_channel
+                .call(DataChannel::new(data_channel, data_channel_events)),
+        });
+    }
+}
+
+enum DataChannelEvent {
+    Open,
+    Message(Vec<u8>),
+    BufferedAmountLow,
+    Closed,
+}
+
+struct DataChannelHandlers {
+    events: EventQueue<DataChannelEvent>,
+    on_open: Handler<()>,
+    on_message: Handler<Vec<u8>>,
+    on_buffered_amount_low: Handler<()>,
+    on_close: Handler<()>,
+}
+
+/// Bytes of sent messages that are still waiting to be transmitted,
+/// either in the queue of the sending task, or in the buffer of webrtc-rs
+#[derive(Debug, Default)]
+struct BufferedAmount {
+    queued: Cell<usize>,
+    /// Last known buffered amount of webrtc-rs
+    sending: Cell<usize>,
+    low_threshold: Cell<usize>,
+}
+
+impl BufferedAmount {
+    fn total(&self) -> usize {
+        self.queued.get() + self.sending.get()
+    }
+
+    fn is_low(&self) -> bool {
+        self.total() <= self.low_threshold.get()
+    }
+}
+
+/// Cloneable pointer to a native `RTCDataChannel`
+#[derive(Clone)]
+pub(crate) struct DataChannel {
+    data_channel: Arc<RTCDataChannel>,
+    outgoing: UnboundedSender<Vec<u8>>,
+    buffered_amount: Rc<BufferedAmount>,
+    send_queue: Rc<SendQueue>,
+    handlers: Rc<DataChannelHandlers>,
+}
+
+impl fmt::Debug for DataChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataChannel")
+            .field("label", &self.data_channel.label())
+            .field("ready_state", &self.data_channel.ready_state())
+            .finish()
+    }
+}
+
+impl DataChannel {
+    fn new(
+        data_channel: Arc<RTCDataChannel>,
+        data_channel_events: UnboundedReceiver<DataChannelEvent>,
+    ) -> Self {
+        let buffered_amount = Rc::new(BufferedAmount::default());
+        let handlers = Rc::new(DataChannelHandlers {
+            events: EventQueue::from_receiver(data_channel_events),
+            on_open: Handler::new(),
+            on_message: Handler::new(),
+            on_buffered_amount_low: Handler::new(),
+            on_close: Handler::new(),
+        });
+
+        // sending is asynchronous natively, so messages are queued and sent in order by a task
+        let (outgoing, mut outgoing_receiver) = mpsc::unbounded_channel::<Vec<u8>>();
+        {
+            let data_channel = data_channel.clone();
+            let buffered_amount = buffered_amount.clone();
+            let handlers = handlers.clone();
+            spawn_local(async move {
+                while let Some(message) = outgoing_receiver.recv().await {
+                    let length = message.len();
+                    if let Err(error) = data_channel.send(&Bytes::from(message)).await {
+                        error!("data channel error: {}", error);
+                    }
+                    buffered_amount
+                        .queued
+                        .set(buffered_amount.queued.get() - length);
+                    buffered_amount
+                        .sending
+                        .set(data_channel.buffered_amount().await);
+                    if buffered_amount.is_low() {
+                        handlers.on_buffered_amount_low.call(());
+                    }
+                }
+            });
+        }
+
+        let data_channel = DataChannel {
+            data_channel,
+            outgoing,
+            buffered_amount,
+            send_queue: Rc::new(SendQueue::default()),
+            handlers,
+        };
+        {
+            let data_channel_clone = data_channel.clone();
+            data_channel
+                .handlers
+                .on_buffered_amount_low
+                .set(move |()| send_queue::wake(&data_channel_clone));
+        }
+        data_channel
+    }
+
+    pub(crate) fn label(&self) -> String {
+        self.data_channel.label().to_string()
+    }
+
+    /// Whether both handles refer to the same data channel
+    pub(crate) fn is_same(&self, other: &DataChannel) -> bool {
+        Arc::ptr_eq(&self.data_channel, &other.data_channel)
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.data_channel.ready_state() == RTCDataChannelState::Open
+    }
+
+    /// Sends the bytes as a single binary message
+    pub(crate) fn send(&self, message: &[u8]) -> Result<(), NetworkError> {
+        if !self.is_open() {
+            return Err(NetworkError::DataChannelNotOpen);
+        }
+        self.outgoing
+            .send(message.to_vec())
+            .map_err(|_| NetworkError::DataChannelNotOpen)?;
+        self.buffered_amount
+            .queued
+            .set(self.buffered_amount.queued.get() + message.len());
+        Ok(())
+    }
+
+    /// Bytes of sent messages that are still waiting to be transmitted
+    pub(crate) fn buffered_amount(&self) -> usize {
+        self.buffered_amount.total()
+    }
+
+    /// Buffered amount below which waiting senders are woken up
+    pub(crate) fn set_buffered_amount_low_threshold(&self, threshold: usize) {
+        self.buffered_amount.low_threshold.set(threshold);
+        let data_channel = self.data_channel.clone();
+        spawn_local(async move {
+            data_channel
+                .set_buffered_amount_low_threshold(threshold)
+                .await;
+        });
+    }
+
+    pub(crate) fn send_queue(&self) -> &SendQueue {
+        &self.send_queue
+    }
+
+    pub(crate) fn on_open(&self, mut callback: impl FnMut() + 'static) {
+        self.handlers.on_open.set(move |()| callback());
+        self.start_handling_events();
+    }
+
+    /// Called once the data channel closes, e.g. when the other peer leaves
+    pub(crate) fn on_close(&self, mut callback: impl FnMut() + 'static) {
+        self.handlers.on_close.set(move |()| callback());
+        self.start_handling_events();
+    }
+
+    /// Called with the content of every message received, text messages as their UTF-8 bytes
+    pub(crate) fn on_message(&self, callback: impl FnMut(Vec<u8>) + 'static) {
+        self.handlers.on_message.set(callback);
+        self.start_handling_events();
+    }
+
+    /// Closes the data channel and drops all callbacks
+    pub(crate) fn close(&self) {
+        self.handlers.on_open.clear();
+        self.handlers.on_message.clear();
+        self.handlers.on_buffered_amount_low.clear();
+        self.handlers.on_close.clear();
+        self.send_queue.close();
+        let data_channel = self.data_channel.clone();
+        spawn_local(async move {
+            if let Err(error) = data_channel.close().await {
+                error!("failed to close data channel: {}", error);
+            }
+        });
+    }
+
+    fn start_handling_events(&self) {
+        let handlers = self.handlers.clone();
+        // a weak pointer, as the data channel owns the sender of its events
+        let data_channel = Arc::downgrade(&self.data_channel);
+        let buffered_amount = self.buffered_amount.clone();
+        self.handlers.events.start(move |event| match event {
+            DataChannelEvent::Open => handlers.on_open.call(()),
+            DataChannelEvent::Message(message) => handlers.on_message.call(message),
+            DataChannelEvent::BufferedAmountLow => {
+                let Some(data_channel) = data_channel.upgrade() else {
+                    return;
+                };
+                let handlers = handlers.clone();
+                let buffered_amount = buffered_amount.clone();
+                spawn_local(async move {
+                    buffered_amount
+                        .sending
+                        .set(data_channel.buffered_amount().await);
+                    if buffered_amount.is_low() {
+                        handlers.on_buffered_amount_low.call(());
+                    }
+                });
+            }
+            DataChannelEvent::Closed => handlers.on_close.call(()),
+        });
+    }
+}
+
+/// Websocket connection with the signaling server,
+/// sending messages in the encoding negotiated during the handshake.
+///
+/// It is a cloneable pointer to the underlying websocket.
+#[derive(Clone)]
+pub(crate) struct SignalingSocket {
+    outgoing: UnboundedSender<Message>,
+    encoding: Rc<Cell<Encoding>>,
+    is_open: Rc<Cell<bool>>,
+    is_closed: Rc<Cell<bool>>,
+    handlers: Rc<SignalingSocketHandlers>,
+}
+
+enum SignalingSocketEvent {
+    Open,
+    Message(SignalMessage),
+    Closed,
+}
+
+struct SignalingSocketHandlers {
+    events: EventQueue<SignalingSocketEvent>,
+    on_open: Handler<()>,
+    on_message: Handler<SignalMessage>,
+    on_close: Handler<()>,
+}
+
+impl fmt::Debug for SignalingSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignalingSocket")
+            .field("encoding", &self.encoding.get())
+            .field("is_open", &self.is_open.get())
+            .field("is_closed", &self.is_closed.get())
+            .finish()
+    }
+}
+
+impl SignalingSocket {
+    pub(crate) fn new(signaling_server_url: &str) -> Result<Self, PlatformError> {
+        let request = signaling_server_url.to_string();
+        let (events_sender, events) = EventQueue::new();
+        // messages sent before the connection is established wait in the queue
+        let (outgoing, mut outgoing_receiver) = mpsc::unbounded_channel::<Message>();
+
+        spawn_local(async move {
+            let (websocket, _) = match tokio_tungstenite::connect_async(request).await {
+                Ok(connection) => connection,
+                Err(error) => {
+                    error!("failed to connect to signaling server: {}", error);
+                    let _ = events_sender.send(SignalingSocketEvent::Closed);
+                    return;
+                }
+            };
+            let (mut websocket_sender, mut websocket_receiver) = websocket.split();
+            let _ = events_sender.send(SignalingSocketEvent::Open);
+
+            spawn_local(async move {
+                while let Some(message) = outgoing_receiver.recv().await {
+                    let is_close = matches!(message, Message::Close(_));
+                    if let Err(error) = websocket_sender.send(message).await {
+                        error!("failed to send message to signaling server: {}", error);
+                    }
+                    if is_close {
+                        break;
+                    }
+                }
+            });
+
+            while let Some(message) = websocket_receiver.next().await {
+                match message
+                    .map_err(PlatformError::from)
+                    .and_then(decode_message)
+                {
+                    Ok(Some(message)) => {
+                        let _ = events_sender.send(SignalingSocketEvent::Message(message));
+                    }
+                    Ok(None) => {}
+                    Err(error) => {
+                        error!(
+                            "failed to deserialize onmessage callback content: {:?}",
+                            error
+                        );
+                    }
+                }
+            }
+            debug!("signaling server closed the connection");
+            let _ = events_sender.send(SignalingSocketEvent::Closed);
+        });
+
+        Ok(SignalingSocket {
+            outgoing,
+            encoding: Rc::new(Cell::new(Encoding::Json)),
+            is_open: Rc::new(Cell::new(false)),
+            is_closed: Rc::new(Cell::new(false)),
+            handlers: Rc::new(SignalingSocketHandlers {
+                events,
+                on_open: Handler::new(),
+                on_message: Handler::new(),
+                on_close: Handler::new(),
+            }),
+        })
+    }
+
+    /// Switch encoding of all messages sent from now on
+    pub(crate) fn set_encoding(&self, encoding: Encoding) {
+        self.encoding.set(encoding);
+    }
+
+    pub(crate) fn send(&self, message: &SignalMessage) -> Result<(), NetworkError> {
+        if self.is_closed.get() {
+            return Err(NetworkError::SignalingClosed);
+        }
+        let message = match self.encoding.get() {
+            Encoding::Json => Message::Text(
+                serde_json_wasm::to_string(message)
+                    .map_err(|error| NetworkError::Serialization(error.to_string()))?,
+            ),
+            Encoding::MessagePack => Message::Binary(
+                rmp_serde::to_vec_named(message)
+                    .map_err(|error| NetworkError::Serialization(error.to_string()))?,
+            ),
+        };
+        self.outgoing
+            .send(message)
+            .map_err(|_| NetworkError::SignalingClosed)
+    }
+
+    /// Called once the connection is open, or right away if it already is
+    pub(crate) fn on_open(&self, mut callback: impl FnMut() + 'static) {
+        if self.is_open.get() {
+            callback();
+            return;
+        }
+        self.handlers.on_open.set(move |()| callback());
+        self.start_handling_events();
+    }
+
+    /// Called once the connection is closed, either by the server or due to an error
+    pub(crate) fn on_close(&self, mut callback: impl FnMut() + 'static) {
+        self.handlers.on_close.set(move |()| callback());
+        self.start_handling_events();
+    }
+
+    /// Called with every message received from signaling server
+    pub(crate) fn on_message(&self, callback: impl FnMut(SignalMessage) + 'static) {
+        self.handlers.on_message.set(callback);
+        self.start_handling_events();
+    }
+
+    /// Closes the connection once all messages sent so far are delivered and drops all callbacks
+    pub(crate) fn close(&self) {
+        self.handlers.on_open.clear();
+        self.handlers.on_message.clear();
+        self.handlers.on_close.clear();
+        let _ = self.outgoing.send(Message::Close(None));
+        self.is_closed.set(true);
+    }
+
+    fn start_handling_events(&self) {
+        let handlers = self.handlers.clone();
+        let is_open = self.is_open.clone();
+        self.handlers.events.start(move |event| match event {
+            SignalingSocketEvent::Open => {
+                is_open.set(true);
+                handlers.on_open.call(());
+            }
+            SignalingSocketEvent::Message(message) => handlers.on_message.call(message),
+            SignalingSocketEvent::Closed => {
+                is_open.set(false);
+                handlers.on_close.call(());
+            }
+        });
+    }
+}
+
+/// Decode a websocket message,
+/// text frames are always JSON and binary frames are always MessagePack.
+/// Returns `None` for control frames.
+fn decode_message(message: Message) -> Result<Option<SignalMessage>, PlatformError> {
+    match message {
+        Message::Text(message) => serde_json_wasm::from_str(&message)
+            .map(Some)
+            .map_err(|error| PlatformError::from(error.to_string())),
+        Message::Binary(message) => rmp_serde::from_slice(&message)
+            .map(Some)
+            .map_err(|error| PlatformError::from(error.to_string())),
+        _ => Ok(None),
+    }
+}
+
+/// Install handlers forwarding data channel events to the current thread
+async fn forward_data_channel_events(
+    data_channel: &Arc<RTCDataChannel>,
+) -> UnboundedReceiver<DataChannelEvent> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    {
+        let sender = sender.clone();
+        let weak_data_channel = Arc::downgrade(data_channel);
+        data_channel.on_open(Box::new(move || {
+            let _ = sender.send(DataChannelEvent::Open);
+            let sender = sender.clone();
+            let data_channel = weak_data_channel.upgrade();
+            Box::pin(async move {
+                // handlers set before an accepted channel opens are never used by webrtc-rs
+                if let Some(data_channel) = data_channel {
+                    forward_buffered_amount_low(&data_channel, sender).await;
+                }
+            })
+        }));
+    }
+    {
+        let sender = sender.clone();
+        data_channel.on_close(Box::new(move || {
+            let _ = sender.send(DataChannelEvent::Closed);
+            Box::pin(async {})
+        }));
+    }
+    forward_buffered_amount_low(data_channel, sender.clone()).await;
+    data_channel.on_message(Box::new(move |message: DataChannelMessage| {
+        let _ = sender.send(DataChannelEvent::Message(message.data.to_vec()));
+        Box::pin(async {})
+    }));
+    data_channel.on_error(Box::new(move |error| {
+        error!("data channel error: {}", error);
+        Box::pin(async {})
+    }));
+    receiver
+}
+
+async fn forward_buffered_amount_low(
+    data_channel: &RTCDataChannel,
+    sender: UnboundedSender<DataChannelEvent>,
+) {
+    data_channel
+        .on_buffered_amount_low(Box::new(move || {
+            let _ = sender.send(DataChannelEvent::BufferedAmountLow);
+            Box::pin(async {})
+        }))
+        .await;
+}
+
+fn to_ice_candidate(candidate: RTCIceCandidateInit) -> IceCandidate {
+    IceCandidate {
+        candidate: candidate.candidate,
+        // an empty media stream identification means there is none
+        sdp_mid: candidate.sdp_mid.filter(|sdp_mid| !sdp_mid.is_empty()),
+        sdp_m_line_index: candidate.sdp_mline_index,
+    }
+}
+
+fn create_configuration(connection_type: &ConnectionType) -> RTCConfiguration {
+    let ice_servers = match connection_type {
+        ConnectionType::Local => vec![],
+        ConnectionType::Stun { urls } => vec![RTCIceServer {
+            urls: vec![urls.clone()],
+            ..Default::default()
+        }],
+        ConnectionType::StunAndTurn {
+            stun_urls,
+            turn_urls,
+            username,
+            credential,
+        } => vec![
+            RTCIceServer {
+                urls: vec![stun_urls.clone()],
+                ..Default::default()
+            },
+            RTCIceServer {
+                urls: vec![turn_urls.clone()],
+                username: username.clone(),
+                credential: credential.clone(),
+            },
+        ],
+    };
+
+    RTCConfiguration {
+        ice_servers,
+        ..Default::default()
+    }
+}
+
+fn set_peer_connection_logging(peer_connection: &RTCPeerConnection) {
+    peer_connection.on_ice_gathering_state_change(Box::new(|state| {
+        debug!("ice gathering state: {:?}", state);
+        Box::pin(async {})
+    }));
+}
@@ -0,0 +1,715 @@
+use crate::send_queue::{self, SendQueue};
+use crate::stats::{CandidatePairReport, StatsReport};
+use crate::{
+    CandidateType, ChannelConfig, ChannelStats, ConnectionStats, ConnectionType,
+    IceConnectionState, NetworkError, Reliability,
+};
+use js_sys::{Array, ArrayBuffer, Function, JsString, Map, Object, Promise, Reflect, Uint8Array};
+use log::{debug, error};
+use rusty_games_protocol::rtc::{IceCandidate, SdpAnswer, SdpOffer};
+use rusty_games_protocol::signal::{Encoding, SignalMessage};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::rc::Rc;
+use std::time::Duration;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    MessageEvent, RtcConfiguration, RtcDataChannel, RtcDataChannelEvent, RtcDataChannelInit,
+    RtcDataChannelState, RtcDataChannelType, RtcIceCandidate, RtcIceCandidateInit,
+    RtcIceConnectionState, RtcPeerConnection, RtcPeerConnectionIceEvent, RtcSdpType,
+    RtcSessionDescriptionInit, RtcSignalingState, WebSocket,
+};
+
+/// Error of the underlying platform, in the browser it's whatever JavaScript has thrown
+pub type PlatformError = JsValue;
+
+pub(crate) fn spawn_local(future: impl Future<Output = ()> + 'static) {
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+#[wasm_bindgen]
+extern "C" {
+    // available both in windows and workers
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &Function, timeout: i32) -> JsValue;
+
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// Time elapsed since the page or worker started, it never goes back
+pub(crate) fn now() -> Duration {
+    Duration::from_secs_f64(performance_now() / 1000.0)
+}
+
+pub(crate) async fn sleep(duration: Duration) {
+    let promise = Promise::new(&mut |resolve, _| {
+        set_timeout(&resolve, duration.as_millis().min(i32::MAX as u128) as i32);
+    });
+    let _ = JsFuture::from(promise).await;
+}
+
+/// Closures passed to JavaScript as callbacks of a single browser object.
+/// They are freed once the object is closed, but leaked if it's dropped without being closed,
+/// as the browser might still call them.
+#[derive(Debug, Default)]
+struct Closures(RefCell<Vec<Box<dyn Any>>>);
+
+impl Closures {
+    fn keep<T: ?Sized + 'static>(&self, closure: Closure<T>) {
+        self.0.borrow_mut().push(Box::new(closure));
+    }
+
+    /// Must only be called after the closures were unset on the browser object
+    fn free(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+impl Drop for Closures {
+    fn drop(&mut self) {
+        std::mem::forget(std::mem::take(self.0.get_mut()));
+    }
+}
+
+/// Cloneable pointer to a browser's `RTCPeerConnection`
+#[derive(Debug, Clone)]
+pub(crate) struct PeerConnection {
+    peer_connection: RtcPeerConnection,
+    closures: Rc<Closures>,
+}
+
+impl PeerConnection {
+    pub(crate) async fn new(connection_type: &ConnectionType) -> Result<Self, PlatformError> {
+        let peer_connection = create_peer_connection(connection_type)?;
+        let closures = Rc::new(Closures::default());
+        set_peer_connection_on_ice_gathering_state_change(&peer_connection, &closures);
+        Ok(PeerConnection {
+            peer_connection,
+            closures,
+        })
+    }
+
+    pub(crate) async fn create_data_channel(
+        &self,
+        label: &str,
+        config: &ChannelConfig,
+    ) -> Result<DataChannel, PlatformError> {
+        let data_channel_init = RtcDataChannelInit::new();
+        data_channel_init.set_ordered(config.ordered);
+        match config.reliability {
+            Reliability::Reliable => {}
+            Reliability::MaxRetransmits(max_retransmits) => {
+                data_channel_init.set_max_retransmits(max_retransmits)
+            }
+            Reliability::MaxPacketLifeTime(max_packet_life_time) => {
+                data_channel_init.set_max_packet_life_time(max_packet_life_time)
+            }
+        }
+        let data_channel = self
+            .peer_connection
+            .create_data_channel_with_data_channel_dict(label, &data_channel_init);
+        debug!(
+            "data_channel created with label: {:?}",
+            data_channel.label()
+        );
+        Ok(DataChannel::new(data_channel))
+    }
+
+    pub(crate) fn ice_connection_state(&self) -> IceConnectionState {
+        match self.peer_connection.ice_connection_state() {
+            RtcIceConnectionState::Checking => IceConnectionState::Checking,
+            RtcIceConnectionState::Connected => IceConnectionState::Connected,
+            RtcIceConnectionState::Completed => IceConnectionState::Completed,
+            RtcIceConnectionState::Disconnected => IceConnectionState::Disconnected,
+            RtcIceConnectionState::Failed => IceConnectionState::Failed,
+            RtcIceConnectionState::Closed => IceConnectionState::Closed,
+            _ => IceConnectionState::New,
+        }
+    }
+
+    /// Picks what the library reports out of `RTCStatsReport`, which differs slightly between browsers
+    pub(crate) async fn stats(&self) -> Result<ConnectionStats, PlatformError> {
+        let report: Map = JsFuture::from(self.peer_connection.get_stats())
+            .await?
+            .unchecked_into();
+        let mut stats_report = StatsReport::default();
+        report.for_each(&mut |stats, _| {
+            let string = |name: &str| stats_field(&stats, name).and_then(|value| value.as_string());
+            let number = |name: &str| stats_field(&stats, name).and_then(|value| value.as_f64());
+            let boolean = |name: &str| stats_field(&stats, name).and_then(|value| value.as_bool());
+            match string("type").as_deref() {
+                Some("transport") => {
+                    if let Some(id) = string("selectedCandidatePairId") {
+                        stats_report.selected_candidate_pair_id = Some(id);
+                    }
+                }
+                Some("candidate-pair") => stats_report.candidate_pairs.push(CandidatePairReport {
+                    id: string("id").unwrap_or_default(),
+                    local_candidate_id: string("localCandidateId").unwrap_or_default(),
+                    remote_candidate_id: string("remoteCandidateId").unwrap_or_default(),
+                    succeeded: string("state").as_deref() == Some("succeeded"),
+                    nominated: boolean("nominated").unwrap_or_default(),
+                    selected: boolean("selected").unwrap_or_default(),
+                    current_round_trip_time: number("currentRoundTripTime"),
+                }),
+                Some("local-candidate") | Some("remote-candidate") => {
+                    let candidate_type = string("candidateType")
+                        .as_deref()
+                        .and_then(CandidateType::from_name);
+                    if let (Some(id), Some(candidate_type)) = (string("id"), candidate_type) {
+                        stats_report.candidate_types.insert(id, candidate_type);
+                    }
+                }
+                Some("data-channel") => {
+                    let count = |name: &str| number(name).unwrap_or_default() as u64;
+                    stats_report.channels.push(ChannelStats {
+                        label: string("label").unwrap_or_default(),
+                        bytes_sent: count("bytesSent"),
+                        bytes_received: count("bytesReceived"),
+                        messages_sent: count("messagesSent"),
+                        messages_received: count("messagesReceived"),
+                    });
+                }
+                _ => {}
+            }
+        });
+        Ok(stats_report.into_connection_stats(self.ice_connection_state()))
+    }
+
+    /// Create an offer and set it as local description
+    pub(crate) async fn create_offer(&self) -> Result<SdpOffer, PlatformError> {
+        let offer = JsFuture::from(self.peer_connection.create_offer())
+            .await
+            .map_err(|error| {
+                JsValue::from_str(&format!(
+                    "failed to create an SDP offer: {}",
+                    error.as_string().unwrap_or_default()
+                ))
+            })?;
+        let offer = Reflect::get(&offer, &JsValue::from_str("sdp"))?
+            .as_string()
+            .expect("failed to create JS object for SDP offer");
+        let local_session_description = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+        local_session_description.set_sdp(&offer);
+        JsFuture::from(
+            self.peer_connection
+                .set_local_description(&local_session_description),
+        )
+        .await
+        .map_err(|error| {
+            JsValue::from_str(&format!(
+                "failed to set local description: {}",
+                error.as_string().unwrap_or_default()
+            ))
+        })?;
+
+        Ok(SdpOffer { sdp: offer })
+    }
+
+    /// Set the offer as remote description, then create an answer and set it as local description
+    pub(crate) async fn create_answer(&self, offer: SdpOffer) -> Result<SdpAnswer, PlatformError> {
+        let remote_session_description = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+        remote_session_description.set_sdp(&offer.sdp);
+        JsFuture::from(
+            self.peer_connection
+                .set_remote_description(&remote_session_description),
+        )
+        .await?;
+
+        let answer = JsFuture::from(self.peer_connection.create_answer()).await?;
+        let answer = Reflect::get(&answer, &JsValue::from_str("sdp"))?
+            .as_string()
+            .expect("failed to create JS object for SPD answer");
+
+        let local_session_description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+        local_session_description.set_sdp(&answer);
+        JsFuture::from(
+            self.peer_connection
+                .set_local_description(&local_session_description),
+        )
+        .await?;
+
+        Ok(SdpAnswer { sdp: answer })
+    }
+
+    pub(crate) async fn set_remote_answer(&self, answer: SdpAnswer) -> Result<(), PlatformError> {
+        let remote_session_description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+        remote_session_description.set_sdp(&answer.sdp);
+        JsFuture::from(
+            self.peer_connection
+                .set_remote_description(&remote_session_description),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Whether there is no offer/answer exchange in progress
+    pub(crate) fn is_stable(&self) -> bool {
+        self.peer_connection.signaling_state() == RtcSignalingState::Stable
+    }
+
+    /// Discards the local offer, that hasn't been answered yet
+    pub(crate) async fn rollback(&self) -> Result<(), PlatformError> {
+        let rollback = RtcSessionDescriptionInit::new(RtcSdpType::Rollback);
+        JsFuture::from(self.peer_connection.set_local_description(&rollback)).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn add_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<(), PlatformError> {
+        let rtc_candidate = RtcIceCandidateInit::new("");
+        rtc_candidate.set_candidate(&ice_candidate.candidate);
+        rtc_candidate.set_sdp_m_line_index(ice_candidate.sdp_m_line_index);
+        rtc_candidate.set_sdp_mid(ice_candidate.sdp_mid.as_deref());
+
+        let rtc_candidate = RtcIceCandidate::new(&rtc_candidate)?;
+        JsFuture::from(
+            self.peer_connection
+                .add_ice_candidate_with_opt_rtc_ice_candidate(Some(&rtc_candidate)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Called for every gathered local ICE candidate
+    pub(crate) fn on_ice_candidate(&self, mut callback: impl FnMut(IceCandidate) + 'static) {
+        let on_ice_candidate = Closure::wrap(Box::new(move |ev: RtcPeerConnectionIceEvent| {
+            match ev.candidate() {
+                Some(candidate) => callback(IceCandidate {
+                    candidate: candidate.candidate(),
+                    sdp_mid: candidate.sdp_mid(),
+                    sdp_m_line_index: candidate.sdp_m_line_index(),
+                }),
+                // gathering is complete
+                None => callback(IceCandidate::end_of_candidates()),
+            }
+        })
+            as Box<dyn FnMut(RtcPeerConnectionIceEvent)>);
+        self.peer_connection
+            .set_onicecandidate(Some(on_ice_candidate.as_ref().unchecked_ref()));
+        self.closures.keep(on_ice_candidate);
+    }
+
+    /// Called for every data channel created by the other peer
+    pub(crate) fn on_data_channel(&self, mut callback: impl FnMut(DataChannel) + 'static) {
+        let on_datachannel =
+            Closure::wrap(Box::new(move |data_channel_event: RtcDataChannelEvent| {
+                callback(DataChannel::new(data_channel_event.channel()));
+            }) as Box<dyn FnMut(RtcDataChannelEvent)>);
+        self.peer_connection
+            .set_ondatachannel(Some(on_datachannel.as_ref().unchecked_ref()));
+        self.closures.keep(on_datachannel);
+    }
+
+    /// Called when changes to the connection require a new offer/answer exchange
+    pub(crate) fn on_negotiation_needed(&self, mut callback: impl FnMut() + 'static) {
+        let on_negotiation_needed = Closure::wrap(Box::new(move || {
+            debug!("on negotiation needed event occurred");
+            callback();
+        }) as Box<dyn FnMut()>);
+        self.peer_connection
+            .set_onnegotiationneeded(Some(on_negotiation_needed.as_ref().unchecked_ref()));
+        self.closures.keep(on_negotiation_needed);
+    }
+
+    /// Called when ICE fails to find a working pair of candidates
+    pub(crate) fn on_ice_connection_failed(&self, mut callback: impl FnMut() + 'static) {
+        let peer_connection = self.peer_connection.clone();
+        let on_ice_connection_state_change = Closure::wrap(Box::new(move || {
+            let state = peer_connection.ice_connection_state();
+            debug!("connection state change: {:?}", state);
+            if state == RtcIceConnectionState::Failed {
+                callback();
+            }
+        }) as Box<dyn FnMut()>);
+        self.peer_connection.set_oniceconnectionstatechange(Some(
+            on_ice_connection_state_change.as_ref().unchecked_ref(),
+        ));
+        self.closures.keep(on_ice_connection_state_change);
+    }
+
+    /// Closes the connection along with all of its data channels and frees all callbacks
+    pub(crate) fn close(&self) {
+        self.peer_connection.set_onicecandidate(None);
+        self.peer_connection.set_ondatachannel(None);
+        self.peer_connection.set_oniceconnectionstatechange(None);
+        self.peer_connection.set_onicegatheringstatechange(None);
+        self.peer_connection.set_onnegotiationneeded(None);
+        self.peer_connection.close();
+        self.closures.free();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn rtc_peer_connection(&self) -> &RtcPeerConnection {
+        &self.peer_connection
+    }
+}
+
+/// Cloneable pointer to a browser's `RTCDataChannel`
+#[derive(Debug, Clone)]
+pub(crate) struct DataChannel {
+    data_channel: RtcDataChannel,
+    send_queue: Rc<SendQueue>,
+    closures: Rc<Closures>,
+}
+
+impl DataChannel {
+    fn new(data_channel: RtcDataChannel) -> Self {
+        data_channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+        let closures = Rc::new(Closures::default());
+        set_data_channel_on_error(&data_channel, &closures);
+        let data_channel = DataChannel {
+            data_channel,
+            send_queue: Rc::new(SendQueue::default()),
+            closures,
+        };
+        {
+            let data_channel_clone = data_channel.clone();
+            let onbufferedamountlow_callback = Closure::wrap(Box::new(move |_| {
+                send_queue::wake(&data_channel_clone);
+            })
+                as Box<dyn FnMut(JsValue)>);
+            data_channel.data_channel.set_onbufferedamountlow(Some(
+                onbufferedamountlow_callback.as_ref().unchecked_ref(),
+            ));
+            data_channel.closures.keep(onbufferedamountlow_callback);
+        }
+        data_channel
+    }
+
+    pub(crate) fn label(&self) -> String {
+        self.data_channel.label()
+    }
+
+    /// Whether both handles refer to the same data channel
+    pub(crate) fn is_same(&self, other: &DataChannel) -> bool {
+        self.data_channel == other.data_channel
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.data_channel.ready_state() == RtcDataChannelState::Open
+    }
+
+    /// Sends the bytes as a single binary message
+    pub(crate) fn send(&self, message: &[u8]) -> Result<(), NetworkError> {
+        if !self.is_open() {
+            return Err(NetworkError::DataChannelNotOpen);
+        }
+        Ok(self.data_channel.send_with_u8_array(message)?)
+    }
+
+    /// Bytes of sent messages that are still waiting to be transmitted
+    pub(crate) fn buffered_amount(&self) -> usize {
+        self.data_channel.buffered_amount() as usize
+    }
+
+    /// Buffered amount below which waiting senders are woken up
+    pub(crate) fn set_buffered_amount_low_threshold(&self, threshold: usize) {
+        self.data_channel
+            .set_buffered_amount_low_threshold(threshold.min(u32::MAX as usize) as u32);
+    }
+
+    pub(crate) fn send_queue(&self) -> &SendQueue {
+        &self.send_queue
+    }
+
+    pub(crate) fn on_open(&self, mut callback: impl FnMut() + 'static) {
+        let onopen_callback = Closure::wrap(Box::new(move |_| {
+            callback();
+        }) as Box<dyn FnMut(JsValue)>);
+        self.data_channel
+            .set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
+        self.closures.keep(onopen_callback);
+    }
+
+    /// Called once the data channel closes, e.g. when the other peer leaves
+    pub(crate) fn on_close(&self, mut callback: impl FnMut() + 'static) {
+        let onclose_callback = Closure::wrap(Box::new(move |_| {
+            callback();
+        }) as Box<dyn FnMut(JsValue)>);
+        self.data_channel
+            .set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
+        self.closures.keep(onclose_callback);
+    }
+
+    /// Called with the content of every message received, text messages as their UTF-8 bytes
+    pub(crate) fn on_message(&self, mut callback: impl FnMut(Vec<u8>) + 'static) {
+        let datachannel_on_message = Closure::wrap(Box::new(move |ev: MessageEvent| {
+            let data = ev.data();
+            if let Some(message) = data.as_string() {
+                callback(message.into_bytes());
+            } else if let Some(message) = data.dyn_ref::<ArrayBuffer>() {
+                callback(Uint8Array::new(message).to_vec());
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        self.data_channel
+            .set_onmessage(Some(datachannel_on_message.as_ref().unchecked_ref()));
+        self.closures.keep(datachannel_on_message);
+    }
+
+    /// Closes the data channel and frees all callbacks
+    pub(crate) fn close(&self) {
+        self.data_channel.set_onopen(None);
+        self.data_channel.set_onclose(None);
+        self.data_channel.set_onmessage(None);
+        self.data_channel.set_onerror(None);
+        self.data_channel.set_onbufferedamountlow(None);
+        self.data_channel.close();
+        self.send_queue.close();
+        self.closures.free();
+    }
+}
+
+/// Websocket connection with the signaling server,
+/// sending messages in the encoding negotiated during the handshake.
+///
+/// It is a cloneable pointer to the underlying websocket.
+#[derive(Debug, Clone)]
+pub(crate) struct SignalingSocket {
+    websocket: WebSocket,
+    encoding: Rc<Cell<Encoding>>,
+    closures: Rc<Closures>,
+}
+
+impl SignalingSocket {
+    pub(crate) fn new(signaling_server_url: &str) -> Result<Self, PlatformError> {
+        let websocket = WebSocket::new(signaling_server_url)?;
+        websocket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+        Ok(SignalingSocket {
+            websocket,
+            encoding: Rc::new(Cell::new(Encoding::Json)),
+            closures: Rc::new(Closures::default()),
+        })
+    }
+
+    /// Switch encoding of all messages sent from now on
+    pub(crate) fn set_encoding(&self, encoding: Encoding) {
+        self.encoding.set(encoding);
+    }
+
+    pub(crate) fn send(&self, message: &SignalMessage) -> Result<(), NetworkError> {
+        if self.websocket.ready_state() >= WebSocket::CLOSING {
+            return Err(NetworkError::SignalingClosed);
+        }
+        match self.encoding.get() {
+            Encoding::Json => {
+                let message = serde_json_wasm::to_string(message)
+                    .map_err(|error| NetworkError::Serialization(error.to_string()))?;
+                Ok(self.websocket.send_with_str(&message)?)
+            }
+            Encoding::MessagePack => {
+                let message = rmp_serde::to_vec_named(message)
+                    .map_err(|error| NetworkError::Serialization(error.to_string()))?;
+                Ok(self.websocket.send_with_u8_array(&message)?)
+            }
+        }
+    }
+
+    /// Called once the connection is open, or right away if it already is
+    pub(crate) fn on_open(&self, mut callback: impl FnMut() + 'static) {
+        if self.websocket.ready_state() == WebSocket::OPEN {
+            callback();
+            return;
+        }
+        let onopen_callback = Closure::wrap(Box::new(move |_| {
+            callback();
+        }) as Box<dyn FnMut(JsValue)>);
+        self.websocket
+            .set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
+        self.closures.keep(onopen_callback);
+    }
+
+    /// Called once the connection is closed, either by the server or due to an error
+    pub(crate) fn on_close(&self, mut callback: impl FnMut() + 'static) {
+        let onclose_callback = Closure::wrap(Box::new(move |_| {
+            callback();
+        }) as Box<dyn FnMut(JsValue)>);
+        self.websocket
+            .set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
+        self.closures.keep(onclose_callback);
+    }
+
+    /// Called with every message received from signaling server
+    pub(crate) fn on_message(&self, mut callback: impl FnMut(SignalMessage) + 'static) {
+        let onmessage_callback = Closure::wrap(Box::new(
+            move |ev: MessageEvent| match decode_message(ev.data()) {
+                Ok(message) => callback(message),
+                Err(error) => {
+                    error!(
+                        "failed to deserialize onmessage callback content: {:?}",
+                        error
+                    );
+                }
+            },
+        ) as Box<dyn FnMut(MessageEvent)>);
+        self.websocket
+            .set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
+        self.closures.keep(onmessage_callback);
+    }
+
+    /// Closes the connection once all messages sent so far are delivered and frees all callbacks
+    pub(crate) fn close(&self) {
+        self.websocket.set_onopen(None);
+        self.websocket.set_onclose(None);
+        self.websocket.set_onmessage(None);
+        if let Err(error) = self.websocket.close() {
+            error!("failed to close websocket: {:?}", error);
+        }
+        self.closures.free();
+    }
+}
+
+/// Field of a stats report entry, `None` if the browser doesn't report it
+fn stats_field(stats: &JsValue, name: &str) -> Option<JsValue> {
+    Reflect::get(stats, &JsValue::from_str(name))
+        .ok()
+        .filter(|value| !value.is_undefined())
+}
+
+/// Decode data of a websocket message event,
+/// text frames are always JSON and binary frames are always MessagePack
+fn decode_message(data: JsValue) -> Result<SignalMessage, JsValue> {
+    if let Some(message) = data.dyn_ref::<JsString>() {
+        serde_json_wasm::from_str(&String::from(message))
+            .map_err(|error| JsValue::from_str(&error.to_string()))
+    } else if let Some(message) = data.dyn_ref::<ArrayBuffer>() {
+        rmp_serde::from_slice(&Uint8Array::new(message).to_vec())
+            .map_err(|error| JsValue::from_str(&error.to_string()))
+    } else {
+        Err(JsValue::from_str("unexpected type of websocket message"))
+    }
+}
+
+fn create_peer_connection(connection_type: &ConnectionType) -> Result<RtcPeerConnection, JsValue> {
+    match connection_type {
+        ConnectionType::Local => RtcPeerConnection::new(),
+        ConnectionType::Stun { urls } => {
+            let ice_servers = Array::new();
+            {
+                let server_entry = Object::new();
+
+                Reflect::set(&server_entry, &"urls".into(), &urls.into())?;
+
+                ice_servers.push(&*server_entry);
+            }
+
+            let rtc_configuration = RtcConfiguration::new();
+            rtc_configuration.set_ice_servers(&ice_servers);
+
+            RtcPeerConnection::new_with_configuration(&rtc_configuration)
+        }
+        ConnectionType::StunAndTurn {
+            stun_urls,
+            turn_urls,
+            username,
+            credential,
+        } => {
+            let ice_servers = Array::new();
+            {
+                let stun_server_entry = Object::new();
+
+                Reflect::set(&stun_server_entry, &"urls".into(), &stun_urls.into())?;
+
+                ice_servers.push(&*stun_server_entry);
+            }
+            {
+                let turn_server_entry = Object::new();
+
+                Reflect::set(&turn_server_entry, &"urls".into(), &turn_urls.into())?;
+                Reflect::set(&turn_server_entry, &"username".into(), &username.into())?;
+                Reflect::set(&turn_server_entry, &"credential".into(), &credential.into())?;
+
+                ice_servers.push(&*turn_server_entry);
+            }
+
+            let rtc_configuration = RtcConfiguration::new();
+            rtc_configuration.set_ice_servers(&ice_servers);
+
+            RtcPeerConnection::new_with_configuration(&rtc_configuration)
+        }
+    }
+}
+
+fn set_peer_connection_on_ice_gathering_state_change(
+    peer_connection: &RtcPeerConnection,
+    closures: &Closures,
+) {
+    let peer_connection_clone = peer_connection.clone();
+    let on_ice_gathering_state_change = Closure::wrap(Box::new(move || {
+        debug!(
+            "ice gathering state: {:?}",
+            peer_connection_clone.ice_gathering_state()
+        );
+    }) as Box<dyn FnMut()>);
+    peer_connection.set_onicegatheringstatechange(Some(
+        on_ice_gathering_state_change.as_ref().unchecked_ref(),
+    ));
+    closures.keep(on_ice_gathering_state_change);
+}
+
+fn set_data_channel_on_error(data_channel: &RtcDataChannel, closures: &Closures) {
+    let onerror = Closure::wrap(Box::new(move |data_channel_error| {
+        error!("data channel error: {:?}", data_channel_error);
+    }) as Box<dyn FnMut(JsValue)>);
+    data_channel.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+    closures.keep(onerror);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    use web_sys::RtcIceGatheringState;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn test_create_stun_peer_connection_is_successful() {
+        let peer_connection = create_peer_connection(&ConnectionType::Local)
+            .expect("creating peer connection failed!");
+        assert_eq!(
+            peer_connection.ice_connection_state(),
+            RtcIceConnectionState::New
+        );
+        assert_eq!(
+            peer_connection.ice_gathering_state(),
+            RtcIceGatheringState::New
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_create_sdp_offer_is_successful() {
+        let peer_connection = PeerConnection::new(&ConnectionType::Local)
+            .await
+            .expect("failed to create peer connection");
+        let _offer = peer_connection.create_offer().await.unwrap();
+        assert!(peer_connection
+            .rtc_peer_connection()
+            .local_description()
+            .is_some());
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_create_sdp_answer_is_successful() {
+        let peer_connection = PeerConnection::new(&ConnectionType::Local)
+            .await
+            .expect("failed to create peer connection");
+        let offer = peer_connection.create_offer().await.unwrap();
+        let _answer = peer_connection.create_answer(offer).await.unwrap();
+        assert!(peer_connection
+            .rtc_peer_connection()
+            .local_description()
+            .is_some());
+        assert!(peer_connection
+            .rtc_peer_connection()
+            .remote_description()
+            .is_some());
+    }
+}
@@ -0,0 +1,744 @@
+/*!
+Authoritative state replication for the [one-to-many](crate::one_to_many) topology.
+
+The host registers state of each entity with a [ReplicationServer], which sends a snapshot
+of all of them to every client-peer at a fixed tick rate. Each snapshot only contains entities
+that changed since the latest snapshot the client-peer acknowledged, and entities removed since then,
+so entities that stand still cost nothing. Until the client-peer acknowledges any snapshot,
+or if the one it acknowledged is too old, the whole state is sent instead.
+
+A [ReplicationClient] puts snapshots back together and buffers them, so that the state can be
+rendered a short [interpolation delay](ReplicationClient::set_interpolation_delay) in the past,
+[interpolated](Interpolate) between the two snapshots around that time.
+This hides both the tick rate and snapshots that got lost or delayed on the way.
+
+Snapshots are sent over a separate unreliable data channel that the host opens with each client-peer,
+so they never reach `on_message_callback` of either side and don't hold back other messages.
+State is serialized with JSON, so it may contain anything serde can describe.
+
+Snapshots that don't fit into a single message (64 KiB, or less if the client-peer accepts less)
+are sent in fragments, and losing any fragment loses the whole snapshot. Deltas are usually small,
+but a client-peer has no state until a whole-state snapshot arrives, so over a link that loses
+messages often a large state may never reach it.
+
+# Example
+
+```no_run
+use rusty_games_library::one_to_many::{MiniClient, MiniServer};
+use rusty_games_library::replication::{Interpolate, ReplicationClient, ReplicationServer};
+use rusty_games_library::{ConnectionType, SessionId};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Position {
+    x: f64,
+    y: f64,
+}
+
+impl Interpolate for Position {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        Position {
+            x: self.x + (other.x - self.x) * t,
+            y: self.y + (other.y - self.y) * t,
+        }
+    }
+}
+
+let mut server = MiniServer::new(
+    "ws://0.0.0.0:9001/signal",
+    SessionId::new("dummy-session-id".to_string()),
+    ConnectionType::Local,
+)
+.unwrap();
+server.start(|_| {}, |_, _| {}).unwrap();
+let replication = ReplicationServer::new(&server, 20);
+replication.set_entity(1, Position { x: 0.0, y: 0.0 });
+replication.start();
+
+let mut client = MiniClient::new(
+    "ws://0.0.0.0:9001/signal",
+    SessionId::new("dummy-session-id".to_string()),
+    ConnectionType::Local,
+)
+.unwrap();
+client.start(|_| {}, |_, _| {}).unwrap();
+let replication = ReplicationClient::<Position>::new(&client);
+// every frame
+let positions = replication.interpolated_state();
+```
+*/
+
+use crate::one_to_many::{MiniClient, MiniServer};
+use crate::platform;
+use crate::timer::IntervalTimer;
+use crate::{ChannelConfig, NetworkError, UserId};
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Name of the data channel snapshots and their acknowledgements are sent over
+const REPLICATION_CHANNEL: &str = "rusty-games-replication";
+
+/// Both sides only keep snapshots of this many latest ticks,
+/// older acknowledgements can't be used as a baseline for deltas
+const HISTORY_TICKS: u32 = 32;
+
+/// How far in the past [ReplicationClient] renders the state, unless changed with
+/// [ReplicationClient::set_interpolation_delay]. Covers two snapshots at 20 ticks per second,
+/// so that a single lost one doesn't leave nothing to interpolate towards.
+pub const DEFAULT_INTERPOLATION_DELAY: Duration = Duration::from_millis(100);
+
+/// Identifies a replicated entity, chosen by the host
+pub type EntityId = u64;
+
+/// Blends two states of an entity, which lets [ReplicationClient] move entities smoothly
+/// between snapshots
+pub trait Interpolate {
+    /// State `t` of the way from `self` to `other`, where `t` is between `0.0` and `1.0`
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ReplicationMessage<S> {
+    Snapshot(SnapshotDelta<S>),
+    /// Tick of the newest snapshot the client-peer put back together
+    Ack(u32),
+}
+
+/// Snapshot as sent to a single client-peer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SnapshotDelta<S> {
+    tick: u32,
+    /// Clock of the host when the snapshot was taken, in microseconds
+    time: u64,
+    /// Tick of the snapshot the delta is relative to, `None` if it contains the whole state
+    baseline: Option<u32>,
+    changed: Vec<(EntityId, S)>,
+    removed: Vec<EntityId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot<S> {
+    /// Clock of the host when the snapshot was taken
+    time: Duration,
+    entities: BTreeMap<EntityId, S>,
+}
+
+/// Snapshots of the latest ticks
+#[derive(Debug)]
+struct History<S> {
+    snapshots: BTreeMap<u32, Snapshot<S>>,
+}
+
+impl<S> Default for History<S> {
+    fn default() -> Self {
+        History {
+            snapshots: BTreeMap::new(),
+        }
+    }
+}
+
+impl<S> History<S> {
+    /// Forgets snapshots that fell out of the window, including this one if it's too old
+    fn insert(&mut self, tick: u32, snapshot: Snapshot<S>) {
+        self.snapshots.insert(tick, snapshot);
+        if let Some((&newest, _)) = self.snapshots.last_key_value() {
+            self.snapshots
+                .retain(|&tick, _| newest - tick < HISTORY_TICKS);
+        }
+    }
+
+    fn get(&self, tick: u32) -> Option<&Snapshot<S>> {
+        self.snapshots.get(&tick)
+    }
+
+    fn newest(&self) -> Option<(u32, &Snapshot<S>)> {
+        self.snapshots
+            .last_key_value()
+            .map(|(&tick, snapshot)| (tick, snapshot))
+    }
+}
+
+/// Delta of the snapshot against the baseline, or the whole snapshot without one
+fn encode_delta<S: Clone + PartialEq>(
+    tick: u32,
+    snapshot: &Snapshot<S>,
+    baseline: Option<(u32, &Snapshot<S>)>,
+) -> SnapshotDelta<S> {
+    let empty = BTreeMap::new();
+    let baseline_entities = baseline.map_or(&empty, |(_, baseline)| &baseline.entities);
+    SnapshotDelta {
+        tick,
+        time: snapshot.time.as_micros() as u64,
+        baseline: baseline.map(|(tick, _)| tick),
+        changed: snapshot
+            .entities
+            .iter()
+            .filter(|(id, state)| baseline_entities.get(id) != Some(state))
+            .map(|(&id, state)| (id, state.clone()))
+            .collect(),
+        removed: baseline_entities
+            .keys()
+            .filter(|id| !snapshot.entities.contains_key(id))
+            .copied()
+            .collect(),
+    }
+}
+
+/// Whole snapshot the delta describes, `None` if its baseline is no longer known
+fn decode_delta<S: Clone>(delta: SnapshotDelta<S>, history: &History<S>) -> Option<Snapshot<S>> {
+    let mut entities = match delta.baseline {
+        None => BTreeMap::new(),
+        Some(baseline) => history.get(baseline)?.entities.clone(),
+    };
+    for id in delta.removed {
+        entities.remove(&id);
+    }
+    entities.extend(delta.changed);
+    Some(Snapshot {
+        time: Duration::from_micros(delta.time),
+        entities,
+    })
+}
+
+/// State between the two snapshots, entities that only exist in one of them
+/// appear and disappear at the time of the later one
+fn interpolate<S: Interpolate + Clone>(
+    from: &Snapshot<S>,
+    to: &Snapshot<S>,
+    time: Duration,
+) -> BTreeMap<EntityId, S> {
+    let span = to.time.saturating_sub(from.time);
+    let t = if span.is_zero() {
+        1.0
+    } else {
+        (time.saturating_sub(from.time).as_secs_f64() / span.as_secs_f64()).clamp(0.0, 1.0)
+    };
+    if t < 1.0 {
+        from.entities
+            .iter()
+            .map(|(&id, state)| match to.entities.get(&id) {
+                Some(next) => (id, state.interpolate(next, t)),
+                None => (id, state.clone()),
+            })
+            .collect()
+    } else {
+        to.entities.clone()
+    }
+}
+
+#[derive(Debug)]
+struct ServerState<S> {
+    tick: u32,
+    entities: BTreeMap<EntityId, S>,
+    history: History<S>,
+    /// Newest tick each client-peer acknowledged
+    acks: HashMap<UserId, u32>,
+    /// Client-peers the replication channel is being opened with
+    opening: HashSet<UserId>,
+}
+
+impl<S: Clone + PartialEq> ServerState<S> {
+    fn take_snapshot(&mut self, time: Duration) -> u32 {
+        self.tick += 1;
+        let snapshot = Snapshot {
+            time,
+            entities: self.entities.clone(),
+        };
+        self.history.insert(self.tick, snapshot);
+        self.tick
+    }
+
+    fn delta(&self, tick: u32, user_id: UserId) -> Option<SnapshotDelta<S>> {
+        let snapshot = self.history.get(tick)?;
+        let baseline = self
+            .acks
+            .get(&user_id)
+            .and_then(|&acked| self.history.get(acked).map(|baseline| (acked, baseline)));
+        Some(encode_delta(tick, snapshot, baseline))
+    }
+
+    /// Acknowledgements may arrive out of order, only the newest one counts
+    fn ack(&mut self, user_id: UserId, tick: u32) {
+        let acked = self.acks.entry(user_id).or_insert(tick);
+        *acked = tick.max(*acked);
+    }
+}
+
+/// Sends state of entities registered by the host to all client-peers of a [MiniServer].
+/// See the [module documentation](self) for how it works.
+///
+/// This class is a cloneable pointer to the underlying resource and can be cloned freely.
+#[derive(Debug)]
+pub struct ReplicationServer<S> {
+    server: MiniServer,
+    state: Rc<RefCell<ServerState<S>>>,
+    timer: Rc<IntervalTimer>,
+}
+
+impl<S> Clone for ReplicationServer<S> {
+    fn clone(&self) -> Self {
+        ReplicationServer {
+            server: self.server.clone(),
+            state: self.state.clone(),
+            timer: self.timer.clone(),
+        }
+    }
+}
+
+impl<S> ReplicationServer<S>
+where
+    S: Serialize + DeserializeOwned + Clone + PartialEq + 'static,
+{
+    /// Replicates state to client-peers of the server `tick_rate` times per second, once started.
+    /// Only one instance should be created for a server.
+    pub fn new(server: &MiniServer, tick_rate: u32) -> Self {
+        let state = Rc::new(RefCell::new(ServerState {
+            tick: 0,
+            entities: BTreeMap::new(),
+            history: History::default(),
+            acks: HashMap::new(),
+            opening: HashSet::new(),
+        }));
+        let interval = Duration::from_secs(1) / tick_rate.max(1);
+        let timer = Rc::new(IntervalTimer::new(Some(interval)));
+        server.inner.stop_on_close(timer.clone());
+        let replication = ReplicationServer {
+            server: server.clone(),
+            state,
+            timer,
+        };
+        replication.handle_acks();
+        replication
+    }
+
+    /// Handlers are forgotten once no client-peer has the channel open,
+    /// so it is registered again before opening the channel with new ones
+    fn handle_acks(&self) {
+        let state = self.state.clone();
+        let network_manager = self.server.inner.clone();
+        self.server
+            .inner
+            .handle_channel(
+                REPLICATION_CHANNEL,
+                move |user_id, message| match serde_json::from_str::<ReplicationMessage<S>>(
+                    &message,
+                ) {
+                    Ok(ReplicationMessage::Ack(tick)) => state.borrow_mut().ack(user_id, tick),
+                    Ok(ReplicationMessage::Snapshot(_)) => {
+                        debug!("ignoring snapshot sent by client {:?}", user_id)
+                    }
+                    Err(error) => {
+                        network_manager.report_error(NetworkError::Serialization(error.to_string()))
+                    }
+                },
+            );
+    }
+
+    /// Adds the entity, or updates its state, which client-peers see with the next tick
+    pub fn set_entity(&self, id: EntityId, state: S) {
+        self.state.borrow_mut().entities.insert(id, state);
+    }
+
+    /// Client-peers see the entity disappear with the next tick
+    pub fn remove_entity(&self, id: EntityId) -> Option<S> {
+        self.state.borrow_mut().entities.remove(&id)
+    }
+
+    /// Current state of the entity, as set by the host
+    pub fn entity(&self, id: EntityId) -> Option<S> {
+        self.state.borrow().entities.get(&id).cloned()
+    }
+
+    /// Starts sending snapshots at the tick rate, the first one a tick from now
+    pub fn start(&self) {
+        let replication = self.clone();
+        self.timer.start(move || replication.tick());
+    }
+
+    /// Stops sending snapshots until started again, [ReplicationServer::tick] still sends them
+    pub fn stop(&self) {
+        self.timer.stop();
+    }
+
+    /// Number of the latest snapshot, `0` before the first one
+    pub fn current_tick(&self) -> u32 {
+        self.state.borrow().tick
+    }
+
+    /// Takes a snapshot and sends it to every client-peer right away,
+    /// for games that drive ticks from their own loop instead of [ReplicationServer::start].
+    /// Also opens the replication channel with client-peers that just connected,
+    /// they receive snapshots from the first tick after it opens.
+    pub fn tick(&self) {
+        let network_manager = &self.server.inner;
+        let user_ids = network_manager.user_ids();
+        let mut to_open = Vec::new();
+        let mut messages = Vec::new();
+        {
+            let mut state = self.state.borrow_mut();
+            let tick = state.take_snapshot(platform::now());
+            state.acks.retain(|user_id, _| user_ids.contains(user_id));
+            for &user_id in &user_ids {
+                if state.opening.contains(&user_id) || !network_manager.is_open(user_id) {
+                    continue;
+                }
+                if !network_manager.has_channel(user_id, REPLICATION_CHANNEL) {
+                    state.opening.insert(user_id);
+                    to_open.push(user_id);
+                    continue;
+                }
+                if let Some(delta) = state.delta(tick, user_id) {
+                    messages.push((user_id, ReplicationMessage::Snapshot(delta)));
+                }
+            }
+        }
+
+        if !to_open.is_empty() {
+            self.handle_acks();
+        }
+        for user_id in to_open {
+            let network_manager = network_manager.clone();
+            let state = self.state.clone();
+            platform::spawn_local(async move {
+                let result = network_manager
+                    .open_channel(user_id, REPLICATION_CHANNEL, ChannelConfig::unreliable())
+                    .await;
+                if let Err(error) = result {
+                    debug!(
+                        "failed to open replication channel with {:?}: {:?}",
+                        user_id, error
+                    );
+                }
+                state.borrow_mut().opening.remove(&user_id);
+            });
+        }
+        for (user_id, message) in messages {
+            let message = match serde_json::to_string(&message) {
+                Ok(message) => message,
+                Err(error) => {
+                    network_manager.report_error(NetworkError::Serialization(error.to_string()));
+                    continue;
+                }
+            };
+            // lost snapshots are made up for by the next ones
+            if let Err(error) =
+                network_manager.send_message_on_channel(user_id, REPLICATION_CHANNEL, &message)
+            {
+                debug!("failed to send snapshot to {:?}: {:?}", user_id, error);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ClientState<S> {
+    history: History<S>,
+    interpolation_delay: Duration,
+    /// Clock of the host minus the local one in microseconds, estimated from arrival times
+    /// of snapshots, for until the clock of the host is synchronized with pings
+    arrival_offset: Option<i64>,
+}
+
+impl<S: Clone> ClientState<S> {
+    /// Returns the tick to acknowledge, unless the snapshot can't be put back together
+    fn receive(&mut self, delta: SnapshotDelta<S>, now: Duration) -> Option<u32> {
+        let tick = delta.tick;
+        let snapshot = decode_delta(delta, &self.history)?;
+        // the snapshot that took the shortest way gives the closest estimate
+        let offset = snapshot.time.as_micros() as i64 - now.as_micros() as i64;
+        self.arrival_offset = Some(self.arrival_offset.map_or(offset, |old| old.max(offset)));
+        self.history.insert(tick, snapshot);
+        Some(tick)
+    }
+
+    fn host_time(&self, estimated: Option<Duration>, now: Duration) -> Duration {
+        estimated.unwrap_or_else(|| {
+            let micros = now.as_micros() as i64 + self.arrival_offset.unwrap_or(0);
+            Duration::from_micros(micros.max(0) as u64)
+        })
+    }
+}
+
+impl<S: Interpolate + Clone> ClientState<S> {
+    /// State at the time of the host clock, the oldest or newest known one
+    /// if the time is outside of the buffered snapshots
+    fn state_at(&self, time: Duration) -> BTreeMap<EntityId, S> {
+        let mut from = None;
+        let mut to = None;
+        for snapshot in self.history.snapshots.values() {
+            if snapshot.time <= time {
+                from = Some(snapshot);
+            } else {
+                to = Some(snapshot);
+                break;
+            }
+        }
+        match (from, to) {
+            (Some(from), Some(to)) => interpolate(from, to, time),
+            (Some(snapshot), None) | (None, Some(snapshot)) => snapshot.entities.clone(),
+            (None, None) => BTreeMap::new(),
+        }
+    }
+}
+
+/// Receives state replicated by the [ReplicationServer] of the host of a [MiniClient].
+/// See the [module documentation](self) for how it works.
+///
+/// This class is a cloneable pointer to the underlying resource and can be cloned freely.
+#[derive(Debug)]
+pub struct ReplicationClient<S> {
+    client: MiniClient,
+    state: Rc<RefCell<ClientState<S>>>,
+}
+
+impl<S> Clone for ReplicationClient<S> {
+    fn clone(&self) -> Self {
+        ReplicationClient {
+            client: self.client.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S> ReplicationClient<S>
+where
+    S: Serialize + DeserializeOwned + Clone + 'static,
+{
+    /// Starts buffering snapshots the host sends to the client,
+    /// only one instance should be created for a client
+    pub fn new(client: &MiniClient) -> Self {
+        let state = Rc::new(RefCell::new(ClientState {
+            history: History::default(),
+            interpolation_delay: DEFAULT_INTERPOLATION_DELAY,
+            arrival_offset: None,
+        }));
+        {
+            let state = state.clone();
+            let network_manager = client.inner.clone();
+            client
+                .inner
+                .handle_channel(REPLICATION_CHANNEL, move |user_id, message| {
+                    let delta = match serde_json::from_str::<ReplicationMessage<S>>(&message) {
+                        Ok(ReplicationMessage::Snapshot(delta)) => delta,
+                        Ok(ReplicationMessage::Ack(_)) => {
+                            debug!("ignoring acknowledgement sent by host {:?}", user_id);
+                            return;
+                        }
+                        Err(error) => {
+                            network_manager
+                                .report_error(NetworkError::Serialization(error.to_string()));
+                            return;
+                        }
+                    };
+                    let Some(tick) = state.borrow_mut().receive(delta, platform::now()) else {
+                        debug!("dropping snapshot with a forgotten baseline");
+                        return;
+                    };
+                    let ack = serde_json::to_string(&ReplicationMessage::<S>::Ack(tick))
+                        .expect("acknowledgement is always serializable");
+                    if let Err(error) =
+                        network_manager.send_message_on_channel(user_id, REPLICATION_CHANNEL, &ack)
+                    {
+                        debug!("failed to acknowledge snapshot {}: {:?}", tick, error);
+                    }
+                });
+        }
+        ReplicationClient {
+            client: client.clone(),
+            state,
+        }
+    }
+
+    /// How far behind the host the state is rendered, [DEFAULT_INTERPOLATION_DELAY] unless changed.
+    /// Should be at least two tick intervals, longer delays survive more lost snapshots.
+    pub fn set_interpolation_delay(&self, delay: Duration) {
+        self.state.borrow_mut().interpolation_delay = delay;
+    }
+
+    /// Tick of the newest snapshot received from the host, `None` before the first one
+    pub fn latest_tick(&self) -> Option<u32> {
+        self.state.borrow().history.newest().map(|(tick, _)| tick)
+    }
+
+    /// State of the newest snapshot received from the host, without interpolation,
+    /// empty before the first one
+    pub fn latest_state(&self) -> BTreeMap<EntityId, S> {
+        self.state
+            .borrow()
+            .history
+            .newest()
+            .map(|(_, snapshot)| snapshot.entities.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl<S> ReplicationClient<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Interpolate + 'static,
+{
+    /// State of all entities as it was on the host the interpolation delay ago,
+    /// meant to be rendered every frame. Uses the clock of the host synchronized with pings,
+    /// or an estimate from arrival times of snapshots until the first pong arrives.
+    pub fn interpolated_state(&self) -> BTreeMap<EntityId, S> {
+        let now = platform::now();
+        let estimated = self.client.estimated_remote_time();
+        let state = self.state.borrow();
+        let render_time = state
+            .host_time(estimated, now)
+            .saturating_sub(state.interpolation_delay);
+        state.state_at(render_time)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Position(f64);
+
+    impl Interpolate for Position {
+        fn interpolate(&self, other: &Self, t: f64) -> Self {
+            Position(self.0 + (other.0 - self.0) * t)
+        }
+    }
+
+    fn ms(milliseconds: u64) -> Duration {
+        Duration::from_millis(milliseconds)
+    }
+
+    fn server_state() -> ServerState<Position> {
+        ServerState {
+            tick: 0,
+            entities: BTreeMap::new(),
+            history: History::default(),
+            acks: HashMap::new(),
+            opening: HashSet::new(),
+        }
+    }
+
+    fn client_state() -> ClientState<Position> {
+        ClientState {
+            history: History::default(),
+            interpolation_delay: DEFAULT_INTERPOLATION_DELAY,
+            arrival_offset: None,
+        }
+    }
+
+    #[test]
+    fn deltas_only_contain_changes_since_acknowledged_snapshot() {
+        let user_id = UserId::new(1);
+        let mut server = server_state();
+        server.entities.insert(1, Position(0.0));
+        server.entities.insert(2, Position(5.0));
+        let first = server.take_snapshot(ms(0));
+        let delta = server.delta(first, user_id).unwrap();
+        assert_eq!(delta.baseline, None);
+        assert_eq!(delta.changed.len(), 2);
+
+        server.ack(user_id, first);
+        server.entities.insert(1, Position(1.0));
+        server.entities.remove(&2);
+        server.entities.insert(3, Position(7.0));
+        let second = server.take_snapshot(ms(50));
+        let delta = server.delta(second, user_id).unwrap();
+        assert_eq!(delta.baseline, Some(first));
+        assert_eq!(delta.changed, vec![(1, Position(1.0)), (3, Position(7.0))]);
+        assert_eq!(delta.removed, vec![2]);
+    }
+
+    #[test]
+    fn client_puts_snapshots_back_together() {
+        let user_id = UserId::new(1);
+        let mut server = server_state();
+        let mut client = client_state();
+        server.entities.insert(1, Position(0.0));
+        server.entities.insert(2, Position(5.0));
+        let first = server.take_snapshot(ms(0));
+        let acked = client.receive(server.delta(first, user_id).unwrap(), ms(0));
+        server.ack(user_id, acked.unwrap());
+
+        server.entities.insert(1, Position(1.0));
+        server.entities.remove(&2);
+        let second = server.take_snapshot(ms(50));
+        assert_eq!(
+            client.receive(server.delta(second, user_id).unwrap(), ms(50)),
+            Some(second)
+        );
+        assert_eq!(client.history.newest().unwrap().1.entities, server.entities);
+    }
+
+    #[test]
+    fn stale_acknowledgements_fall_back_to_whole_state() {
+        let user_id = UserId::new(1);
+        let mut server = server_state();
+        server.entities.insert(1, Position(0.0));
+        let first = server.take_snapshot(ms(0));
+        server.ack(user_id, first);
+        for tick in 1..=HISTORY_TICKS as u64 {
+            server.take_snapshot(ms(tick * 50));
+        }
+        // acknowledgements can arrive out of order
+        server.ack(user_id, first - 1);
+        let delta = server.delta(server.tick, user_id).unwrap();
+        assert_eq!(delta.baseline, None);
+        assert_eq!(delta.changed, vec![(1, Position(0.0))]);
+    }
+
+    #[test]
+    fn deltas_with_unknown_baseline_are_dropped() {
+        let mut client = client_state();
+        let delta = SnapshotDelta {
+            tick: 2,
+            time: 0,
+            baseline: Some(1),
+            changed: vec![],
+            removed: vec![],
+        };
+        assert_eq!(client.receive(delta, ms(0)), None);
+        assert_eq!(client.history.newest(), None);
+    }
+
+    #[test]
+    fn state_is_interpolated_between_surrounding_snapshots() {
+        let mut client = client_state();
+        for (tick, time, x) in [(1, 0, 0.0), (2, 100, 10.0), (3, 200, 30.0)] {
+            client.receive(
+                SnapshotDelta {
+                    tick,
+                    time: time * 1000,
+                    baseline: None,
+                    changed: vec![(1, Position(x))],
+                    removed: vec![],
+                },
+                ms(time),
+            );
+        }
+        assert_eq!(client.state_at(ms(150))[&1], Position(20.0));
+        assert_eq!(client.state_at(ms(25))[&1], Position(2.5));
+        // holds the oldest and newest state outside of the buffer
+        assert_eq!(client.state_at(ms(0))[&1], Position(0.0));
+        assert_eq!(client.state_at(ms(500))[&1], Position(30.0));
+    }
+
+    #[test]
+    fn messages_survive_json() {
+        let message = ReplicationMessage::Snapshot(SnapshotDelta {
+            tick: 7,
+            time: 1_000_000,
+            baseline: Some(5),
+            changed: vec![(1, Position(0.5))],
+            removed: vec![2],
+        });
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ReplicationMessage<Position>>(&json).unwrap(),
+            message
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use crate::rollback::sync::{InputMessage, InputSync};
+use crate::rollback::{Frame, RollbackConfig, RollbackGame};
+
+/// Messages on the way to a single peer, with the step each of them arrives at
+type InFlight<I> = Vec<(u64, InputMessage<I>)>;
+
+/// Runs a [RollbackGame] on two peers in the same process, linked by a simulated connection
+/// with the given latency, jitter and packet loss, all measured in frames.
+/// Randomness comes from a seed, so every run with the same inputs and seed plays out the same way,
+/// which makes it suitable for tests of games that must stay deterministic under rollback.
+///
+/// Every step both peers advance by a frame, as if they rendered one,
+/// after receiving the messages that arrived in the meantime.
+/// Once the peers confirmed a frame, both games must have simulated it
+/// with the same inputs as a game that never had to guess any.
+pub struct TestHarness<G: RollbackGame> {
+    games: [G; 2],
+    syncs: [InputSync<G>; 2],
+    in_flight: [InFlight<G::Input>; 2],
+    step: u64,
+    latency: u32,
+    jitter: u32,
+    packet_loss: f64,
+    random: SplitMix64,
+}
+
+impl<G: RollbackGame> TestHarness<G> {
+    /// Game of the first player goes first, the link is perfect until changed with
+    /// [TestHarness::set_link]
+    pub fn new(games: [G; 2], config: RollbackConfig, seed: u64) -> Self {
+        TestHarness {
+            games,
+            syncs: [InputSync::new(0, config), InputSync::new(1, config)],
+            in_flight: [Vec::new(), Vec::new()],
+            step: 0,
+            latency: 0,
+            jitter: 0,
+            packet_loss: 0.0,
+            random: SplitMix64(seed),
+        }
+    }
+
+    /// Messages take `latency` frames to arrive, plus up to `jitter` more, which reorders them.
+    /// Fraction of them given by `packet_loss`, from `0.0` to `1.0`, never arrives.
+    pub fn set_link(&mut self, latency: u32, jitter: u32, packet_loss: f64) {
+        self.latency = latency;
+        self.jitter = jitter;
+        self.packet_loss = packet_loss;
+    }
+
+    /// Advances both peers by a frame, asking `input` for the input of each player
+    /// for the frame it takes effect on. Returns whether each peer advanced,
+    /// a peer that had to stall asks for the same input again next step.
+    pub fn step(&mut self, mut input: impl FnMut(usize, Frame) -> G::Input) -> [bool; 2] {
+        let step = self.step;
+        self.step += 1;
+        let mut advanced = [false; 2];
+        for (player, advanced) in advanced.iter_mut().enumerate() {
+            let (arrived, in_flight) = std::mem::take(&mut self.in_flight[player])
+                .into_iter()
+                .partition(|(arrival, _)| *arrival <= step);
+            self.in_flight[player] = in_flight;
+            for (_, message) in arrived {
+                self.syncs[player].receive(message);
+            }
+
+            let sync = &mut self.syncs[player];
+            let frame = sync.current_frame() + sync.config().input_delay;
+            *advanced = sync.advance_frame(&mut self.games[player], input(player, frame));
+
+            let message = sync.message();
+            if self.random.next_f64() < self.packet_loss {
+                continue;
+            }
+            let delay = self.latency as u64 + self.random.next_u64() % (self.jitter as u64 + 1);
+            // sent at the end of the step, received at the start of the next one at the earliest
+            self.in_flight[1 - player].push((step + 1 + delay, message));
+        }
+        advanced
+    }
+
+    /// Game simulated by the peer of the player
+    pub fn game(&self, player: usize) -> &G {
+        &self.games[player]
+    }
+
+    /// Next frame the peer of the player simulates
+    pub fn current_frame(&self, player: usize) -> Frame {
+        self.syncs[player].current_frame()
+    }
+
+    /// Newest frame up to which the peer of the player received all inputs of the other one,
+    /// `None` before the first one arrives
+    pub fn confirmed_frame(&self, player: usize) -> Option<Frame> {
+        self.syncs[player].confirmed_frame()
+    }
+
+    /// Number of frames the peer of the player simulated again after guessing wrong
+    pub fn resimulated_frames(&self, player: usize) -> u64 {
+        self.syncs[player].resimulated_frames()
+    }
+}
+
+/// Small deterministic generator, good enough for simulating a link
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0.0..1.0`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rollback::test::InputLog;
+
+    /// Changes every few frames, so that some guesses are right and some are wrong
+    fn input(player: usize, frame: Frame) -> u8 {
+        ((frame / 5 + frame / 7 + player as u32) % 4) as u8
+    }
+
+    /// Inputs every frame is simulated with when nothing has to be guessed
+    fn expected_inputs(config: RollbackConfig, frames: Frame) -> Vec<[u8; 2]> {
+        (0..frames)
+            .map(|frame| {
+                if frame < config.input_delay {
+                    [0, 0]
+                } else {
+                    [input(0, frame), input(1, frame)]
+                }
+            })
+            .collect()
+    }
+
+    fn assert_confirmed_frames_match(harness: &TestHarness<InputLog>, config: RollbackConfig) {
+        for player in 0..2 {
+            let confirmed = harness.confirmed_frame(player).unwrap() + 1;
+            let simulated = confirmed.min(harness.current_frame(player));
+            let expected = expected_inputs(config, simulated);
+            assert_eq!(
+                harness.game(player).inputs[..simulated as usize],
+                expected[..]
+            );
+        }
+    }
+
+    #[test]
+    fn peers_agree_on_confirmed_frames_over_lossy_link() {
+        let config = RollbackConfig {
+            input_delay: 2,
+            max_rollback_frames: 8,
+        };
+        let mut harness = TestHarness::new([InputLog::default(), InputLog::default()], config, 7);
+        harness.set_link(3, 2, 0.2);
+        for _ in 0..300 {
+            harness.step(input);
+        }
+        assert!(harness.resimulated_frames(0) > 0);
+        assert!(harness.resimulated_frames(1) > 0);
+        assert!(harness.current_frame(0) > 200);
+        assert_confirmed_frames_match(&harness, config);
+
+        // messages still on the way arrive after those sent over the perfect link
+        harness.set_link(0, 0, 0.0);
+        for _ in 0..20 {
+            harness.step(input);
+        }
+        assert_confirmed_frames_match(&harness, config);
+    }
+
+    #[test]
+    fn peers_stall_without_messages_of_the_other_one() {
+        let config = RollbackConfig {
+            input_delay: 0,
+            max_rollback_frames: 4,
+        };
+        let mut harness = TestHarness::new([InputLog::default(), InputLog::default()], config, 1);
+        harness.set_link(0, 0, 1.0);
+        for _ in 0..10 {
+            harness.step(input);
+        }
+        assert_eq!(harness.current_frame(0), 4);
+        assert_eq!(harness.current_frame(1), 4);
+
+        harness.set_link(0, 0, 0.0);
+        for _ in 0..10 {
+            harness.step(input);
+        }
+        assert!(harness.current_frame(0) > 10);
+        assert_confirmed_frames_match(&harness, config);
+    }
+
+    #[test]
+    fn same_seed_plays_out_the_same_way() {
+        let config = RollbackConfig::default();
+        let run = || {
+            let mut harness =
+                TestHarness::new([InputLog::default(), InputLog::default()], config, 42);
+            harness.set_link(2, 4, 0.3);
+            let advanced: Vec<[bool; 2]> = (0..100).map(|_| harness.step(input)).collect();
+            (
+                advanced,
+                harness.resimulated_frames(0),
+                harness.resimulated_frames(1),
+            )
+        };
+        assert_eq!(run(), run());
+    }
+}
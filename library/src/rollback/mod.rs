@@ -0,0 +1,298 @@
+/*!
+Rollback netcode for the [one-to-one](crate::one_to_one) topology, in the style of GGPO.
+
+Both peers simulate the game frame by frame with inputs of both players, without waiting
+for inputs of the other peer. Inputs that didn't arrive yet are guessed to be the same as the last
+one that did. Once an input arrives that was guessed wrong, the game is rolled back to the state
+before that frame and simulated again up to the current frame, within a single call to
+[RollbackSession::advance_frame]. Games only need to implement [RollbackGame], which saves,
+loads and advances their state, and has to be deterministic: the same state advanced with
+the same inputs must always end up the same on both peers.
+
+Local inputs take effect a configurable [input delay](RollbackConfig::input_delay) later,
+which gives them time to arrive before the other peer simulates their frame,
+trading a little responsiveness for fewer rollbacks. Peers never guess more than
+[max_rollback_frames](RollbackConfig::max_rollback_frames) inputs ahead, once they would have to,
+they stall until inputs of the other peer catch up.
+
+Inputs are sent over a separate unreliable data channel, opened by the first player.
+Every message repeats all inputs the other peer didn't acknowledge yet, so a lost message
+is made up for by the next one without waiting for retransmission.
+Inputs are serialized with JSON.
+
+[TestHarness] runs a game on two peers over a simulated connection that loses and reorders messages,
+so that games can test they stay deterministic under rollback.
+
+# Example
+
+```no_run
+use rusty_games_library::one_to_one::NetworkManager;
+use rusty_games_library::rollback::{RollbackConfig, RollbackGame, RollbackSession};
+use rusty_games_library::{ConnectionType, SessionId};
+
+#[derive(Default)]
+struct Fight {
+    positions: [i32; 2],
+}
+
+impl RollbackGame for Fight {
+    /// -1, 0 or 1 for moving left, standing or moving right
+    type Input = i8;
+    type State = [i32; 2];
+
+    fn save_state(&mut self) -> Self::State {
+        self.positions
+    }
+
+    fn load_state(&mut self, state: &Self::State) {
+        self.positions = *state;
+    }
+
+    fn advance_frame(&mut self, inputs: &[Self::Input; 2]) {
+        for (position, input) in self.positions.iter_mut().zip(inputs) {
+            *position += *input as i32;
+        }
+    }
+}
+
+let mut network_manager = NetworkManager::new(
+    "ws://0.0.0.0:9001/signal",
+    SessionId::new("dummy-session-id".to_string()),
+    ConnectionType::Local,
+)
+.unwrap();
+network_manager.start(|| {}, |_| {}).unwrap();
+// the peer that created the session plays first
+let session = RollbackSession::<Fight>::new(&network_manager, 0, RollbackConfig::default());
+
+let mut fight = Fight::default();
+// every frame, at a fixed rate
+session.advance_frame(&mut fight, 1);
+```
+*/
+
+mod harness;
+mod sync;
+
+pub use harness::TestHarness;
+
+use crate::one_to_one::NetworkManager;
+use crate::rollback::sync::{InputMessage, InputSync};
+use crate::{platform, ChannelConfig, NetworkError};
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+/// Name of the data channel inputs are sent over
+const ROLLBACK_CHANNEL: &str = "rusty-games-rollback";
+
+/// Number of a simulated frame, the first one is `0`
+pub type Frame = u32;
+
+/// Game simulated with rollback, see the [module documentation](self)
+pub trait RollbackGame {
+    /// Input of a single player for a single frame, e.g. buttons held down.
+    /// The default is used for frames before the first input takes effect.
+    type Input: Clone + PartialEq + Default + Serialize + DeserializeOwned + 'static;
+    /// Everything [RollbackGame::advance_frame] depends on
+    type State;
+
+    /// Called before every frame is simulated, the state may be loaded back later
+    fn save_state(&mut self) -> Self::State;
+
+    /// Rolls the game back to a state saved earlier
+    fn load_state(&mut self, state: &Self::State);
+
+    /// Simulates a single frame with inputs of both players, indexed by player
+    fn advance_frame(&mut self, inputs: &[Self::Input; 2]);
+}
+
+/// Parameters of a [RollbackSession], see the [module documentation](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollbackConfig {
+    /// Number of frames between a local input being passed in and it taking effect
+    pub input_delay: u32,
+    /// Number of frames peers simulate ahead of the newest input of the other peer, at most
+    pub max_rollback_frames: u32,
+}
+
+impl Default for RollbackConfig {
+    fn default() -> Self {
+        RollbackConfig {
+            input_delay: 2,
+            max_rollback_frames: 8,
+        }
+    }
+}
+
+/// Synchronizes inputs of a [RollbackGame] with the other peer of a [NetworkManager].
+/// See the [module documentation](self) for how it works.
+///
+/// This class is a cloneable pointer to the underlying resource and can be cloned freely.
+pub struct RollbackSession<G: RollbackGame> {
+    network_manager: NetworkManager,
+    local_player: usize,
+    sync: Rc<RefCell<InputSync<G>>>,
+    /// Whether the first player is opening the channel for inputs
+    opening: Rc<Cell<bool>>,
+}
+
+impl<G: RollbackGame> Clone for RollbackSession<G> {
+    fn clone(&self) -> Self {
+        RollbackSession {
+            network_manager: self.network_manager.clone(),
+            local_player: self.local_player,
+            sync: self.sync.clone(),
+            opening: self.opening.clone(),
+        }
+    }
+}
+
+impl<G: RollbackGame> fmt::Debug for RollbackSession<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RollbackSession")
+            .field("local_player", &self.local_player)
+            .field("current_frame", &self.sync.borrow().current_frame())
+            .field("confirmed_frame", &self.sync.borrow().confirmed_frame())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<G: RollbackGame + 'static> RollbackSession<G> {
+    /// Starts receiving inputs of the other peer, who plays as the other player,
+    /// both peers must agree on who plays first and use the same config.
+    /// Only one instance should be created for a network manager.
+    ///
+    /// # Panics
+    ///
+    /// If `local_player` is neither `0` nor `1`.
+    pub fn new(
+        network_manager: &NetworkManager,
+        local_player: usize,
+        config: RollbackConfig,
+    ) -> Self {
+        assert!(local_player < 2, "one-to-one games have two players");
+        let sync = Rc::new(RefCell::new(InputSync::new(local_player, config)));
+        {
+            let sync = sync.clone();
+            let network_manager_clone = network_manager.clone();
+            network_manager.handle_channel(ROLLBACK_CHANNEL, move |message| {
+                match serde_json::from_str::<InputMessage<G::Input>>(&message) {
+                    Ok(message) => sync.borrow_mut().receive(message),
+                    Err(error) => network_manager_clone
+                        .report_error(NetworkError::Serialization(error.to_string())),
+                }
+            });
+        }
+        RollbackSession {
+            network_manager: network_manager.clone(),
+            local_player,
+            sync,
+            opening: Rc::default(),
+        }
+    }
+
+    /// Simulates the next frame, first rolling back and simulating again frames whose
+    /// inputs were guessed wrong, and sends the local input, which takes effect
+    /// [input_delay](RollbackConfig::input_delay) frames later.
+    /// Meant to be called at a fixed rate, the same on both peers.
+    ///
+    /// Returns `false` without simulating the next frame if the other peer is
+    /// [max_rollback_frames](RollbackConfig::max_rollback_frames) behind,
+    /// the input is dropped then and the same one should be passed again.
+    /// Hooks of the game must not call methods of the session.
+    pub fn advance_frame(&self, game: &mut G, input: G::Input) -> bool {
+        self.open_channel();
+        let (advanced, message) = {
+            let mut sync = self.sync.borrow_mut();
+            let advanced = sync.advance_frame(game, input);
+            (advanced, sync.message())
+        };
+        let message = match serde_json::to_string(&message) {
+            Ok(message) => message,
+            Err(error) => {
+                self.network_manager
+                    .report_error(NetworkError::Serialization(error.to_string()));
+                return advanced;
+            }
+        };
+        // lost inputs are sent again with the next frame
+        if let Err(error) = self
+            .network_manager
+            .send_message_on_channel(ROLLBACK_CHANNEL, &message)
+        {
+            debug!("failed to send inputs: {:?}", error);
+        }
+        advanced
+    }
+
+    /// Next frame to be simulated
+    pub fn current_frame(&self) -> Frame {
+        self.sync.borrow().current_frame()
+    }
+
+    /// Newest frame up to which all inputs of the other peer arrived, which is never rolled back,
+    /// `None` before the first one arrives
+    pub fn confirmed_frame(&self) -> Option<Frame> {
+        self.sync.borrow().confirmed_frame()
+    }
+
+    /// Number of frames simulated again after an input of the other peer was guessed wrong
+    pub fn resimulated_frames(&self) -> u64 {
+        self.sync.borrow().resimulated_frames()
+    }
+
+    /// First player opens the channel for inputs once the connection opens
+    fn open_channel(&self) {
+        if self.local_player != 0
+            || self.opening.get()
+            || !self.network_manager.is_open()
+            || self.network_manager.has_channel(ROLLBACK_CHANNEL)
+        {
+            return;
+        }
+        self.opening.set(true);
+        let network_manager = self.network_manager.clone();
+        let opening = self.opening.clone();
+        platform::spawn_local(async move {
+            if let Err(error) = network_manager
+                .open_channel(ROLLBACK_CHANNEL, ChannelConfig::unreliable())
+                .await
+            {
+                debug!("failed to open rollback channel: {:?}", error);
+            }
+            opening.set(false);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Game whose state is the inputs of every frame simulated so far
+    #[derive(Debug, Default)]
+    pub(crate) struct InputLog {
+        pub(crate) inputs: Vec<[u8; 2]>,
+    }
+
+    impl RollbackGame for InputLog {
+        type Input = u8;
+        type State = usize;
+
+        fn save_state(&mut self) -> usize {
+            self.inputs.len()
+        }
+
+        fn load_state(&mut self, state: &usize) {
+            self.inputs.truncate(*state);
+        }
+
+        fn advance_frame(&mut self, inputs: &[u8; 2]) {
+            self.inputs.push(*inputs);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::rollback::{Frame, RollbackConfig, RollbackGame};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Inputs a peer sends every frame, along with acknowledgement of the inputs it received
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct InputMessage<I> {
+    /// Frame of the first input, the rest are for consecutive frames
+    pub(crate) start_frame: Frame,
+    /// All inputs the receiver didn't acknowledge yet, so that a lost message is made up for by the next one
+    pub(crate) inputs: Vec<I>,
+    /// Newest frame up to which all inputs of the receiver arrived
+    pub(crate) ack: Option<Frame>,
+}
+
+/// Inputs of both players, simulation of the game with them,
+/// and rolling it back once a guessed remote input turns out wrong
+pub(crate) struct InputSync<G: RollbackGame> {
+    config: RollbackConfig,
+    local_player: usize,
+    /// Next frame to be simulated
+    current_frame: Frame,
+    local_inputs: BTreeMap<Frame, G::Input>,
+    remote_inputs: BTreeMap<Frame, G::Input>,
+    /// Newest frame up to which all remote inputs arrived
+    confirmed_frame: Option<Frame>,
+    /// Newest frame up to which the peer received all local inputs
+    remote_ack: Option<Frame>,
+    /// Remote inputs guessed for frames that were simulated before their input arrived
+    predictions: BTreeMap<Frame, G::Input>,
+    /// Oldest frame simulated with a guess that turned out wrong
+    first_mispredicted: Option<Frame>,
+    /// States at the start of frames that may still be rolled back to
+    states: BTreeMap<Frame, G::State>,
+    resimulated_frames: u64,
+}
+
+impl<G: RollbackGame> InputSync<G> {
+    pub(crate) fn new(local_player: usize, config: RollbackConfig) -> Self {
+        // nothing was pressed during the frames before the first local input takes effect
+        let local_inputs = (0..config.input_delay)
+            .map(|frame| (frame, G::Input::default()))
+            .collect();
+        InputSync {
+            config,
+            local_player,
+            current_frame: 0,
+            local_inputs,
+            remote_inputs: BTreeMap::new(),
+            confirmed_frame: None,
+            remote_ack: None,
+            predictions: BTreeMap::new(),
+            first_mispredicted: None,
+            states: BTreeMap::new(),
+            resimulated_frames: 0,
+        }
+    }
+
+    pub(crate) fn config(&self) -> RollbackConfig {
+        self.config
+    }
+
+    pub(crate) fn current_frame(&self) -> Frame {
+        self.current_frame
+    }
+
+    pub(crate) fn confirmed_frame(&self) -> Option<Frame> {
+        self.confirmed_frame
+    }
+
+    pub(crate) fn resimulated_frames(&self) -> u64 {
+        self.resimulated_frames
+    }
+
+    /// Takes in inputs of the peer, marking the game for rollback if any of them was guessed wrong
+    pub(crate) fn receive(&mut self, message: InputMessage<G::Input>) {
+        if let Some(ack) = message.ack {
+            self.remote_ack = Some(
+                self.remote_ack
+                    .map_or(ack, |remote_ack| remote_ack.max(ack)),
+            );
+        }
+        let first_unconfirmed = self.confirmed_frame.map_or(0, |frame| frame + 1);
+        for (frame, input) in (message.start_frame..).zip(message.inputs) {
+            // messages repeat inputs until they're acknowledged
+            if frame < first_unconfirmed || self.remote_inputs.contains_key(&frame) {
+                continue;
+            }
+            if let Some(prediction) = self.predictions.remove(&frame) {
+                if prediction != input {
+                    self.first_mispredicted = Some(
+                        self.first_mispredicted
+                            .map_or(frame, |mispredicted| mispredicted.min(frame)),
+                    );
+                }
+            }
+            self.remote_inputs.insert(frame, input);
+        }
+        let mut next = first_unconfirmed;
+        while self.remote_inputs.contains_key(&next) {
+            self.confirmed_frame = Some(next);
+            next += 1;
+        }
+    }
+
+    /// Rolls back and resimulates the frames with wrong guesses, then simulates the current frame,
+    /// with the local input taking effect `input_delay` frames later.
+    /// Returns `false` without doing the latter if it would have to guess more than
+    /// `max_rollback_frames` remote inputs, the local input is dropped then.
+    pub(crate) fn advance_frame(&mut self, game: &mut G, input: G::Input) -> bool {
+        self.roll_back(game);
+        let first_unconfirmed = self.confirmed_frame.map_or(0, |frame| frame + 1);
+        if (self.current_frame + 1).saturating_sub(first_unconfirmed)
+            > self.config.max_rollback_frames
+        {
+            return false;
+        }
+        self.local_inputs
+            .insert(self.current_frame + self.config.input_delay, input);
+        self.simulate(game);
+        self.forget_confirmed();
+        true
+    }
+
+    /// Local inputs the peer didn't acknowledge yet
+    pub(crate) fn message(&self) -> InputMessage<G::Input> {
+        let unacknowledged = self.remote_ack.map_or(0, |frame| frame + 1);
+        let mut inputs = self.local_inputs.range(unacknowledged..).peekable();
+        InputMessage {
+            start_frame: inputs.peek().map_or(unacknowledged, |(&frame, _)| frame),
+            inputs: inputs.map(|(_, input)| input.clone()).collect(),
+            ack: self.confirmed_frame,
+        }
+    }
+
+    fn roll_back(&mut self, game: &mut G) {
+        let Some(frame) = self.first_mispredicted.take() else {
+            return;
+        };
+        let Some(state) = self.states.get(&frame) else {
+            return;
+        };
+        game.load_state(state);
+        let end = self.current_frame;
+        self.current_frame = frame;
+        while self.current_frame < end {
+            self.simulate(game);
+            self.resimulated_frames += 1;
+        }
+    }
+
+    fn simulate(&mut self, game: &mut G) {
+        let frame = self.current_frame;
+        self.states.insert(frame, game.save_state());
+        let local = self.local_inputs[&frame].clone();
+        let remote = match self.remote_inputs.get(&frame) {
+            Some(input) => input.clone(),
+            None => {
+                // the peer most likely still holds what it held last
+                let prediction = self
+                    .remote_inputs
+                    .range(..frame)
+                    .next_back()
+                    .map(|(_, input)| input.clone())
+                    .unwrap_or_default();
+                self.predictions.insert(frame, prediction.clone());
+                prediction
+            }
+        };
+        let inputs = if self.local_player == 0 {
+            [local, remote]
+        } else {
+            [remote, local]
+        };
+        game.advance_frame(&inputs);
+        self.current_frame += 1;
+    }
+
+    /// Frames up to the confirmed one are never rolled back to,
+    /// the confirmed remote input is still needed to guess the following ones,
+    /// and remote inputs of frames that weren't simulated yet are needed to simulate them
+    fn forget_confirmed(&mut self) {
+        let Some(confirmed_frame) = self.confirmed_frame else {
+            return;
+        };
+        let first_unconfirmed = confirmed_frame + 1;
+        self.states = self.states.split_off(&first_unconfirmed);
+        self.predictions = self.predictions.split_off(&first_unconfirmed);
+        self.remote_inputs = self
+            .remote_inputs
+            .split_off(&confirmed_frame.min(self.current_frame));
+        // local inputs are also needed until they're simulated and acknowledged
+        let unacknowledged = self.remote_ack.map_or(0, |frame| frame + 1);
+        let needed = first_unconfirmed
+            .min(unacknowledged)
+            .min(self.current_frame);
+        self.local_inputs = self.local_inputs.split_off(&needed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rollback::test::InputLog;
+
+    fn config(input_delay: u32, max_rollback_frames: u32) -> RollbackConfig {
+        RollbackConfig {
+            input_delay,
+            max_rollback_frames,
+        }
+    }
+
+    fn remote_inputs(start_frame: Frame, inputs: Vec<u8>) -> InputMessage<u8> {
+        InputMessage {
+            start_frame,
+            inputs,
+            ack: None,
+        }
+    }
+
+    #[test]
+    fn local_input_takes_effect_after_input_delay() {
+        let mut game = InputLog::default();
+        let mut sync = InputSync::<InputLog>::new(0, config(2, 8));
+        sync.receive(remote_inputs(0, vec![0, 0, 0]));
+        for _ in 0..3 {
+            assert!(sync.advance_frame(&mut game, 1));
+        }
+        assert_eq!(game.inputs, vec![[0, 0], [0, 0], [1, 0]]);
+        assert_eq!(sync.resimulated_frames(), 0);
+    }
+
+    #[test]
+    fn wrong_guess_is_rolled_back_and_resimulated() {
+        let mut game = InputLog::default();
+        let mut sync = InputSync::<InputLog>::new(1, config(0, 8));
+        sync.receive(remote_inputs(0, vec![3]));
+        for _ in 0..4 {
+            sync.advance_frame(&mut game, 1);
+        }
+        // remote input is guessed to stay the same
+        assert_eq!(game.inputs, vec![[3, 1]; 4]);
+        assert_eq!(sync.confirmed_frame(), Some(0));
+
+        sync.receive(remote_inputs(1, vec![3, 5]));
+        sync.advance_frame(&mut game, 1);
+        assert_eq!(game.inputs, vec![[3, 1], [3, 1], [5, 1], [5, 1], [5, 1]]);
+        assert_eq!(sync.resimulated_frames(), 2);
+        assert_eq!(sync.confirmed_frame(), Some(2));
+    }
+
+    #[test]
+    fn inputs_confirmed_ahead_of_simulation_are_kept() {
+        let mut game = InputLog::default();
+        let mut sync = InputSync::<InputLog>::new(0, config(0, 8));
+        sync.receive(remote_inputs(0, vec![1, 2, 3, 4]));
+        for _ in 0..4 {
+            sync.advance_frame(&mut game, 0);
+        }
+        assert_eq!(game.inputs, vec![[0, 1], [0, 2], [0, 3], [0, 4]]);
+        assert_eq!(sync.confirmed_frame(), Some(3));
+    }
+
+    #[test]
+    fn right_guess_is_not_rolled_back() {
+        let mut game = InputLog::default();
+        let mut sync = InputSync::<InputLog>::new(0, config(0, 8));
+        for _ in 0..3 {
+            sync.advance_frame(&mut game, 1);
+        }
+        sync.receive(remote_inputs(0, vec![0, 0, 0]));
+        sync.advance_frame(&mut game, 1);
+        assert_eq!(sync.resimulated_frames(), 0);
+        assert_eq!(sync.confirmed_frame(), Some(2));
+    }
+
+    #[test]
+    fn frames_stall_once_too_many_remote_inputs_are_guessed() {
+        let mut game = InputLog::default();
+        let mut sync = InputSync::<InputLog>::new(0, config(0, 2));
+        assert!(sync.advance_frame(&mut game, 1));
+        assert!(sync.advance_frame(&mut game, 1));
+        assert!(!sync.advance_frame(&mut game, 1));
+        assert_eq!(sync.current_frame(), 2);
+
+        sync.receive(remote_inputs(0, vec![0]));
+        assert!(sync.advance_frame(&mut game, 1));
+    }
+
+    #[test]
+    fn unacknowledged_inputs_are_sent_again() {
+        let mut game = InputLog::default();
+        let mut sync = InputSync::<InputLog>::new(0, config(1, 8));
+        sync.advance_frame(&mut game, 1);
+        sync.advance_frame(&mut game, 2);
+        let message = sync.message();
+        assert_eq!(message.start_frame, 0);
+        assert_eq!(message.inputs, vec![0, 1, 2]);
+        assert_eq!(message.ack, None);
+
+        sync.receive(InputMessage {
+            start_frame: 0,
+            inputs: vec![0],
+            ack: Some(1),
+        });
+        let message = sync.message();
+        assert_eq!(message.start_frame, 2);
+        assert_eq!(message.inputs, vec![2]);
+        assert_eq!(message.ack, Some(0));
+    }
+}
@@ -0,0 +1,258 @@
+use futures::channel::oneshot;
+use log::debug;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Non-critical messages queued for a single peer above this count push out the oldest ones
+const MAX_QUEUED_MESSAGES: usize = 4096;
+
+/// How urgently a message has to reach the peer, messages of higher priority
+/// overtake queued messages of lower priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// Events the game can't go on without, e.g. a player dying.
+    /// Sent as soon as the data channel allows, regardless of the bandwidth budget.
+    Critical,
+    /// Regular gameplay messages
+    #[default]
+    Normal,
+    /// Large transfers that can wait, e.g. map chunks
+    Bulk,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::Critical, Priority::Normal, Priority::Bulk];
+}
+
+/// What should happen next with messages queued for a peer
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Next<T> {
+    Send(T),
+    /// Bandwidth budget is used up for at least this long
+    Wait(Duration),
+    Idle,
+}
+
+#[derive(Debug)]
+struct Queued<T> {
+    /// Key of the state the message carries, a newer message with the same key supersedes it
+    key: Option<String>,
+    length: usize,
+    message: T,
+}
+
+/// Token bucket refilled at a constant rate, holding at most a second worth of bytes
+#[derive(Debug, Clone, Copy)]
+struct Budget {
+    bytes_per_second: usize,
+    /// Goes below zero when critical messages are sent over the budget
+    available: f64,
+    refilled_at: Duration,
+}
+
+impl Budget {
+    fn refill(&mut self, now: Duration) {
+        let elapsed = now.saturating_sub(self.refilled_at).as_secs_f64();
+        self.available = (self.available + elapsed * self.bytes_per_second as f64)
+            .min(self.bytes_per_second as f64);
+        self.refilled_at = now;
+    }
+
+    /// Time until a message of the given length fits, messages larger than the whole budget
+    /// only have to wait for it to fill up
+    fn wait_time(&self, length: usize) -> Option<Duration> {
+        let needed = length.min(self.bytes_per_second) as f64;
+        if self.available >= needed {
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            (needed - self.available) / self.bytes_per_second as f64,
+        ))
+    }
+}
+
+/// Messages waiting to be sent to a single peer, ordered by [Priority] and limited by
+/// an optional bandwidth budget
+#[derive(Debug)]
+pub(crate) struct Scheduler<T> {
+    queues: [VecDeque<Queued<T>>; 3],
+    budget: Option<Budget>,
+    /// Whether a task sending the queued messages is running
+    pub(crate) sending: bool,
+    /// Wakes the sending task while it waits for the budget,
+    /// so that newly queued messages that don't have to wait are sent right away
+    pub(crate) waiting: Option<oneshot::Sender<()>>,
+}
+
+impl<T> Default for Scheduler<T> {
+    fn default() -> Self {
+        Scheduler {
+            queues: Default::default(),
+            budget: None,
+            sending: false,
+            waiting: None,
+        }
+    }
+}
+
+impl<T> Scheduler<T> {
+    /// Queues a message of the given encoded length, dropping a queued message with
+    /// the same `key` of the same priority, as it's superseded by the new one
+    pub(crate) fn push(
+        &mut self,
+        priority: Priority,
+        key: Option<String>,
+        length: usize,
+        message: T,
+    ) {
+        let queue = &mut self.queues[priority as usize];
+        if let Some(key) = &key {
+            let before = queue.len();
+            queue.retain(|queued| queued.key.as_ref() != Some(key));
+            if queue.len() < before {
+                debug!("dropped superseded {:?} message {}", priority, key);
+            }
+        }
+        if priority != Priority::Critical && queue.len() >= MAX_QUEUED_MESSAGES {
+            queue.pop_front();
+            debug!(
+                "too many {:?} messages queued, dropped the oldest one",
+                priority
+            );
+        }
+        queue.push_back(Queued {
+            key,
+            length,
+            message,
+        });
+    }
+
+    /// Takes the first message of the highest priority, if the budget allows sending it
+    pub(crate) fn next(&mut self, now: Duration) -> Next<T> {
+        let Some(priority) = Priority::ALL
+            .into_iter()
+            .find(|priority| !self.queues[*priority as usize].is_empty())
+        else {
+            return Next::Idle;
+        };
+        let queue = &mut self.queues[priority as usize];
+        let length = queue[0].length;
+        if let Some(budget) = &mut self.budget {
+            budget.refill(now);
+            if priority != Priority::Critical {
+                if let Some(wait_time) = budget.wait_time(length) {
+                    return Next::Wait(wait_time);
+                }
+            }
+            budget.available -= length as f64;
+        }
+        Next::Send(queue.pop_front().unwrap().message)
+    }
+
+    /// Limits the rate at which non-critical messages are sent, `None` removes the limit
+    pub(crate) fn set_budget(&mut self, bytes_per_second: Option<usize>, now: Duration) {
+        self.budget = bytes_per_second
+            .filter(|bytes_per_second| *bytes_per_second > 0)
+            .map(|bytes_per_second| Budget {
+                bytes_per_second,
+                available: bytes_per_second as f64,
+                refilled_at: now,
+            });
+    }
+
+    /// Drops all queued messages
+    pub(crate) fn clear(&mut self) {
+        for queue in &mut self.queues {
+            queue.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn drain(scheduler: &mut Scheduler<&'static str>, now: Duration) -> Vec<&'static str> {
+        let mut sent = vec![];
+        while let Next::Send(message) = scheduler.next(now) {
+            sent.push(message);
+        }
+        sent
+    }
+
+    #[test]
+    fn higher_priority_overtakes_queued_messages() {
+        let mut scheduler = Scheduler::default();
+        scheduler.push(Priority::Bulk, None, 1, "chunk 1");
+        scheduler.push(Priority::Normal, None, 1, "move");
+        scheduler.push(Priority::Bulk, None, 1, "chunk 2");
+        scheduler.push(Priority::Critical, None, 1, "player died");
+        assert_eq!(
+            drain(&mut scheduler, Duration::ZERO),
+            vec!["player died", "move", "chunk 1", "chunk 2"]
+        );
+        assert_eq!(scheduler.next(Duration::ZERO), Next::Idle);
+    }
+
+    #[test]
+    fn newer_state_supersedes_queued_one() {
+        let mut scheduler = Scheduler::default();
+        scheduler.push(Priority::Bulk, Some("position".to_string()), 1, "x=1");
+        scheduler.push(Priority::Bulk, Some("health".to_string()), 1, "hp=9");
+        scheduler.push(Priority::Bulk, Some("position".to_string()), 1, "x=2");
+        scheduler.push(Priority::Normal, Some("position".to_string()), 1, "x=3");
+        assert_eq!(
+            drain(&mut scheduler, Duration::ZERO),
+            vec!["x=3", "hp=9", "x=2"]
+        );
+    }
+
+    #[test]
+    fn oldest_non_critical_messages_are_dropped_when_queue_is_full() {
+        let mut scheduler = Scheduler::default();
+        for _ in 0..MAX_QUEUED_MESSAGES {
+            scheduler.push(Priority::Bulk, None, 1, "old");
+            scheduler.push(Priority::Critical, None, 1, "critical");
+        }
+        scheduler.push(Priority::Bulk, None, 1, "new");
+        let sent = drain(&mut scheduler, Duration::ZERO);
+        assert_eq!(sent.len(), 2 * MAX_QUEUED_MESSAGES);
+        assert_eq!(sent.last(), Some(&"new"));
+    }
+
+    #[test]
+    fn budget_limits_rate_of_non_critical_messages() {
+        let mut scheduler = Scheduler::default();
+        scheduler.set_budget(Some(1000), Duration::ZERO);
+        for message in ["a", "b", "c"] {
+            scheduler.push(Priority::Normal, None, 400, message);
+        }
+        assert_eq!(drain(&mut scheduler, Duration::ZERO), vec!["a", "b"]);
+        assert_eq!(
+            scheduler.next(Duration::ZERO),
+            Next::Wait(Duration::from_millis(200))
+        );
+
+        scheduler.push(Priority::Critical, None, 400, "critical");
+        assert_eq!(drain(&mut scheduler, Duration::ZERO), vec!["critical"]);
+        assert_eq!(
+            scheduler.next(Duration::from_millis(200)),
+            Next::Wait(Duration::from_millis(400))
+        );
+        assert_eq!(drain(&mut scheduler, Duration::from_millis(600)), vec!["c"]);
+    }
+
+    #[test]
+    fn message_larger_than_budget_waits_for_full_budget() {
+        let mut scheduler = Scheduler::default();
+        scheduler.set_budget(Some(100), Duration::ZERO);
+        scheduler.push(Priority::Bulk, None, 50, "small");
+        scheduler.push(Priority::Bulk, None, 1000, "large");
+        assert_eq!(drain(&mut scheduler, Duration::ZERO), vec!["small"]);
+        assert_eq!(
+            drain(&mut scheduler, Duration::from_millis(500)),
+            vec!["large"]
+        );
+        assert_eq!(scheduler.next(Duration::from_millis(500)), Next::Idle);
+    }
+}
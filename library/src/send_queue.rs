@@ -0,0 +1,119 @@
+use crate::fragmentation;
+use crate::framing::Frame;
+use crate::negotiation::Negotiation;
+use crate::platform::DataChannel;
+use crate::NetworkError;
+use futures::channel::oneshot;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+/// Number of bytes that may wait in the buffer of a data channel before sending to it blocks,
+/// unless changed with `set_high_water_mark` of network managers
+pub const DEFAULT_HIGH_WATER_MARK: usize = 1024 * 1024;
+
+/// Senders waiting for the buffer of a single data channel to drain, in order of arrival
+#[derive(Debug, Default)]
+pub(crate) struct SendQueue {
+    waiters: RefCell<VecDeque<oneshot::Sender<()>>>,
+    /// High-water mark of the last sender that had to wait
+    high_water_mark: Cell<usize>,
+}
+
+impl SendQueue {
+    /// Fails all waiting senders with [NetworkError::DataChannelNotOpen]
+    pub(crate) fn close(&self) {
+        self.waiters.borrow_mut().clear();
+    }
+
+    fn must_wait(&self, data_channel: &DataChannel, high_water_mark: usize) -> bool {
+        !self.waiters.borrow().is_empty() || data_channel.buffered_amount() > high_water_mark
+    }
+}
+
+/// Lets the first waiting sender go if the buffer is below the high-water mark,
+/// called by data channels once their buffered amount drops low
+pub(crate) fn wake(data_channel: &DataChannel) {
+    let send_queue = data_channel.send_queue();
+    if data_channel.buffered_amount() > send_queue.high_water_mark.get() {
+        return;
+    }
+    let mut waiters = send_queue.waiters.borrow_mut();
+    while let Some(waiter) = waiters.pop_front() {
+        // senders that stopped waiting are skipped
+        if waiter.send(()).is_ok() {
+            break;
+        }
+    }
+}
+
+/// Message encoded for a single data channel, ready to be sent
+#[derive(Debug)]
+pub(crate) struct OutgoingMessage {
+    data_channel: DataChannel,
+    frames: Vec<Vec<u8>>,
+    high_water_mark: usize,
+}
+
+impl OutgoingMessage {
+    /// Encodes the text or binary frame as negotiated with the peer, if negotiation has completed
+    pub(crate) fn new(
+        data_channel: &DataChannel,
+        frame: Frame,
+        negotiation: Option<&Negotiation>,
+        high_water_mark: usize,
+    ) -> Result<Self, NetworkError> {
+        Ok(OutgoingMessage {
+            data_channel: data_channel.clone(),
+            frames: fragmentation::encode(
+                frame,
+                negotiation.and_then(Negotiation::max_message_size),
+                negotiation.and_then(Negotiation::compression),
+            )?,
+            high_water_mark,
+        })
+    }
+
+    /// Number of bytes the message takes in the buffer of the data channel
+    pub(crate) fn length(&self) -> usize {
+        self.frames.iter().map(Vec::len).sum()
+    }
+
+    /// Sends the message right away, or fails with [NetworkError::WouldBlock]
+    /// if more than the high-water mark is buffered or other senders are waiting
+    pub(crate) fn try_send(&self) -> Result<(), NetworkError> {
+        if self
+            .data_channel
+            .send_queue()
+            .must_wait(&self.data_channel, self.high_water_mark)
+        {
+            return Err(NetworkError::WouldBlock);
+        }
+        self.send_frames()
+    }
+
+    /// Waits until the buffer drains below the high-water mark and all senders
+    /// that waited before are done, then sends the message
+    pub(crate) async fn send(self) -> Result<(), NetworkError> {
+        let send_queue = self.data_channel.send_queue();
+        if send_queue.must_wait(&self.data_channel, self.high_water_mark) {
+            let (sender, receiver) = oneshot::channel();
+            send_queue.waiters.borrow_mut().push_back(sender);
+            send_queue.high_water_mark.set(self.high_water_mark);
+            self.data_channel
+                .set_buffered_amount_low_threshold(self.high_water_mark / 2);
+            receiver
+                .await
+                .map_err(|_| NetworkError::DataChannelNotOpen)?;
+        }
+        let result = self.send_frames();
+        wake(&self.data_channel);
+        result
+    }
+
+    fn send_frames(&self) -> Result<(), NetworkError> {
+        for frame in &self.frames {
+            self.data_channel.send(frame)?;
+        }
+        Ok(())
+    }
+}
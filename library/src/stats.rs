@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// State of the ICE agent of a connection, as in `RTCPeerConnection.iceConnectionState`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IceConnectionState {
+    /// Gathering addresses, or waiting for the candidates of the peer
+    New,
+    /// Checking pairs of candidates for one that works
+    Checking,
+    /// Usable pair of candidates was found, others may still be checked
+    Connected,
+    /// Checking candidates has finished and the connection is usable
+    Completed,
+    /// No pair of candidates works, the connection is lost
+    Failed,
+    /// Connectivity was lost, which may get resolved on its own
+    Disconnected,
+    /// Connection was closed
+    Closed,
+}
+
+/// Origin of a candidate, which tells whether a connection is direct or relayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CandidateType {
+    /// Address of a network interface of the peer, e.g. in the same local network
+    Host,
+    /// Public address of the peer as seen by a STUN server
+    ServerReflexive,
+    /// Public address of the peer learned during connectivity checks
+    PeerReflexive,
+    /// Address of a TURN server relaying all traffic
+    Relay,
+}
+
+impl CandidateType {
+    /// Parses the type the way it's written in candidates and stats, e.g. `srflx`
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "host" => Some(CandidateType::Host),
+            "srflx" => Some(CandidateType::ServerReflexive),
+            "prflx" => Some(CandidateType::PeerReflexive),
+            "relay" => Some(CandidateType::Relay),
+            _ => None,
+        }
+    }
+}
+
+/// Traffic of a single data channel of a connection
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ChannelStats {
+    /// Name of the channel, the one opened on connect is named after the session id
+    pub label: String,
+    /// Bytes sent, including framing headers of the library
+    pub bytes_sent: u64,
+    /// Bytes received, including framing headers of the library
+    pub bytes_received: u64,
+    /// Data channel messages sent, a fragmented message counts once for every fragment
+    pub messages_sent: u64,
+    /// Data channel messages received, a fragmented message counts once for every fragment
+    pub messages_received: u64,
+}
+
+/// Snapshot of the state of a connection with a single peer, returned by `stats` of network managers
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionStats {
+    /// Current state of the ICE agent
+    pub ice_connection_state: IceConnectionState,
+    /// Latest round-trip time measured on the selected pair of candidates,
+    /// `None` if none was measured yet or the platform doesn't measure it
+    pub round_trip_time: Option<Duration>,
+    /// Type of the local candidate of the selected pair, `None` until a pair is selected
+    pub local_candidate_type: Option<CandidateType>,
+    /// Type of the candidate of the peer of the selected pair, `None` until a pair is selected
+    pub remote_candidate_type: Option<CandidateType>,
+    /// Traffic of each data channel of the connection
+    pub channels: Vec<ChannelStats>,
+}
+
+impl ConnectionStats {
+    /// Whether the traffic goes through a TURN server instead of directly to the peer
+    pub fn is_relayed(&self) -> bool {
+        self.local_candidate_type == Some(CandidateType::Relay)
+            || self.remote_candidate_type == Some(CandidateType::Relay)
+    }
+}
+
+/// Candidate pair found in a stats report of either platform
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CandidatePairReport {
+    pub(crate) id: String,
+    pub(crate) local_candidate_id: String,
+    pub(crate) remote_candidate_id: String,
+    pub(crate) succeeded: bool,
+    pub(crate) nominated: bool,
+    /// Set by some browsers instead of pointing at the pair from the transport
+    pub(crate) selected: bool,
+    /// In seconds, as reported by WebRTC
+    pub(crate) current_round_trip_time: Option<f64>,
+}
+
+/// Parts of a stats report of either platform the library makes use of
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StatsReport {
+    /// Pair the transport says it uses, if the platform reports it
+    pub(crate) selected_candidate_pair_id: Option<String>,
+    pub(crate) candidate_pairs: Vec<CandidatePairReport>,
+    /// Type of every local and remote candidate, by id
+    pub(crate) candidate_types: HashMap<String, CandidateType>,
+    pub(crate) channels: Vec<ChannelStats>,
+}
+
+impl StatsReport {
+    /// Pair pointed at by the transport, otherwise the one marked as selected,
+    /// otherwise a nominated one that succeeded
+    fn selected_candidate_pair(&self) -> Option<&CandidatePairReport> {
+        let pairs = &self.candidate_pairs;
+        self.selected_candidate_pair_id
+            .as_ref()
+            .and_then(|id| pairs.iter().find(|pair| pair.id == *id))
+            .or_else(|| pairs.iter().find(|pair| pair.selected))
+            .or_else(|| pairs.iter().find(|pair| pair.nominated && pair.succeeded))
+    }
+
+    pub(crate) fn into_connection_stats(
+        self,
+        ice_connection_state: IceConnectionState,
+    ) -> ConnectionStats {
+        let selected = self.selected_candidate_pair();
+        let candidate_type = |id: &String| self.candidate_types.get(id).copied();
+        ConnectionStats {
+            ice_connection_state,
+            round_trip_time: selected
+                .and_then(|pair| pair.current_round_trip_time)
+                .filter(|round_trip_time| round_trip_time.is_finite() && *round_trip_time > 0.0)
+                .map(Duration::from_secs_f64),
+            local_candidate_type: selected
+                .and_then(|pair| candidate_type(&pair.local_candidate_id)),
+            remote_candidate_type: selected
+                .and_then(|pair| candidate_type(&pair.remote_candidate_id)),
+            channels: self.channels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pair(id: &str, local: &str, remote: &str) -> CandidatePairReport {
+        CandidatePairReport {
+            id: id.to_string(),
+            local_candidate_id: local.to_string(),
+            remote_candidate_id: remote.to_string(),
+            ..CandidatePairReport::default()
+        }
+    }
+
+    fn report(candidate_pairs: Vec<CandidatePairReport>) -> StatsReport {
+        StatsReport {
+            candidate_pairs,
+            candidate_types: HashMap::from([
+                ("host".to_string(), CandidateType::Host),
+                ("srflx".to_string(), CandidateType::ServerReflexive),
+                ("relay".to_string(), CandidateType::Relay),
+            ]),
+            ..StatsReport::default()
+        }
+    }
+
+    #[test]
+    fn pair_pointed_at_by_transport_is_selected() {
+        let mut stats_report = report(vec![
+            CandidatePairReport {
+                nominated: true,
+                succeeded: true,
+                ..pair("direct", "host", "srflx")
+            },
+            CandidatePairReport {
+                current_round_trip_time: Some(0.05),
+                ..pair("relayed", "relay", "srflx")
+            },
+        ]);
+        stats_report.selected_candidate_pair_id = Some("relayed".to_string());
+        let stats = stats_report.into_connection_stats(IceConnectionState::Connected);
+        assert_eq!(stats.local_candidate_type, Some(CandidateType::Relay));
+        assert_eq!(
+            stats.remote_candidate_type,
+            Some(CandidateType::ServerReflexive)
+        );
+        assert_eq!(stats.round_trip_time, Some(Duration::from_millis(50)));
+        assert!(stats.is_relayed());
+    }
+
+    #[test]
+    fn nominated_pair_is_selected_without_transport_stats() {
+        let stats = report(vec![
+            pair("waiting", "relay", "relay"),
+            CandidatePairReport {
+                nominated: true,
+                succeeded: true,
+                current_round_trip_time: Some(0.0),
+                ..pair("direct", "host", "host")
+            },
+        ])
+        .into_connection_stats(IceConnectionState::Completed);
+        assert_eq!(stats.local_candidate_type, Some(CandidateType::Host));
+        assert_eq!(stats.remote_candidate_type, Some(CandidateType::Host));
+        assert_eq!(stats.round_trip_time, None);
+        assert!(!stats.is_relayed());
+    }
+
+    #[test]
+    fn nothing_is_selected_while_checking() {
+        let stats = report(vec![pair("waiting", "host", "host")])
+            .into_connection_stats(IceConnectionState::Checking);
+        assert_eq!(stats.local_candidate_type, None);
+        assert_eq!(stats.remote_candidate_type, None);
+    }
+
+    #[test]
+    fn candidate_types_are_parsed_from_names() {
+        assert_eq!(
+            CandidateType::from_name("srflx"),
+            Some(CandidateType::ServerReflexive)
+        );
+        assert_eq!(
+            CandidateType::from_name("relay"),
+            Some(CandidateType::Relay)
+        );
+        assert_eq!(CandidateType::from_name("unspecified"), None);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::platform;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Task calling a callback at a fixed interval, e.g. sending pings or snapshots
+#[derive(Debug)]
+pub(crate) struct IntervalTimer {
+    interval: Cell<Option<Duration>>,
+    /// Changed whenever the task is restarted or stopped, so that the old one ends
+    generation: Cell<u32>,
+    started: Cell<bool>,
+}
+
+impl IntervalTimer {
+    /// `None` interval never calls the callback
+    pub(crate) fn new(interval: Option<Duration>) -> Self {
+        IntervalTimer {
+            interval: Cell::new(interval),
+            generation: Cell::new(0),
+            started: Cell::new(false),
+        }
+    }
+
+    /// Takes effect once the timer is (re)started
+    pub(crate) fn set_interval(&self, interval: Option<Duration>) {
+        self.interval.set(interval);
+    }
+
+    pub(crate) fn is_started(&self) -> bool {
+        self.started.get()
+    }
+
+    /// Calls `callback` every interval until the timer is restarted or stopped,
+    /// the first time one interval from now
+    pub(crate) fn start(self: &Rc<Self>, mut callback: impl FnMut() + 'static) {
+        self.stop();
+        self.started.set(true);
+        let Some(interval) = self.interval.get() else {
+            return;
+        };
+        let generation = self.generation.get();
+        let timer = self.clone();
+        platform::spawn_local(async move {
+            loop {
+                platform::sleep(interval).await;
+                if timer.generation.get() != generation {
+                    break;
+                }
+                callback();
+            }
+        });
+    }
+
+    pub(crate) fn stop(&self) {
+        self.started.set(false);
+        self.generation.set(self.generation.get().wrapping_add(1));
+    }
+}
@@ -9,7 +9,7 @@ use std::rc::Rc;
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
 use web_sys::console;
 
-const SIGNALING_SERVER_URL: &str = "ws://0.0.0.0:9001/one-to-many";
+const SIGNALING_SERVER_URL: &str = "ws://0.0.0.0:9001/signal";
 
 wasm_bindgen_test_configure!(run_in_browser);
 
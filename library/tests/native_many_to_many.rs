@@ -0,0 +1,146 @@
+//! Test suite for the native backend, running against an in-process signaling server.
+
+#![cfg(feature = "native")]
+
+use futures::StreamExt;
+use rusty_games_library::many_to_many::NetworkManager;
+use rusty_games_library::{ClockOffset, ConnectionType, NetworkEvent, SessionId, UserId};
+use rusty_games_signaling_server::server::SignalingServer;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::task::LocalSet;
+
+#[tokio::test]
+async fn single_message_passes_between_all_with_event_streams() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer = || async {
+                let mut peer = NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("native-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap();
+                let mut events = peer.start_with_events().unwrap();
+                let mut received_messages = Vec::new();
+                while received_messages.len() < 2 {
+                    match events.next().await.unwrap() {
+                        NetworkEvent::PeerConnected(user_id) => {
+                            peer.send_message(user_id, "hello!").unwrap()
+                        }
+                        NetworkEvent::Message(_, message) => received_messages.push(message),
+                        event => panic!("unexpected event: {:?}", event),
+                    }
+                }
+                received_messages
+            };
+
+            let all_received_messages = tokio::time::timeout(
+                Duration::from_secs(30),
+                futures::future::join_all([peer(), peer(), peer()]),
+            )
+            .await
+            .expect("messages were not exchanged in time");
+            for received_messages in all_received_messages {
+                assert_eq!(received_messages, vec!["hello!", "hello!"]);
+            }
+        })
+        .await;
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn peers_converge_on_clock_of_lowest_user_id() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let mut peers = Vec::new();
+            for _ in 0..3 {
+                let mut peer = NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("clock-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap();
+                peer.set_ping_interval(Some(Duration::from_millis(20)));
+                let mut events = peer.start_with_events().unwrap();
+                let connected: Rc<RefCell<Vec<UserId>>> = Rc::default();
+                tokio::task::spawn_local({
+                    let connected = connected.clone();
+                    async move {
+                        while let Some(event) = events.next().await {
+                            if let NetworkEvent::PeerConnected(user_id) = event {
+                                connected.borrow_mut().push(user_id);
+                            }
+                        }
+                    }
+                });
+                peers.push((peer, connected));
+            }
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                while peers
+                    .iter()
+                    .any(|(_, connected)| connected.borrow().len() < 2)
+                {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                let lowest = peers
+                    .iter()
+                    .flat_map(|(_, connected)| connected.borrow().clone())
+                    .min_by_key(|user_id| user_id.into_inner())
+                    .unwrap();
+                // the reference is the only peer that isn't connected to it
+                let is_reference: Vec<bool> = peers
+                    .iter()
+                    .map(|(_, connected)| !connected.borrow().contains(&lowest))
+                    .collect();
+                assert_eq!(is_reference.iter().filter(|is| **is).count(), 1);
+
+                let synchronized = || {
+                    peers
+                        .iter()
+                        .zip(&is_reference)
+                        .all(|((peer, _), is_reference)| match peer.clock_offset() {
+                            Some(offset) if *is_reference => offset == ClockOffset::default(),
+                            Some(offset) => offset.uncertainty > Duration::ZERO,
+                            None => false,
+                        })
+                };
+                while !synchronized() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+
+                // all peers run on the same clock here, so offsets only reflect the error
+                for (peer, _) in &peers {
+                    let offset = peer.clock_offset().unwrap();
+                    assert!(offset.uncertainty < Duration::from_secs(1));
+                    let error = Duration::from_micros(offset.offset_micros.unsigned_abs());
+                    assert!(error <= offset.uncertainty + Duration::from_millis(1));
+                }
+                let times: Vec<Duration> = peers
+                    .iter()
+                    .map(|(peer, _)| peer.estimated_remote_time().unwrap())
+                    .collect();
+                let spread = *times.iter().max().unwrap() - *times.iter().min().unwrap();
+                assert!(spread < Duration::from_secs(1));
+            })
+            .await
+            .expect("clocks were not synchronized in time");
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
@@ -0,0 +1,694 @@
+//! Test suite for the native backend, running against an in-process signaling server.
+
+#![cfg(feature = "native")]
+
+use futures::StreamExt;
+use rusty_games_library::one_to_many::{MiniClient, MiniServer};
+use rusty_games_library::replication::{
+    Interpolate, ReplicationClient, ReplicationServer, DEFAULT_INTERPOLATION_DELAY,
+};
+use rusty_games_library::{
+    ClockOffset, ConnectionType, LatencyStats, NetworkError, NetworkEvent, Priority, SessionId,
+    UserId,
+};
+use rusty_games_signaling_server::server::SignalingServer;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::task::LocalSet;
+
+#[tokio::test]
+async fn messages_pass_between_server_and_all_clients() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let server_received_messages = Rc::new(RefCell::new(Vec::new()));
+    let client_received_messages = Rc::new(RefCell::new(Vec::new()));
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                SessionId::new("native-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let server_clone = server.clone();
+            let server_on_open = move |user_id: UserId| {
+                server_clone.send_message(user_id, "ping!").unwrap();
+            };
+            let server_on_message = {
+                let server_received_messages = server_received_messages.clone();
+                move |_, message: String| server_received_messages.borrow_mut().push(message)
+            };
+            server.start(server_on_open, server_on_message).unwrap();
+
+            for _ in 0..2 {
+                let mut client = MiniClient::new(
+                    &signaling_server_url,
+                    SessionId::new("native-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap();
+                let client_clone = client.clone();
+                let client_on_message = {
+                    let client_received_messages = client_received_messages.clone();
+                    move |_, message: String| {
+                        client_clone.send_message_to_host("pong!").unwrap();
+                        client_received_messages.borrow_mut().push(message);
+                    }
+                };
+                client.start(|_| {}, client_on_message).unwrap();
+            }
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                while server_received_messages.borrow().len() < 2 {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            })
+            .await
+            .expect("messages were not exchanged in time");
+        })
+        .await;
+
+    assert_eq!(*client_received_messages.borrow(), vec!["ping!", "ping!"]);
+    assert_eq!(*server_received_messages.borrow(), vec!["pong!", "pong!"]);
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn sending_without_connections_fails() {
+    LocalSet::new()
+        .run_until(async {
+            let server = MiniServer::new(
+                "ws://127.0.0.1:9/signal",
+                SessionId::new("native-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let client = MiniClient::new(
+                "ws://127.0.0.1:9/signal",
+                SessionId::new("native-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+
+            let user_id = UserId::new(1);
+            assert!(matches!(
+                server.send_message(user_id, "ping!"),
+                Err(NetworkError::NoConnectionForUser(id)) if id == user_id
+            ));
+            assert!(server.send_message_to_all("ping!").is_empty());
+            assert!(matches!(
+                client.send_message_to_host("pong!"),
+                Err(NetworkError::DataChannelNotOpen)
+            ));
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn server_disconnects_single_client() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let session_id = SessionId::new("disconnect-session-id".to_string());
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                session_id.clone(),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut clients = Vec::new();
+            for _ in 0..2 {
+                let mut client = MiniClient::new(
+                    &signaling_server_url,
+                    session_id.clone(),
+                    ConnectionType::Local,
+                )
+                .unwrap();
+                let events = client.start_with_events().unwrap();
+                clients.push((client, events));
+            }
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                let mut connected = Vec::new();
+                while connected.len() < 2 {
+                    if let Some(NetworkEvent::PeerConnected(user_id)) = server_events.next().await {
+                        connected.push(user_id);
+                    }
+                }
+
+                server.disconnect(connected[0]).unwrap();
+                assert!(matches!(
+                    server.disconnect(connected[0]),
+                    Err(NetworkError::NoConnectionForUser(_))
+                ));
+                let results = server.send_message_to_all("still here?");
+                assert_eq!(results.len(), 1);
+                assert!(results[&connected[1]].is_ok());
+
+                let mut disconnected = 0;
+                for (_, events) in &mut clients {
+                    loop {
+                        match events.next().await {
+                            Some(NetworkEvent::PeerDisconnected(_)) => {
+                                disconnected += 1;
+                                break;
+                            }
+                            Some(NetworkEvent::Message(_, message)) => {
+                                assert_eq!(message, "still here?");
+                                break;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                assert_eq!(disconnected, 1);
+            })
+            .await
+            .expect("client was not disconnected in time");
+
+            server.close();
+            for (client, _) in &clients {
+                client.close();
+            }
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn large_message_is_fragmented_and_reassembled_for_all_clients() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let large_message: String = (0..200_000)
+        .map(|i| char::from(b'a' + (i % 26) as u8))
+        .collect();
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                SessionId::new("fragments-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut clients = Vec::new();
+            let mut client_events = Vec::new();
+            for _ in 0..2 {
+                let mut client = MiniClient::new(
+                    &signaling_server_url,
+                    SessionId::new("fragments-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap();
+                client_events.push(client.start_with_events().unwrap());
+                clients.push(client);
+            }
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                let mut connected = Vec::new();
+                while connected.len() < 2 {
+                    if let Some(NetworkEvent::PeerConnected(user_id)) = server_events.next().await {
+                        connected.push(user_id);
+                    }
+                }
+                for user_id in connected {
+                    assert!(server.max_message_size(user_id).unwrap() > 0);
+                }
+
+                let results = server.send_message_to_all(&large_message);
+                assert!(results.values().all(Result::is_ok));
+                for events in &mut client_events {
+                    let message = loop {
+                        match events.next().await {
+                            Some(NetworkEvent::Message(_, message)) => break message,
+                            Some(NetworkEvent::Error(error)) => panic!("{}", error),
+                            _ => {}
+                        }
+                    };
+                    assert_eq!(message, large_message);
+                }
+            })
+            .await
+            .expect("large message was not delivered in time");
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn critical_messages_overtake_bulk_traffic_held_by_budget() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let chunk = "x".repeat(400);
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                SessionId::new("priorities-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client = MiniClient::new(
+                &signaling_server_url,
+                SessionId::new("priorities-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                let user_id = loop {
+                    if let Some(NetworkEvent::PeerConnected(user_id)) = server_events.next().await {
+                        break user_id;
+                    }
+                };
+                server.set_bandwidth_budget(user_id, Some(1000)).unwrap();
+                for _ in 0..4 {
+                    server
+                        .send_message_with_priority(user_id, &chunk, Priority::Bulk)
+                        .unwrap();
+                }
+                for position in ["x=1", "x=2", "x=3"] {
+                    server
+                        .send_state_update(user_id, "position", position, Priority::Normal)
+                        .unwrap();
+                }
+                server
+                    .send_message_with_priority(user_id, "player died", Priority::Critical)
+                    .unwrap();
+
+                let mut received = Vec::new();
+                while received.len() < 6 {
+                    match client_events.next().await {
+                        Some(NetworkEvent::Message(_, message)) => received.push(message),
+                        Some(NetworkEvent::Error(error)) => panic!("{}", error),
+                        _ => {}
+                    }
+                }
+                assert_eq!(received[..2], ["player died", "x=3"]);
+                assert!(received[2..].iter().all(|message| *message == chunk));
+            })
+            .await
+            .expect("prioritized messages were not delivered in time");
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn critical_message_queued_while_waiting_for_budget_is_sent_right_away() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let chunk = "x".repeat(1000);
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                SessionId::new("waiting-priorities-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client = MiniClient::new(
+                &signaling_server_url,
+                SessionId::new("waiting-priorities-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                let user_id = loop {
+                    if let Some(NetworkEvent::PeerConnected(user_id)) = server_events.next().await {
+                        break user_id;
+                    }
+                };
+                // the first chunk uses up the budget for ten seconds, the second one waits for it
+                server.set_bandwidth_budget(user_id, Some(100)).unwrap();
+                for _ in 0..2 {
+                    server
+                        .send_message_with_priority(user_id, &chunk, Priority::Bulk)
+                        .unwrap();
+                }
+                while !matches!(
+                    client_events.next().await,
+                    Some(NetworkEvent::Message(_, message)) if message == chunk
+                ) {}
+                tokio::time::sleep(Duration::from_millis(100)).await;
+
+                server
+                    .send_message_with_priority(user_id, "player died", Priority::Critical)
+                    .unwrap();
+                let received = tokio::time::timeout(Duration::from_secs(2), async {
+                    loop {
+                        match client_events.next().await {
+                            Some(NetworkEvent::Message(_, message)) => break message,
+                            Some(NetworkEvent::Error(error)) => panic!("{}", error),
+                            _ => {}
+                        }
+                    }
+                })
+                .await
+                .expect("critical message waited for the budget");
+                assert_eq!(received, "player died");
+            })
+            .await
+            .expect("prioritized messages were not delivered in time");
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn compression_is_used_only_with_clients_that_enabled_it() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let players: Vec<String> = (0..200)
+        .map(|i| format!(r#"{{"id":{},"x":{},"y":{},"alive":true}}"#, i, i * 3, i * 7))
+        .collect();
+    let snapshot = format!(r#"{{"players":[{}]}}"#, players.join(","));
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                SessionId::new("compression-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            server.set_compression_threshold(Some(1024));
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client_events = Vec::new();
+            for compression_threshold in [Some(1024), None] {
+                let mut client = MiniClient::new(
+                    &signaling_server_url,
+                    SessionId::new("compression-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap();
+                client.set_compression_threshold(compression_threshold);
+                client_events.push(client.start_with_events().unwrap());
+            }
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                let mut connected = 0;
+                while connected < 2 {
+                    if let Some(NetworkEvent::PeerConnected(_)) = server_events.next().await {
+                        connected += 1;
+                    }
+                }
+
+                let results = server.send_message_to_all(&snapshot);
+                assert!(results.values().all(Result::is_ok));
+                for events in &mut client_events {
+                    let message = loop {
+                        match events.next().await {
+                            Some(NetworkEvent::Message(_, message)) => break message,
+                            Some(NetworkEvent::Error(error)) => panic!("{}", error),
+                            _ => {}
+                        }
+                    };
+                    assert_eq!(message, snapshot);
+                }
+            })
+            .await
+            .expect("snapshot was not delivered in time");
+
+            let stats = server.compression_stats();
+            assert_eq!(stats.compressed_messages, 1);
+            assert_eq!(stats.uncompressed_bytes, snapshot.len() as u64);
+            assert!(stats.ratio() > 5.0);
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn pings_measure_latency_without_reaching_application() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                SessionId::new("latency-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let mut client = MiniClient::new(
+                &signaling_server_url,
+                SessionId::new("latency-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            server.set_ping_interval(Some(Duration::from_millis(20)));
+            client.set_ping_interval(None);
+            let updates: Rc<RefCell<Vec<(UserId, LatencyStats)>>> = Rc::default();
+            server.on_latency_update({
+                let updates = updates.clone();
+                move |user_id, stats| updates.borrow_mut().push((user_id, stats))
+            });
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                let client_id = loop {
+                    if let Some(NetworkEvent::PeerConnected(user_id)) = server_events.next().await {
+                        break user_id;
+                    }
+                };
+                while updates.borrow().len() < 3 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                assert!(updates
+                    .borrow()
+                    .iter()
+                    .all(|(user_id, _)| *user_id == client_id));
+
+                let stats = server.latency(client_id).unwrap();
+                assert!(stats.round_trip_time > Duration::ZERO);
+                assert!(stats.round_trip_time < Duration::from_secs(1));
+                assert_eq!(stats.packet_loss, 0.0);
+                assert_eq!(client.latency(), None);
+                // clients synchronize to the host only from pongs to their own pings
+                assert_eq!(client.estimated_remote_time(), None);
+                assert_eq!(server.clock_offset(), ClockOffset::default());
+
+                // pings that arrived before it are not delivered as messages
+                server.send_message(client_id, "done").unwrap();
+                let message = loop {
+                    match client_events.next().await {
+                        Some(NetworkEvent::Message(_, message)) => break message,
+                        Some(NetworkEvent::Error(error)) => panic!("{}", error),
+                        _ => {}
+                    }
+                };
+                assert_eq!(message, "done");
+            })
+            .await
+            .expect("latency was not measured in time");
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Position {
+    x: f64,
+    y: f64,
+}
+
+impl Interpolate for Position {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        Position {
+            x: self.x + (other.x - self.x) * t,
+            y: self.y + (other.y - self.y) * t,
+        }
+    }
+}
+
+#[tokio::test]
+async fn replicated_state_reaches_client_without_reaching_application() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let received_messages = Rc::new(RefCell::new(Vec::new()));
+    let opened_channels = Rc::new(RefCell::new(Vec::new()));
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                SessionId::new("native-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            server.start(|_| {}, |_, _| {}).unwrap();
+            let replication_server = ReplicationServer::new(&server, 20);
+            replication_server.set_entity(1, Position { x: 0.0, y: 0.0 });
+            replication_server.set_entity(2, Position { x: 5.0, y: 5.0 });
+            replication_server.start();
+
+            let mut client = MiniClient::new(
+                &signaling_server_url,
+                SessionId::new("native-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            {
+                let opened_channels = opened_channels.clone();
+                client.on_channel_open(move |_, name| opened_channels.borrow_mut().push(name));
+            }
+            let client_on_message = {
+                let received_messages = received_messages.clone();
+                move |_, message: String| received_messages.borrow_mut().push(message)
+            };
+            client.start(|_| {}, client_on_message).unwrap();
+            let replication_client = ReplicationClient::<Position>::new(&client);
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                while replication_client.latest_state().len() < 2 {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            })
+            .await
+            .expect("state was not replicated in time");
+
+            replication_server.set_entity(1, Position { x: 10.0, y: 0.0 });
+            replication_server.remove_entity(2);
+            let expected = BTreeMap::from([(1, Position { x: 10.0, y: 0.0 })]);
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while replication_client.latest_state() != expected {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("changes were not replicated in time");
+
+            // rendered state catches up once the change is older than the interpolation delay
+            tokio::time::sleep(DEFAULT_INTERPOLATION_DELAY * 3).await;
+            assert_eq!(replication_client.interpolated_state(), expected);
+            assert!(replication_client.latest_tick().unwrap() <= replication_server.current_tick());
+
+            server.close();
+            client.close();
+        })
+        .await;
+
+    assert!(received_messages.borrow().is_empty());
+    assert!(opened_channels.borrow().is_empty());
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn closing_drops_all_callbacks() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let opened = Rc::new(RefCell::new(Vec::new()));
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = MiniServer::new(
+                &signaling_server_url,
+                SessionId::new("dropping-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let mut client = MiniClient::new(
+                &signaling_server_url,
+                SessionId::new("dropping-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let callbacks = || {
+                let on_open = {
+                    let opened = opened.clone();
+                    move |user_id| opened.borrow_mut().push(user_id)
+                };
+                let on_message = {
+                    let opened = opened.clone();
+                    move |_, _| drop(opened.borrow())
+                };
+                (on_open, on_message)
+            };
+            let (on_open, on_message) = callbacks();
+            server.start(on_open, on_message).unwrap();
+            let (on_open, on_message) = callbacks();
+            client.start(on_open, on_message).unwrap();
+            {
+                let opened = opened.clone();
+                server.on_channel_open(move |_, _| drop(opened.borrow()));
+            }
+            {
+                let opened = opened.clone();
+                client.on_latency_update(move |_, _| drop(opened.borrow()));
+            }
+            // their handlers and the timer of the server are held by the managers as well
+            let replication_server = ReplicationServer::new(&server, 20);
+            replication_server.set_entity(1, Position { x: 0.0, y: 0.0 });
+            replication_server.start();
+            let replication_client = ReplicationClient::<Position>::new(&client);
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                while replication_client.latest_state().is_empty() {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            })
+            .await
+            .expect("state was not replicated in time");
+            assert_eq!(opened.borrow().len(), 2);
+
+            server.close();
+            client.close();
+            // while tasks of the managers could still be running
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(Rc::strong_count(&opened), 1);
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
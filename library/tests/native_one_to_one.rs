@@ -0,0 +1,810 @@
+//! Test suite for the native backend, running against an in-process signaling server.
+
+#![cfg(feature = "native")]
+
+use futures::StreamExt;
+use rusty_games_library::one_to_one::NetworkManager;
+use rusty_games_library::rollback::{Frame, RollbackConfig, RollbackGame, RollbackSession};
+use rusty_games_library::{
+    CandidateType, ChannelConfig, ConnectionType, DropPolicy, IceConnectionState, NetworkError,
+    NetworkEvent, SessionId,
+};
+use rusty_games_signaling_server::server::SignalingServer;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::task::LocalSet;
+
+#[tokio::test]
+async fn single_message_passes_both_ways() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let server_received_message = Rc::new(RefCell::new(None));
+    let client_received_message = Rc::new(RefCell::new(None));
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = NetworkManager::new(
+                &signaling_server_url,
+                SessionId::new("native-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let server_clone = server.clone();
+            let server_on_open = move || server_clone.send_message("ping!").unwrap();
+            let server_on_message = {
+                let server_received_message = server_received_message.clone();
+                move |message| *server_received_message.borrow_mut() = Some(message)
+            };
+            server.start(server_on_open, server_on_message).unwrap();
+
+            let mut client = NetworkManager::new(
+                &signaling_server_url,
+                SessionId::new("native-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let client_clone = client.clone();
+            let client_on_message = {
+                let client_received_message = client_received_message.clone();
+                move |message| {
+                    client_clone.send_message("pong!").unwrap();
+                    *client_received_message.borrow_mut() = Some(message);
+                }
+            };
+            client.start(|| {}, client_on_message).unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                while server_received_message.borrow().is_none() {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            })
+            .await
+            .expect("messages were not exchanged in time");
+        })
+        .await;
+
+    assert_eq!(client_received_message.borrow().as_deref(), Some("ping!"));
+    assert_eq!(server_received_message.borrow().as_deref(), Some("pong!"));
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn binary_messages_reach_binary_callback() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let received_messages = Rc::new(RefCell::new(Vec::new()));
+    let received_binary_messages = Rc::new(RefCell::new(Vec::new()));
+    // larger than a single data channel message, so that it's fragmented
+    let large_message: Vec<u8> = (0..200_000).map(|index| index as u8).collect();
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = NetworkManager::new(
+                &signaling_server_url,
+                SessionId::new("binary-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let server_clone = server.clone();
+            let server_on_open = {
+                let large_message = large_message.clone();
+                let sent = Rc::new(Cell::new(false));
+                move || {
+                    // called for the channel of each peer, by copies of this closure
+                    if sent.replace(true) {
+                        return;
+                    }
+                    server_clone.send_binary_message(&[]).unwrap();
+                    server_clone.send_binary_message(&large_message).unwrap();
+                }
+            };
+            server.start(server_on_open, |_| {}).unwrap();
+
+            let mut client = NetworkManager::new(
+                &signaling_server_url,
+                SessionId::new("binary-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let client_on_message = {
+                let received_messages = received_messages.clone();
+                move |message| received_messages.borrow_mut().push(message)
+            };
+            client.start(|| {}, client_on_message).unwrap();
+            {
+                let received_binary_messages = received_binary_messages.clone();
+                client.on_binary_message(move |message| {
+                    received_binary_messages.borrow_mut().push(message)
+                });
+            }
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                while received_binary_messages.borrow().len() < 2 {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            })
+            .await
+            .expect("binary messages were not received in time");
+        })
+        .await;
+
+    assert_eq!(
+        *received_binary_messages.borrow(),
+        vec![vec![], large_message]
+    );
+    assert!(received_messages.borrow().is_empty());
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn connect_resolves_once_both_peers_connect() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer_generator = || {
+                NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("native-connect-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap()
+            };
+            let mut server = peer_generator();
+            let mut client = peer_generator();
+
+            let (server_connected, client_connected) = tokio::join!(
+                server.connect(|| {}, |_| {}, Duration::from_secs(30)),
+                client.connect(|| {}, |_| {}, Duration::from_secs(30)),
+            );
+            let server_connected = server_connected.unwrap();
+            let client_connected = client_connected.unwrap();
+            assert_ne!(server_connected.peer_id, client_connected.peer_id);
+        })
+        .await;
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn connect_times_out_without_other_peer() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = NetworkManager::new(
+                &signaling_server_url,
+                SessionId::new("native-lonely-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let result = server
+                .connect(|| {}, |_| {}, Duration::from_millis(500))
+                .await;
+            assert!(matches!(result, Err(NetworkError::Timeout(_))));
+        })
+        .await;
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn connect_fails_without_signaling_server() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    signaling_server.shutdown().await;
+
+    LocalSet::new()
+        .run_until(async {
+            let mut server = NetworkManager::new(
+                &signaling_server_url,
+                SessionId::new("native-session-id".to_string()),
+                ConnectionType::Local,
+            )
+            .unwrap();
+            let result = server.connect(|| {}, |_| {}, Duration::from_secs(30)).await;
+            assert!(matches!(result, Err(NetworkError::SignalingClosed)));
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn polled_events_arrive_in_order() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer_generator = || {
+                NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("polled-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap()
+            };
+            let mut server = peer_generator();
+            let mut client = peer_generator();
+            server
+                .start_with_event_queue(16, DropPolicy::DropOldest)
+                .unwrap();
+            client
+                .start_with_event_queue(16, DropPolicy::DropOldest)
+                .unwrap();
+
+            let mut sent = false;
+            let mut client_connected = false;
+            let mut client_messages = Vec::new();
+            tokio::time::timeout(Duration::from_secs(30), async {
+                while client_messages.len() < 2 {
+                    for event in server.poll_events() {
+                        if let (NetworkEvent::PeerConnected(_), false) = (event, sent) {
+                            server.send_message("first").unwrap();
+                            server.send_message("second").unwrap();
+                            sent = true;
+                        }
+                    }
+                    for event in client.poll_events() {
+                        match event {
+                            NetworkEvent::PeerConnected(_) => client_connected = true,
+                            NetworkEvent::Message(_, message) => {
+                                assert!(client_connected, "message polled before connection");
+                                client_messages.push(message);
+                            }
+                            event => panic!("unexpected event: {:?}", event),
+                        }
+                    }
+                    tokio::time::sleep(Duration::from_millis(16)).await;
+                }
+            })
+            .await
+            .expect("events were not polled in time");
+
+            assert_eq!(client_messages, vec!["first", "second"]);
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn closing_ends_events_and_disconnects_other_peer() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer_generator = || {
+                NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("closing-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap()
+            };
+            let mut server = peer_generator();
+            let mut client = peer_generator();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                for events in [&mut server_events, &mut client_events] {
+                    while !matches!(events.next().await, Some(NetworkEvent::PeerConnected(_))) {}
+                }
+
+                server.close();
+                while server_events.next().await.is_some() {}
+                while !matches!(
+                    client_events.next().await,
+                    Some(NetworkEvent::PeerDisconnected(_))
+                ) {}
+            })
+            .await
+            .expect("peers were not disconnected in time");
+            assert!(matches!(
+                server.send_message("ping!"),
+                Err(NetworkError::DataChannelNotOpen)
+            ));
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn closing_drops_all_callbacks() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let opened_channels = Rc::new(RefCell::new(Vec::new()));
+
+    LocalSet::new()
+        .run_until(async {
+            let mut network_managers = Vec::new();
+            for player in 0..2 {
+                let mut network_manager = NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("dropping-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap();
+                let on_open = {
+                    let opened_channels = opened_channels.clone();
+                    move || opened_channels.borrow_mut().push(String::new())
+                };
+                let on_message = {
+                    let opened_channels = opened_channels.clone();
+                    move |_| drop(opened_channels.borrow())
+                };
+                network_manager.start(on_open, on_message).unwrap();
+                network_manager.on_channel_open({
+                    let opened_channels = opened_channels.clone();
+                    move |channel| opened_channels.borrow_mut().push(channel)
+                });
+                network_manager.on_latency_update({
+                    let opened_channels = opened_channels.clone();
+                    move |_| drop(opened_channels.borrow())
+                });
+                // its handler of the rollback channel is stored by the manager as well
+                let _ =
+                    RollbackSession::<InputLog>::new(&network_manager, player, Default::default());
+                network_managers.push(network_manager);
+            }
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                while opened_channels.borrow().len() < 2 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                network_managers[0]
+                    .open_channel("state", ChannelConfig::unreliable())
+                    .await
+                    .unwrap();
+                while !opened_channels
+                    .borrow()
+                    .iter()
+                    .any(|channel| channel == "state")
+                {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("channel was not opened in time");
+
+            for network_manager in &network_managers {
+                network_manager.close();
+            }
+            // while tasks of the managers could still be running
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(Rc::strong_count(&opened_channels), 1);
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn channel_opened_mid_session_carries_messages() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer_generator = || {
+                NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("channel-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap()
+            };
+            let mut server = peer_generator();
+            let mut client = peer_generator();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                for events in [&mut server_events, &mut client_events] {
+                    while !matches!(events.next().await, Some(NetworkEvent::PeerConnected(_))) {}
+                }
+
+                client
+                    .open_channel("state", ChannelConfig::unreliable())
+                    .await
+                    .unwrap();
+                for events in [&mut server_events, &mut client_events] {
+                    while !matches!(
+                        events.next().await,
+                        Some(NetworkEvent::ChannelOpened(_, channel)) if channel == "state"
+                    ) {}
+                }
+
+                server.send_message_on_channel("state", "ping!").unwrap();
+                while !matches!(
+                    client_events.next().await,
+                    Some(NetworkEvent::Message(_, message)) if message == "ping!"
+                ) {}
+            })
+            .await
+            .expect("channel was not opened in time");
+            assert!(matches!(
+                server.send_message_on_channel("missing", "ping!"),
+                Err(NetworkError::UnknownChannel(_))
+            ));
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn channels_opened_by_both_peers_at_once_open() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer_generator = || {
+                NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("glare-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap()
+            };
+            let mut server = peer_generator();
+            let mut client = peer_generator();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                for events in [&mut server_events, &mut client_events] {
+                    while !matches!(events.next().await, Some(NetworkEvent::PeerConnected(_))) {}
+                }
+
+                // offers of both peers collide, then the connection is renegotiated once more
+                let (server_result, client_result) = tokio::join!(
+                    server.open_channel("server-state", ChannelConfig::unreliable()),
+                    client.open_channel("client-state", ChannelConfig::unreliable()),
+                );
+                server_result.unwrap();
+                client_result.unwrap();
+                client
+                    .open_channel("chat", ChannelConfig::default())
+                    .await
+                    .unwrap();
+                for events in [&mut server_events, &mut client_events] {
+                    let mut opened = Vec::new();
+                    while opened.len() < 3 {
+                        match events.next().await {
+                            Some(NetworkEvent::ChannelOpened(_, channel)) => opened.push(channel),
+                            Some(NetworkEvent::Error(error)) => panic!("{}", error),
+                            _ => {}
+                        }
+                    }
+                    opened.sort();
+                    assert_eq!(opened, ["chat", "client-state", "server-state"]);
+                }
+            })
+            .await
+            .expect("channels were not opened in time");
+
+            // a collision the polite peer failed to resolve would be reported by now
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            for events in [&mut server_events, &mut client_events] {
+                while let Ok(Some(event)) =
+                    tokio::time::timeout(Duration::from_millis(10), events.next()).await
+                {
+                    assert!(!matches!(event, NetworkEvent::Error(_)), "{:?}", event);
+                }
+            }
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn channel_closed_by_other_peer_is_forgotten() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer_generator = || {
+                NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("closed-channel-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap()
+            };
+            let mut server = peer_generator();
+            let mut client = peer_generator();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                for events in [&mut server_events, &mut client_events] {
+                    while !matches!(events.next().await, Some(NetworkEvent::PeerConnected(_))) {}
+                }
+
+                client
+                    .open_channel("state", ChannelConfig::unreliable())
+                    .await
+                    .unwrap();
+                while !matches!(
+                    server_events.next().await,
+                    Some(NetworkEvent::ChannelOpened(_, channel)) if channel == "state"
+                ) {}
+
+                client.close();
+                while !matches!(
+                    server.send_message_on_channel("state", "ping!"),
+                    Err(NetworkError::UnknownChannel(_))
+                ) {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("closed channel was not forgotten in time");
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn sending_waits_for_buffer_to_drain_below_high_water_mark() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer_generator = || {
+                NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("backpressure-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap()
+            };
+            let mut server = peer_generator();
+            let mut client = peer_generator();
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                for events in [&mut server_events, &mut client_events] {
+                    while !matches!(events.next().await, Some(NetworkEvent::PeerConnected(_))) {}
+                }
+
+                server.set_high_water_mark(0);
+                server.send_message("first").unwrap();
+                assert!(matches!(
+                    server.send_message("second"),
+                    Err(NetworkError::WouldBlock)
+                ));
+                for i in 0..20 {
+                    server.send_message_async(&i.to_string()).await.unwrap();
+                }
+
+                let expected: Vec<String> = std::iter::once("first".to_string())
+                    .chain((0..20).map(|i| i.to_string()))
+                    .collect();
+                let mut received = Vec::new();
+                while received.len() < expected.len() {
+                    if let Some(NetworkEvent::Message(_, message)) = client_events.next().await {
+                        received.push(message);
+                    }
+                }
+                assert_eq!(received, expected);
+            })
+            .await
+            .expect("messages were not sent in time");
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+#[tokio::test]
+async fn stats_report_traffic_and_direct_connection() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+
+    LocalSet::new()
+        .run_until(async {
+            let peer_generator = || {
+                NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("stats-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap()
+            };
+            let mut server = peer_generator();
+            let mut client = peer_generator();
+            assert!(matches!(
+                server.stats().await,
+                Err(NetworkError::DataChannelNotOpen)
+            ));
+            let mut server_events = server.start_with_events().unwrap();
+            let mut client_events = client.start_with_events().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(30), async {
+                for events in [&mut server_events, &mut client_events] {
+                    while !matches!(events.next().await, Some(NetworkEvent::PeerConnected(_))) {}
+                }
+
+                server.send_message("ping").unwrap();
+                while !matches!(
+                    client_events.next().await,
+                    Some(NetworkEvent::Message(_, _))
+                ) {}
+
+                let stats = server.stats().await.unwrap();
+                assert!(matches!(
+                    stats.ice_connection_state,
+                    IceConnectionState::Connected | IceConnectionState::Completed
+                ));
+                assert_eq!(stats.local_candidate_type, Some(CandidateType::Host));
+                // connectivity checks of the peer may arrive before its candidates do
+                assert!(matches!(
+                    stats.remote_candidate_type,
+                    Some(CandidateType::Host | CandidateType::PeerReflexive)
+                ));
+                assert!(!stats.is_relayed());
+                assert!(stats
+                    .channels
+                    .iter()
+                    .any(|channel| channel.bytes_sent > 0 && channel.messages_sent > 0));
+
+                let stats = client.stats().await.unwrap();
+                assert!(stats
+                    .channels
+                    .iter()
+                    .any(|channel| channel.bytes_received > 0 && channel.messages_received > 0));
+            })
+            .await
+            .expect("stats were not reported in time");
+        })
+        .await;
+
+    signaling_server.shutdown().await;
+}
+
+/// Game whose state is the inputs of every frame simulated so far
+#[derive(Default)]
+struct InputLog {
+    inputs: Vec<[u8; 2]>,
+}
+
+impl RollbackGame for InputLog {
+    type Input = u8;
+    type State = usize;
+
+    fn save_state(&mut self) -> usize {
+        self.inputs.len()
+    }
+
+    fn load_state(&mut self, state: &usize) {
+        self.inputs.truncate(*state);
+    }
+
+    fn advance_frame(&mut self, inputs: &[u8; 2]) {
+        self.inputs.push(*inputs);
+    }
+}
+
+#[tokio::test]
+async fn rollback_sessions_agree_on_confirmed_frames() {
+    let signaling_server = SignalingServer::builder()
+        .bind(([127, 0, 0, 1], 0))
+        .spawn()
+        .unwrap();
+    let signaling_server_url = format!("ws://{}/signal", signaling_server.local_addr());
+    let received_messages = Rc::new(RefCell::new(Vec::new()));
+    let config = RollbackConfig::default();
+    let input = |player: usize, frame: Frame| ((frame / 5 + player as u32) % 4) as u8;
+
+    let games = LocalSet::new()
+        .run_until(async {
+            let mut sessions = Vec::new();
+            let mut network_managers = Vec::new();
+            for player in 0..2 {
+                let mut network_manager = NetworkManager::new(
+                    &signaling_server_url,
+                    SessionId::new("native-session-id".to_string()),
+                    ConnectionType::Local,
+                )
+                .unwrap();
+                let on_message = {
+                    let received_messages = received_messages.clone();
+                    move |message| received_messages.borrow_mut().push(message)
+                };
+                network_manager.start(|| {}, on_message).unwrap();
+                sessions.push(RollbackSession::<InputLog>::new(
+                    &network_manager,
+                    player,
+                    config,
+                ));
+                network_managers.push(network_manager);
+            }
+
+            let mut games = [InputLog::default(), InputLog::default()];
+            tokio::time::timeout(Duration::from_secs(30), async {
+                // frames may be confirmed before they're simulated, when the other peer is ahead
+                while sessions.iter().any(|session| {
+                    session.confirmed_frame().unwrap_or(0) < 100 || session.current_frame() < 100
+                }) {
+                    for (player, session) in sessions.iter().enumerate() {
+                        let frame = session.current_frame() + config.input_delay;
+                        session.advance_frame(&mut games[player], input(player, frame));
+                    }
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            })
+            .await
+            .expect("frames were not confirmed in time");
+
+            for network_manager in network_managers {
+                network_manager.close();
+            }
+            games
+        })
+        .await;
+
+    let expected: Vec<[u8; 2]> = (0..100)
+        .map(|frame| {
+            if frame < config.input_delay {
+                [0, 0]
+            } else {
+                [input(0, frame), input(1, frame)]
+            }
+        })
+        .collect();
+    for game in &games {
+        assert_eq!(game.inputs[..100], expected[..]);
+    }
+    assert!(received_messages.borrow().is_empty());
+    signaling_server.shutdown().await;
+}
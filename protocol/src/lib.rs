@@ -5,14 +5,18 @@ and [rusty-games-signaling-server](../rusty_games_signaling_server/index.html).
 
 #![deny(missing_docs)]
 
-use serde::{Deserialize, Serialize};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{Display, Formatter};
+use std::num::ParseIntError;
 use std::ops::Deref;
 use std::str::FromStr;
 
 pub mod many_to_many;
 pub mod one_to_many;
 pub mod one_to_one;
+pub mod rtc;
+pub mod signal;
 
 /// Unique identifier of signaling session that each user provides
 /// when communicating with the signaling server.
@@ -51,24 +55,45 @@ impl Display for SessionId {
 
 /// Unique identifier of each peer connected to signaling server
 /// useful when communicating in one-to-many and many-to-many topologies.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Hash)]
-pub struct UserId(usize);
+///
+/// It is an opaque 128-bit value. By default signaling server assigns random identifiers,
+/// so they can't be guessed and don't collide across server restarts or instances.
+/// On the wire it is serialized as a decimal string, because JSON numbers can't hold 128 bits
+/// in most implementations, but plain numbers are still accepted when deserializing.
+/// Legacy protocols of the old endpoints keep sending plain numbers,
+/// so signaling server assigns sequential identifiers to their users.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UserId(u128);
 
 impl UserId {
-    /// Wrap usize into a UserId struct
-    pub fn new(inner: usize) -> Self {
+    /// Wrap u128 into a UserId struct
+    pub fn new(inner: u128) -> Self {
         UserId(inner)
     }
 
     /// Acquire the underlying type
-    pub fn into_inner(self) -> usize {
+    pub fn into_inner(self) -> u128 {
         self.0
     }
 }
 
+impl From<u128> for UserId {
+    fn from(val: u128) -> Self {
+        UserId(val)
+    }
+}
+
 impl From<usize> for UserId {
     fn from(val: usize) -> Self {
-        UserId(val)
+        UserId(val as u128)
+    }
+}
+
+impl FromStr for UserId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(UserId)
     }
 }
 
@@ -79,13 +104,95 @@ impl Display for UserId {
 }
 
 impl Deref for UserId {
-    type Target = usize;
+    type Target = u128;
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
+impl Serialize for UserId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for UserId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UserIdVisitor;
+
+        impl<'de> Visitor<'de> for UserIdVisitor {
+            type Value = UserId;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                write!(f, "a user id as a decimal string or an unsigned integer")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(UserId(v as u128))
+            }
+
+            fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+                Ok(UserId(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(UserIdVisitor)
+    }
+}
+
+/// Serializes [UserId] as a plain number, the way legacy protocols always did,
+/// use with `#[serde(with = "crate::numeric_user_id")]`.
+pub(crate) mod numeric_user_id {
+    use super::UserId;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        user_id: &UserId,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u128(user_id.0)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<UserId, D::Error> {
+        UserId::deserialize(deserializer)
+    }
+}
+
 /// Unique identifier specifying which peer is host and will be creating an offer,
 /// and which will await it.
 pub type IsHost = bool;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn user_id_display_and_from_str_round_trip() {
+        for user_id in [UserId::new(0), UserId::new(42), UserId::new(u128::MAX)] {
+            assert_eq!(user_id.to_string().parse::<UserId>().unwrap(), user_id);
+        }
+    }
+
+    #[test]
+    fn user_id_serializes_as_string() {
+        let user_id = UserId::new(u128::MAX);
+        let serialized = serde_json::to_string(&user_id).unwrap();
+        assert_eq!(serialized, format!("\"{}\"", u128::MAX));
+        assert_eq!(
+            serde_json::from_str::<UserId>(&serialized).unwrap(),
+            user_id
+        );
+    }
+
+    #[test]
+    fn user_id_deserializes_from_number() {
+        assert_eq!(serde_json::from_str::<UserId>("7").unwrap(), UserId::new(7));
+    }
+}
@@ -1,6 +1,8 @@
 /*!
 Signaling messages exchanged between used by MiniServer, MiniClient and signaling server
 to facilitate communication in client-server topology.
+
+Legacy protocol of the `/one-to-many` endpoint, new peers should use [signal](crate::signal) instead.
 */
 
 use crate::{IsHost, SessionId, UserId};
@@ -15,16 +17,28 @@ pub enum SignalMessage {
     SessionJoin(SessionId, IsHost),
 
     /// Report back to the users that both of them are in session
-    SessionReady(SessionId, UserId),
+    SessionReady(SessionId, #[serde(with = "crate::numeric_user_id")] UserId),
 
     /// SDP Offer that gets passed to the other user without modifications
-    SdpOffer(SessionId, UserId, String),
+    SdpOffer(
+        SessionId,
+        #[serde(with = "crate::numeric_user_id")] UserId,
+        String,
+    ),
 
     /// SDP Answer that gets passed to the other user without modifications
-    SdpAnswer(SessionId, UserId, String),
+    SdpAnswer(
+        SessionId,
+        #[serde(with = "crate::numeric_user_id")] UserId,
+        String,
+    ),
 
     /// Proposed ICE Candidate of one user passed to the other user without modifications
-    IceCandidate(SessionId, UserId, String),
+    IceCandidate(
+        SessionId,
+        #[serde(with = "crate::numeric_user_id")] UserId,
+        String,
+    ),
 
     /// Generic error containing detailed information about the cause
     Error(SessionId, String),
@@ -1,7 +1,9 @@
 /*!
 Signaling messages exchanged between used by MiniServer, MiniClient and signaling server
 to facilitate communication in client-server topology.
- */
+
+Legacy protocol of the `/one-to-one` endpoint, new peers should use [signal](crate::signal) instead.
+*/
 
 use crate::{IsHost, SessionId};
 use serde::{Deserialize, Serialize};
@@ -0,0 +1,393 @@
+/*!
+WebRTC payloads that peers exchange through the signaling server while setting up a connection.
+
+Candidate attribute of an [IceCandidate] can be parsed into a [Candidate],
+e.g. to inspect, log or filter candidates before passing them on.
+
+```
+use rusty_games_protocol::rtc::{CandidateType, IceCandidate};
+
+let ice_candidate = IceCandidate {
+    candidate: "candidate:842163049 1 udp 1677729535 203.0.113.7 46154 typ srflx \
+        raddr 192.168.1.2 rport 46154 generation 0".to_string(),
+    sdp_mid: Some("0".to_string()),
+    sdp_m_line_index: Some(0),
+};
+let candidate = ice_candidate.parse().unwrap().unwrap();
+assert_eq!(candidate.candidate_type, CandidateType::ServerReflexive);
+assert_eq!(candidate.port, 46154);
+assert!(!candidate.is_private_host());
+```
+*/
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// ICE candidate gathered by one peer and passed to the other one,
+/// mirrors the fields of the browser's `RTCIceCandidateInit`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct IceCandidate {
+    /// Candidate attribute, e.g. `candidate:1 1 udp 2122260223 192.168.1.2 54321 typ host`
+    pub candidate: String,
+    /// Identification tag of the media stream the candidate is associated with
+    pub sdp_mid: Option<String>,
+    /// Index of the media description the candidate is associated with
+    pub sdp_m_line_index: Option<u16>,
+}
+
+impl IceCandidate {
+    /// Candidate with an empty candidate attribute,
+    /// telling the other peer that no more candidates will be sent
+    pub fn end_of_candidates() -> Self {
+        IceCandidate {
+            candidate: String::new(),
+            sdp_mid: None,
+            sdp_m_line_index: Some(0),
+        }
+    }
+
+    /// Whether this is the last candidate, see [IceCandidate::end_of_candidates]
+    pub fn is_end_of_candidates(&self) -> bool {
+        self.candidate.is_empty()
+    }
+
+    /// Parse the candidate attribute,
+    /// returns `None` for an empty one, that signals the end of candidates
+    pub fn parse(&self) -> Result<Option<Candidate>, ParseCandidateError> {
+        if self.candidate.is_empty() {
+            Ok(None)
+        } else {
+            self.candidate.parse().map(Some)
+        }
+    }
+}
+
+/// Session description created by the peer initiating the connection
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SdpOffer {
+    /// Session description in SDP format
+    pub sdp: String,
+}
+
+/// Session description created by the peer responding to an [SdpOffer]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SdpAnswer {
+    /// Session description in SDP format
+    pub sdp: String,
+}
+
+/// Transport protocol of a [Candidate]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum CandidateProtocol {
+    /// UDP
+    Udp,
+    /// TCP
+    Tcp,
+    /// Protocol unknown to this crate
+    Other(String),
+}
+
+/// Type of a [Candidate], describing how its address was obtained
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CandidateType {
+    /// Address of a local network interface
+    Host,
+    /// Public address discovered with a STUN server
+    ServerReflexive,
+    /// Address discovered from connectivity checks with the other peer
+    PeerReflexive,
+    /// Address of a TURN server relaying the traffic
+    Relay,
+}
+
+impl CandidateType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CandidateType::Host => "host",
+            CandidateType::ServerReflexive => "srflx",
+            CandidateType::PeerReflexive => "prflx",
+            CandidateType::Relay => "relay",
+        }
+    }
+}
+
+/// Parsed candidate attribute of an [IceCandidate], as described in RFC 8839
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Candidate {
+    /// Identifier shared by candidates of the same type, base and STUN or TURN server
+    pub foundation: String,
+    /// Component the candidate belongs to, `1` for RTP and data channels
+    pub component: u32,
+    /// Transport protocol
+    pub protocol: CandidateProtocol,
+    /// Priority of the candidate
+    pub priority: u32,
+    /// IP address, or an mDNS host name ending with `.local` when browsers hide the local address
+    pub address: String,
+    /// Port
+    pub port: u16,
+    /// Type of the candidate
+    pub candidate_type: CandidateType,
+    /// Base address of reflexive and relayed candidates
+    pub related_address: Option<String>,
+    /// Base port of reflexive and relayed candidates
+    pub related_port: Option<u16>,
+    /// Type of TCP candidates, `active`, `passive` or `so`
+    pub tcp_type: Option<String>,
+    /// Remaining attributes, like `generation` or `ufrag`, in the order they appeared
+    pub extensions: Vec<(String, String)>,
+}
+
+impl Candidate {
+    /// Address of the candidate, if it is an IP address rather than an mDNS host name
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.address.parse().ok()
+    }
+
+    /// Check whether this is a host candidate exposing a private, loopback or link-local address,
+    /// or one hidden behind an mDNS host name, which is only reachable within local network
+    pub fn is_private_host(&self) -> bool {
+        if self.candidate_type != CandidateType::Host {
+            return false;
+        }
+        match self.ip() {
+            Some(IpAddr::V4(ip)) => ip.is_private() || ip.is_loopback() || ip.is_link_local(),
+            // unique local (fc00::/7) and link-local (fe80::/10) addresses
+            Some(IpAddr::V6(ip)) => {
+                ip.is_loopback()
+                    || (ip.segments()[0] & 0xfe00) == 0xfc00
+                    || (ip.segments()[0] & 0xffc0) == 0xfe80
+            }
+            None => self.address.ends_with(".local"),
+        }
+    }
+}
+
+impl FromStr for Candidate {
+    type Err = ParseCandidateError;
+
+    fn from_str(candidate: &str) -> Result<Self, Self::Err> {
+        let candidate = candidate
+            .strip_prefix("a=")
+            .unwrap_or(candidate)
+            .strip_prefix("candidate:")
+            .ok_or(ParseCandidateError::MissingPrefix)?;
+        let mut fields = candidate.split_ascii_whitespace();
+        let mut next = |name| fields.next().ok_or(ParseCandidateError::MissingField(name));
+
+        let foundation = next("foundation")?.to_string();
+        let component = parse_field(next("component")?, "component")?;
+        let protocol = match next("protocol")?.to_ascii_lowercase().as_str() {
+            "udp" => CandidateProtocol::Udp,
+            "tcp" => CandidateProtocol::Tcp,
+            other => CandidateProtocol::Other(other.to_string()),
+        };
+        let priority = parse_field(next("priority")?, "priority")?;
+        let address = next("address")?.to_string();
+        let port = parse_field(next("port")?, "port")?;
+        if next("typ")? != "typ" {
+            return Err(ParseCandidateError::InvalidField("typ"));
+        }
+        let candidate_type = match next("type")? {
+            "host" => CandidateType::Host,
+            "srflx" => CandidateType::ServerReflexive,
+            "prflx" => CandidateType::PeerReflexive,
+            "relay" => CandidateType::Relay,
+            _ => return Err(ParseCandidateError::InvalidField("type")),
+        };
+
+        let mut related_address = None;
+        let mut related_port = None;
+        let mut tcp_type = None;
+        let mut extensions = Vec::new();
+        while let Ok(name) = next("extension") {
+            let value = next("extension value")?;
+            match name {
+                "raddr" => related_address = Some(value.to_string()),
+                "rport" => related_port = Some(parse_field(value, "rport")?),
+                "tcptype" => tcp_type = Some(value.to_string()),
+                _ => extensions.push((name.to_string(), value.to_string())),
+            }
+        }
+
+        Ok(Candidate {
+            foundation,
+            component,
+            protocol,
+            priority,
+            address,
+            port,
+            candidate_type,
+            related_address,
+            related_port,
+            tcp_type,
+            extensions,
+        })
+    }
+}
+
+fn parse_field<T: FromStr>(value: &str, name: &'static str) -> Result<T, ParseCandidateError> {
+    value
+        .parse()
+        .map_err(|_| ParseCandidateError::InvalidField(name))
+}
+
+impl Display for Candidate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let protocol = match &self.protocol {
+            CandidateProtocol::Udp => "udp",
+            CandidateProtocol::Tcp => "tcp",
+            CandidateProtocol::Other(protocol) => protocol,
+        };
+        write!(
+            f,
+            "candidate:{} {} {} {} {} {} typ {}",
+            self.foundation,
+            self.component,
+            protocol,
+            self.priority,
+            self.address,
+            self.port,
+            self.candidate_type.as_str()
+        )?;
+        if let Some(related_address) = &self.related_address {
+            write!(f, " raddr {}", related_address)?;
+        }
+        if let Some(related_port) = self.related_port {
+            write!(f, " rport {}", related_port)?;
+        }
+        if let Some(tcp_type) = &self.tcp_type {
+            write!(f, " tcptype {}", tcp_type)?;
+        }
+        for (name, value) in &self.extensions {
+            write!(f, " {} {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reason why a candidate attribute could not be parsed
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseCandidateError {
+    /// Attribute doesn't start with `candidate:`
+    MissingPrefix,
+    /// Attribute ended before the named field
+    MissingField(&'static str),
+    /// Named field has an invalid value
+    InvalidField(&'static str),
+}
+
+impl Display for ParseCandidateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseCandidateError::MissingPrefix => {
+                write!(f, "candidate must start with \"candidate:\"")
+            }
+            ParseCandidateError::MissingField(name) => write!(f, "candidate is missing {}", name),
+            ParseCandidateError::InvalidField(name) => write!(f, "candidate has invalid {}", name),
+        }
+    }
+}
+
+impl Error for ParseCandidateError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn end_of_candidates_has_no_candidate_to_parse() {
+        let end_of_candidates = IceCandidate::end_of_candidates();
+        assert!(end_of_candidates.is_end_of_candidates());
+        assert_eq!(end_of_candidates.parse().unwrap(), None);
+    }
+
+    #[test]
+    fn parses_host_candidate() {
+        let candidate: Candidate =
+            "candidate:3442447574 1 udp 2122260223 192.168.1.2 54321 typ host generation 0 ufrag EsAw network-id 1"
+                .parse()
+                .unwrap();
+        assert_eq!(candidate.foundation, "3442447574");
+        assert_eq!(candidate.component, 1);
+        assert_eq!(candidate.protocol, CandidateProtocol::Udp);
+        assert_eq!(candidate.priority, 2122260223);
+        assert_eq!(candidate.address, "192.168.1.2");
+        assert_eq!(candidate.port, 54321);
+        assert_eq!(candidate.candidate_type, CandidateType::Host);
+        assert_eq!(candidate.related_address, None);
+        assert_eq!(
+            candidate.extensions,
+            vec![
+                ("generation".to_string(), "0".to_string()),
+                ("ufrag".to_string(), "EsAw".to_string()),
+                ("network-id".to_string(), "1".to_string()),
+            ]
+        );
+        assert!(candidate.is_private_host());
+    }
+
+    #[test]
+    fn parses_relay_tcp_candidate() {
+        let candidate: Candidate =
+            "candidate:1 1 TCP 8331263 203.0.113.9 3478 typ relay raddr 198.51.100.4 rport 9 tcptype passive"
+                .parse()
+                .unwrap();
+        assert_eq!(candidate.protocol, CandidateProtocol::Tcp);
+        assert_eq!(candidate.candidate_type, CandidateType::Relay);
+        assert_eq!(candidate.related_address.as_deref(), Some("198.51.100.4"));
+        assert_eq!(candidate.related_port, Some(9));
+        assert_eq!(candidate.tcp_type.as_deref(), Some("passive"));
+        assert!(!candidate.is_private_host());
+    }
+
+    #[test]
+    fn mdns_host_candidate_is_private() {
+        let candidate: Candidate =
+            "candidate:0 1 UDP 2122252543 0f4c6c4b-7ea4-4a3c-9bf4-3b1e8ab1e5a6.local 60155 typ host"
+                .parse()
+                .unwrap();
+        assert_eq!(candidate.ip(), None);
+        assert!(candidate.is_private_host());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let attribute = "candidate:1 1 tcp 8331263 203.0.113.9 3478 typ relay raddr 198.51.100.4 rport 9 tcptype passive generation 0";
+        let candidate: Candidate = attribute.parse().unwrap();
+        assert_eq!(candidate.to_string(), attribute);
+    }
+
+    #[test]
+    fn empty_candidate_ends_candidates() {
+        let ice_candidate = IceCandidate {
+            candidate: String::new(),
+            sdp_mid: Some("0".to_string()),
+            sdp_m_line_index: None,
+        };
+        assert_eq!(ice_candidate.parse(), Ok(None));
+    }
+
+    #[test]
+    fn rejects_malformed_candidates() {
+        assert_eq!(
+            "1 1 udp 1 192.168.1.2 1 typ host".parse::<Candidate>(),
+            Err(ParseCandidateError::MissingPrefix)
+        );
+        assert_eq!(
+            "candidate:1 1 udp 1 192.168.1.2".parse::<Candidate>(),
+            Err(ParseCandidateError::MissingField("port"))
+        );
+        assert_eq!(
+            "candidate:1 1 udp 1 192.168.1.2 99999 typ host".parse::<Candidate>(),
+            Err(ParseCandidateError::InvalidField("port"))
+        );
+        assert_eq!(
+            "candidate:1 1 udp 1 192.168.1.2 1 typ nat".parse::<Candidate>(),
+            Err(ParseCandidateError::InvalidField("type"))
+        );
+    }
+}
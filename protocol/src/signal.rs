@@ -0,0 +1,401 @@
+/*!
+Versioned signaling messages exchanged on the unified `/signal` endpoint of the signaling server,
+shared by all network topologies.
+
+Every connection starts with a [SignalMessage::Hello] stating protocol version, topology and
+capabilities of the peer, to which server responds with [SignalMessage::Welcome] or an [SignalMessage::Error].
+Afterwards the same set of messages is used to set up sessions and WebRTC connections in every topology.
+
+Hello, Welcome and errors sent in response to a Hello are always JSON in text frames,
+the [Encoding] of all following messages depends on negotiated capabilities.
+
+Per-topology modules, like [one_to_one](crate::one_to_one), describe the legacy protocols
+that are still spoken on the old, dedicated endpoints.
+*/
+
+use crate::rtc::{IceCandidate, SdpAnswer, SdpOffer};
+use crate::{IsHost, SessionId, UserId};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Version of the protocol described by [SignalMessage]
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Versions of the protocol that peers built with this crate can talk to.
+/// Legacy protocols of the per-topology endpoints are considered to be version `0`.
+pub const SUPPORTED_PROTOCOL_VERSIONS: VersionRange = VersionRange {
+    min: 1,
+    max: PROTOCOL_VERSION,
+};
+
+/// Inclusive range of protocol versions
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct VersionRange {
+    /// Oldest supported version
+    pub min: u32,
+    /// Newest supported version
+    pub max: u32,
+}
+
+impl VersionRange {
+    /// Check whether given version falls within the range
+    pub fn contains(&self, version: u32) -> bool {
+        self.min <= version && version <= self.max
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..={}", self.min, self.max)
+    }
+}
+
+/// Network topology that a peer wants to take part in
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Topology {
+    /// Two equal peers, see [one_to_one](crate::one_to_one)
+    OneToOne,
+    /// Single host with many clients, see [one_to_many](crate::one_to_many)
+    OneToMany,
+    /// Every peer connected with every other one, see [many_to_many](crate::many_to_many)
+    ManyToMany,
+}
+
+/// Optional features a peer or signaling server supports,
+/// exchanged in [SignalMessage::Hello] and [SignalMessage::Welcome].
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Capability {
+    /// ICE candidates are sent one by one with [SignalMessage::IceCandidate] as they are gathered
+    TrickleIce,
+    /// Messages following [SignalMessage::Welcome] are encoded with [Encoding::MessagePack]
+    MessagePack,
+    /// Capability introduced in a newer version of the protocol, unknown to this one
+    #[serde(other)]
+    Unknown,
+}
+
+/// Wire encoding of [SignalMessage]s
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum Encoding {
+    /// JSON sent in websocket text frames
+    #[default]
+    Json,
+    /// MessagePack, with structs serialized as maps, sent in websocket binary frames
+    MessagePack,
+}
+
+impl Encoding {
+    /// Encoding used after the handshake, given capabilities from [SignalMessage::Welcome]
+    pub fn negotiated(capabilities: &[Capability]) -> Self {
+        if capabilities.contains(&Capability::MessagePack) {
+            Encoding::MessagePack
+        } else {
+            Encoding::Json
+        }
+    }
+}
+
+/// Machine readable category of [SignalMessage::Error]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// Protocol version from [SignalMessage::Hello] is not supported by the signaling server
+    UnsupportedVersion {
+        /// Versions that signaling server supports
+        supported: VersionRange,
+    },
+    /// Connection didn't start with a [SignalMessage::Hello]
+    HandshakeRequired,
+    /// Message could not be deserialized
+    InvalidMessage,
+    /// Error without a more specific category, or one introduced in a newer version of the protocol
+    #[serde(other)]
+    Other,
+}
+
+/// Enum consisting of two main categories are messages used to setup signaling session
+/// and messages used to setup WebRTC connection afterwards.
+/// Most of the include [SessionId] and [UserId] to uniquely identify each peer.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SignalMessage {
+    /// First message sent by a peer, selecting protocol version and topology for the connection
+    Hello {
+        /// Version of the protocol the peer speaks, should be [PROTOCOL_VERSION]
+        protocol_version: u32,
+        /// Topology that all subsequent messages relate to
+        topology: Topology,
+        /// Features supported by the peer
+        capabilities: Vec<Capability>,
+    },
+
+    /// Signaling server accepted the [SignalMessage::Hello]
+    Welcome {
+        /// Version of the protocol that will be used for the rest of the connection
+        protocol_version: u32,
+        /// All versions supported by signaling server
+        supported_versions: VersionRange,
+        /// Capabilities supported by both the peer and signaling server
+        capabilities: Vec<Capability>,
+    },
+
+    /// Either client or server connecting to signaling session,
+    /// [IsHost] is only meaningful in one-to-many topology
+    SessionJoin(SessionId, IsHost),
+
+    /// Report back to the user that session with the other peer is ready,
+    /// [IsHost] specifies whether the user should create an offer
+    SessionReady(SessionId, UserId, IsHost),
+
+    /// SDP Offer that gets passed to the other user without modifications
+    SdpOffer(SessionId, UserId, SdpOffer),
+
+    /// SDP Answer that gets passed to the other user without modifications
+    SdpAnswer(SessionId, UserId, SdpAnswer),
+
+    /// Proposed ICE Candidate of one user passed to the other user without modifications
+    IceCandidate(SessionId, UserId, IceCandidate),
+
+    /// Generic error containing its category and detailed information about the cause,
+    /// not every error is related to a session
+    Error(Option<SessionId>, ErrorCode, String),
+
+    /// Peer leaves the session for good, so that signaling server forgets about it
+    /// before the websocket is closed
+    SessionLeave(SessionId),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unknown_capability_deserializes() {
+        let capabilities =
+            serde_json::from_str::<Vec<Capability>>(r#"["TrickleIce", "FromTheFuture"]"#).unwrap();
+        assert_eq!(
+            capabilities,
+            vec![Capability::TrickleIce, Capability::Unknown]
+        );
+    }
+
+    #[test]
+    fn hello_round_trips() {
+        let hello = SignalMessage::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            topology: Topology::ManyToMany,
+            capabilities: vec![Capability::TrickleIce],
+        };
+        let serialized = serde_json::to_string(&hello).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SignalMessage>(&serialized).unwrap(),
+            hello
+        );
+    }
+
+    #[test]
+    fn unknown_capability_deserializes_from_message_pack() {
+        let capabilities = rmp_serde::to_vec_named(&["TrickleIce", "FromTheFuture"]).unwrap();
+        assert_eq!(
+            rmp_serde::from_slice::<Vec<Capability>>(&capabilities).unwrap(),
+            vec![Capability::TrickleIce, Capability::Unknown]
+        );
+    }
+
+    #[test]
+    fn message_pack_is_negotiated_only_when_agreed_on() {
+        assert_eq!(
+            Encoding::negotiated(&[Capability::TrickleIce]),
+            Encoding::Json
+        );
+        assert_eq!(
+            Encoding::negotiated(&[Capability::TrickleIce, Capability::MessagePack]),
+            Encoding::MessagePack
+        );
+    }
+
+    #[test]
+    fn unknown_error_code_deserializes() {
+        let code = serde_json::from_str::<ErrorCode>(r#""FromTheFuture""#).unwrap();
+        assert_eq!(code, ErrorCode::Other);
+    }
+
+    #[test]
+    fn version_range_contains_its_bounds() {
+        let range = VersionRange { min: 2, max: 4 };
+        assert!(!range.contains(1));
+        assert!(range.contains(2));
+        assert!(range.contains(4));
+        assert!(!range.contains(5));
+        assert!(SUPPORTED_PROTOCOL_VERSIONS.contains(PROTOCOL_VERSION));
+    }
+
+    // wire format of every released protocol version must stay the same,
+    // otherwise peers and signaling servers built from different versions stop understanding each other
+
+    #[test]
+    fn version_0_one_to_one_wire_format() {
+        use crate::one_to_one::SignalMessage;
+        let session_id = SessionId::new("s".to_string());
+        let messages = [
+            (
+                SignalMessage::SessionJoin(session_id.clone()),
+                r#"{"SessionJoin":"s"}"#,
+            ),
+            (
+                SignalMessage::SessionReady(session_id.clone(), true),
+                r#"{"SessionReady":["s",true]}"#,
+            ),
+            (
+                SignalMessage::SdpOffer(session_id.clone(), "o".to_string()),
+                r#"{"SdpOffer":["s","o"]}"#,
+            ),
+            (
+                SignalMessage::SdpAnswer(session_id.clone(), "a".to_string()),
+                r#"{"SdpAnswer":["s","a"]}"#,
+            ),
+            (
+                SignalMessage::IceCandidate(session_id.clone(), "c".to_string()),
+                r#"{"IceCandidate":["s","c"]}"#,
+            ),
+            (
+                SignalMessage::Error(session_id, "e".to_string()),
+                r#"{"Error":["s","e"]}"#,
+            ),
+        ];
+        for (message, expected) in messages {
+            assert_eq!(serde_json::to_string(&message).unwrap(), expected);
+            serde_json::from_str::<SignalMessage>(expected).unwrap();
+        }
+    }
+
+    #[test]
+    fn version_0_one_to_many_wire_format() {
+        use crate::one_to_many::SignalMessage;
+        let session_id = SessionId::new("s".to_string());
+        let user_id = UserId::new(7);
+        let messages = [
+            (
+                SignalMessage::SessionJoin(session_id.clone(), true),
+                r#"{"SessionJoin":["s",true]}"#,
+            ),
+            (
+                SignalMessage::SessionReady(session_id.clone(), user_id),
+                r#"{"SessionReady":["s",7]}"#,
+            ),
+            (
+                SignalMessage::SdpOffer(session_id.clone(), user_id, "o".to_string()),
+                r#"{"SdpOffer":["s",7,"o"]}"#,
+            ),
+            (
+                SignalMessage::SdpAnswer(session_id.clone(), user_id, "a".to_string()),
+                r#"{"SdpAnswer":["s",7,"a"]}"#,
+            ),
+            (
+                SignalMessage::IceCandidate(session_id.clone(), user_id, "c".to_string()),
+                r#"{"IceCandidate":["s",7,"c"]}"#,
+            ),
+            (
+                SignalMessage::Error(session_id, "e".to_string()),
+                r#"{"Error":["s","e"]}"#,
+            ),
+        ];
+        for (message, expected) in messages {
+            assert_eq!(serde_json::to_string(&message).unwrap(), expected);
+            serde_json::from_str::<SignalMessage>(expected).unwrap();
+        }
+    }
+
+    #[test]
+    fn version_1_wire_format() {
+        let session_id = SessionId::new("s".to_string());
+        let user_id = UserId::new(7);
+        let messages = [
+            (
+                SignalMessage::Hello {
+                    protocol_version: 1,
+                    topology: Topology::OneToMany,
+                    capabilities: vec![Capability::TrickleIce, Capability::MessagePack],
+                },
+                r#"{"Hello":{"protocol_version":1,"topology":"OneToMany","capabilities":["TrickleIce","MessagePack"]}}"#,
+            ),
+            (
+                SignalMessage::Welcome {
+                    protocol_version: 1,
+                    supported_versions: VersionRange { min: 1, max: 1 },
+                    capabilities: vec![],
+                },
+                r#"{"Welcome":{"protocol_version":1,"supported_versions":{"min":1,"max":1},"capabilities":[]}}"#,
+            ),
+            (
+                SignalMessage::SessionJoin(session_id.clone(), false),
+                r#"{"SessionJoin":["s",false]}"#,
+            ),
+            (
+                SignalMessage::SessionReady(session_id.clone(), user_id, true),
+                r#"{"SessionReady":["s","7",true]}"#,
+            ),
+            (
+                SignalMessage::SessionLeave(session_id.clone()),
+                r#"{"SessionLeave":"s"}"#,
+            ),
+            (
+                SignalMessage::SdpOffer(
+                    session_id.clone(),
+                    user_id,
+                    SdpOffer {
+                        sdp: "o".to_string(),
+                    },
+                ),
+                r#"{"SdpOffer":["s","7",{"sdp":"o"}]}"#,
+            ),
+            (
+                SignalMessage::SdpAnswer(
+                    session_id.clone(),
+                    user_id,
+                    SdpAnswer {
+                        sdp: "a".to_string(),
+                    },
+                ),
+                r#"{"SdpAnswer":["s","7",{"sdp":"a"}]}"#,
+            ),
+            (
+                SignalMessage::IceCandidate(
+                    session_id.clone(),
+                    user_id,
+                    IceCandidate {
+                        candidate: "c".to_string(),
+                        sdp_mid: Some("0".to_string()),
+                        sdp_m_line_index: None,
+                    },
+                ),
+                r#"{"IceCandidate":["s","7",{"candidate":"c","sdp_mid":"0","sdp_m_line_index":null}]}"#,
+            ),
+            (
+                SignalMessage::Error(
+                    None,
+                    ErrorCode::UnsupportedVersion {
+                        supported: VersionRange { min: 1, max: 1 },
+                    },
+                    "e".to_string(),
+                ),
+                r#"{"Error":[null,{"UnsupportedVersion":{"supported":{"min":1,"max":1}}},"e"]}"#,
+            ),
+            (
+                SignalMessage::Error(Some(session_id), ErrorCode::InvalidMessage, "e".to_string()),
+                r#"{"Error":["s","InvalidMessage","e"]}"#,
+            ),
+        ];
+        for (message, expected) in messages {
+            assert_eq!(serde_json::to_string(&message).unwrap(), expected);
+            assert_eq!(
+                serde_json::from_str::<SignalMessage>(expected).unwrap(),
+                message
+            );
+
+            let message_pack = rmp_serde::to_vec_named(&message).unwrap();
+            assert_eq!(
+                rmp_serde::from_slice::<SignalMessage>(&message_pack).unwrap(),
+                message
+            );
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use futures_util::stream::SplitStream;
+use futures_util::{SinkExt, StreamExt, TryFutureExt};
+use log::{debug, error, info, warn};
+use tokio::sync::{mpsc, RwLock};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use warp::ws::{Message, WebSocket};
+
+use rusty_games_protocol::signal::{Encoding, ErrorCode, SignalMessage};
+use rusty_games_protocol::UserId;
+
+use crate::user_ids::UserIdGenerator;
+use crate::{legacy, validation};
+
+/// Wire protocol spoken by a connected user
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Dialect {
+    /// Versioned protocol of the `/signal` endpoint
+    Signal,
+    /// Legacy protocol of the `/one-to-one` endpoint
+    OneToOne,
+    /// Legacy protocol of the `/one-to-many` and `/many-to-many` endpoints
+    OneToMany,
+}
+
+/// Reason why a received message was rejected
+#[derive(Debug)]
+pub enum DecodeError {
+    Json(serde_json::Error),
+    MessagePack(rmp_serde::decode::Error),
+    /// Binary frame received in a legacy dialect, that only knows JSON
+    UnexpectedBinary,
+    /// Message was deserialized, but its content is invalid
+    Invalid(String),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Json(error) => write!(f, "invalid JSON message: {}", error),
+            DecodeError::MessagePack(error) => write!(f, "invalid MessagePack message: {}", error),
+            DecodeError::UnexpectedBinary => write!(f, "binary messages are not supported"),
+            DecodeError::Invalid(reason) => write!(f, "invalid message: {}", reason),
+        }
+    }
+}
+
+impl Dialect {
+    /// Text frames are always JSON, binary frames are MessagePack,
+    /// no matter which encoding was negotiated for messages sent to the user
+    fn decode(self, sender_id: UserId, message: &Message) -> Result<SignalMessage, DecodeError> {
+        let message = if let Ok(text) = message.to_str() {
+            match self {
+                Dialect::Signal => serde_json::from_str(text),
+                Dialect::OneToOne => serde_json::from_str(text)
+                    .and_then(|message| legacy::from_one_to_one(sender_id, message)),
+                Dialect::OneToMany => serde_json::from_str(text).and_then(legacy::from_one_to_many),
+            }
+            .map_err(DecodeError::Json)?
+        } else if self == Dialect::Signal {
+            rmp_serde::from_slice(message.as_bytes()).map_err(DecodeError::MessagePack)?
+        } else {
+            return Err(DecodeError::UnexpectedBinary);
+        };
+        validation::validate(&message).map_err(DecodeError::Invalid)?;
+        Ok(message)
+    }
+
+    fn encode(self, message: SignalMessage, encoding: Encoding) -> Option<Message> {
+        match (self, encoding) {
+            (Dialect::Signal, Encoding::Json) => {
+                Some(Message::text(serde_json::to_string(&message).unwrap()))
+            }
+            (Dialect::Signal, Encoding::MessagePack) => {
+                Some(Message::binary(rmp_serde::to_vec_named(&message).unwrap()))
+            }
+            (Dialect::OneToOne, _) => legacy::to_one_to_one(message)
+                .map(|message| Message::text(serde_json::to_string(&message).unwrap())),
+            (Dialect::OneToMany, _) => legacy::to_one_to_many(message)
+                .map(|message| Message::text(serde_json::to_string(&message).unwrap())),
+        }
+    }
+}
+
+/// Handle used to send messages to a connected user in the dialect and encoding they speak
+#[derive(Debug, Clone)]
+pub struct Connection {
+    tx: mpsc::UnboundedSender<Message>,
+    dialect: Dialect,
+    encoding: Encoding,
+}
+
+impl Connection {
+    pub fn send(&self, message: SignalMessage) {
+        match self.dialect.encode(message, self.encoding) {
+            Some(message) => {
+                if self.tx.send(message).is_err() {
+                    warn!("tried to send a message to an already disconnected user");
+                }
+            }
+            None => debug!("message has no equivalent in {:?} dialect", self.dialect),
+        }
+    }
+
+    /// Switch encoding of all messages sent from now on
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// Ask the user to close the websocket, after all messages queued so far are sent
+    pub fn close(&self) {
+        let _ = self.tx.send(Message::close());
+    }
+}
+
+pub type Connections = Arc<RwLock<HashMap<UserId, Connection>>>;
+
+/// Assign an id to a newly connected user and register a task forwarding messages to them.
+/// Returns the receiving half of the websocket that should be read with [next_message].
+pub(crate) async fn register(
+    ws: WebSocket,
+    dialect: Dialect,
+    connections: &Connections,
+    user_ids: &UserIdGenerator,
+) -> (UserId, SplitStream<WebSocket>) {
+    let user_id = match dialect {
+        Dialect::Signal => user_ids.next_id(),
+        Dialect::OneToOne | Dialect::OneToMany => user_ids.next_legacy_id(),
+    };
+    info!("new user connected: {:?}", user_id);
+
+    let (mut user_ws_tx, user_ws_rx) = ws.split();
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut rx = UnboundedReceiverStream::new(rx);
+
+    tokio::task::spawn(async move {
+        while let Some(message) = rx.next().await {
+            user_ws_tx
+                .send(message)
+                .unwrap_or_else(|e| eprintln!("websocket send error: {}", e))
+                .await;
+        }
+    });
+
+    connections.write().await.insert(
+        user_id,
+        Connection {
+            tx,
+            dialect,
+            encoding: Encoding::Json,
+        },
+    );
+
+    (user_id, user_ws_rx)
+}
+
+/// Wait for the next message that can be understood, returns `None` once the user disconnects.
+/// Messages that can't be decoded are reported back to the user with [ErrorCode::InvalidMessage].
+pub(crate) async fn next_message(
+    user_id: UserId,
+    user_ws_rx: &mut SplitStream<WebSocket>,
+    dialect: Dialect,
+    connections: &Connections,
+) -> Option<SignalMessage> {
+    while let Some(result) = user_ws_rx.next().await {
+        let msg = match result {
+            Ok(msg) => msg,
+            Err(e) => {
+                eprintln!("websocket error (id={:?}): {}", user_id, e);
+                return None;
+            }
+        };
+
+        if msg.is_text() || msg.is_binary() {
+            match dialect.decode(user_id, &msg) {
+                Ok(message) => {
+                    info!("message received from user {:?}: {:?}", user_id, message);
+                    return Some(message);
+                }
+                Err(error) => {
+                    error!("An error occurred: {:?}", error);
+                    if let Some(user_tx) = connections.read().await.get(&user_id) {
+                        user_tx.send(SignalMessage::Error(
+                            None,
+                            ErrorCode::InvalidMessage,
+                            error.to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,118 @@
+//! Adapters translating legacy, per-topology protocols of the old endpoints
+//! to and from the versioned [SignalMessage] handled by the server.
+
+use rusty_games_protocol::rtc::{SdpAnswer, SdpOffer};
+use rusty_games_protocol::signal::{ErrorCode, SignalMessage};
+use rusty_games_protocol::{one_to_many, one_to_one, SessionId, UserId};
+
+// legacy protocols pass SDP as plain strings and ICE candidates as JSON serialized strings
+
+pub(crate) fn from_one_to_one(
+    sender_id: UserId,
+    message: one_to_one::SignalMessage,
+) -> serde_json::Result<SignalMessage> {
+    // legacy one-to-one peers don't know each other's ids, which is fine,
+    // because one-to-one sessions route messages to the other member of the session anyway
+    Ok(match message {
+        one_to_one::SignalMessage::SessionJoin(session_id) => {
+            SignalMessage::SessionJoin(session_id, false)
+        }
+        one_to_one::SignalMessage::SessionReady(session_id, is_host) => {
+            SignalMessage::SessionReady(session_id, sender_id, is_host)
+        }
+        one_to_one::SignalMessage::SdpOffer(session_id, sdp) => {
+            SignalMessage::SdpOffer(session_id, sender_id, SdpOffer { sdp })
+        }
+        one_to_one::SignalMessage::SdpAnswer(session_id, sdp) => {
+            SignalMessage::SdpAnswer(session_id, sender_id, SdpAnswer { sdp })
+        }
+        one_to_one::SignalMessage::IceCandidate(session_id, candidate) => {
+            SignalMessage::IceCandidate(session_id, sender_id, serde_json::from_str(&candidate)?)
+        }
+        one_to_one::SignalMessage::Error(session_id, error) => {
+            SignalMessage::Error(Some(session_id), ErrorCode::Other, error)
+        }
+    })
+}
+
+pub(crate) fn to_one_to_one(message: SignalMessage) -> Option<one_to_one::SignalMessage> {
+    match message {
+        SignalMessage::SessionReady(session_id, _, is_host) => {
+            Some(one_to_one::SignalMessage::SessionReady(session_id, is_host))
+        }
+        SignalMessage::SdpOffer(session_id, _, offer) => {
+            Some(one_to_one::SignalMessage::SdpOffer(session_id, offer.sdp))
+        }
+        SignalMessage::SdpAnswer(session_id, _, answer) => {
+            Some(one_to_one::SignalMessage::SdpAnswer(session_id, answer.sdp))
+        }
+        SignalMessage::IceCandidate(session_id, _, candidate) => {
+            Some(one_to_one::SignalMessage::IceCandidate(
+                session_id,
+                serde_json::to_string(&candidate).unwrap(),
+            ))
+        }
+        SignalMessage::Error(session_id, _, error) => Some(one_to_one::SignalMessage::Error(
+            session_id.unwrap_or_else(|| SessionId::new(String::new())),
+            error,
+        )),
+        SignalMessage::Hello { .. }
+        | SignalMessage::Welcome { .. }
+        | SignalMessage::SessionJoin(..)
+        | SignalMessage::SessionLeave(..) => None,
+    }
+}
+
+pub(crate) fn from_one_to_many(
+    message: one_to_many::SignalMessage,
+) -> serde_json::Result<SignalMessage> {
+    Ok(match message {
+        one_to_many::SignalMessage::SessionJoin(session_id, is_host) => {
+            SignalMessage::SessionJoin(session_id, is_host)
+        }
+        one_to_many::SignalMessage::SessionReady(session_id, user_id) => {
+            SignalMessage::SessionReady(session_id, user_id, true)
+        }
+        one_to_many::SignalMessage::SdpOffer(session_id, user_id, sdp) => {
+            SignalMessage::SdpOffer(session_id, user_id, SdpOffer { sdp })
+        }
+        one_to_many::SignalMessage::SdpAnswer(session_id, user_id, sdp) => {
+            SignalMessage::SdpAnswer(session_id, user_id, SdpAnswer { sdp })
+        }
+        one_to_many::SignalMessage::IceCandidate(session_id, user_id, candidate) => {
+            SignalMessage::IceCandidate(session_id, user_id, serde_json::from_str(&candidate)?)
+        }
+        one_to_many::SignalMessage::Error(session_id, error) => {
+            SignalMessage::Error(Some(session_id), ErrorCode::Other, error)
+        }
+    })
+}
+
+pub(crate) fn to_one_to_many(message: SignalMessage) -> Option<one_to_many::SignalMessage> {
+    match message {
+        SignalMessage::SessionReady(session_id, user_id, _) => Some(
+            one_to_many::SignalMessage::SessionReady(session_id, user_id),
+        ),
+        SignalMessage::SdpOffer(session_id, user_id, offer) => Some(
+            one_to_many::SignalMessage::SdpOffer(session_id, user_id, offer.sdp),
+        ),
+        SignalMessage::SdpAnswer(session_id, user_id, answer) => Some(
+            one_to_many::SignalMessage::SdpAnswer(session_id, user_id, answer.sdp),
+        ),
+        SignalMessage::IceCandidate(session_id, user_id, candidate) => {
+            Some(one_to_many::SignalMessage::IceCandidate(
+                session_id,
+                user_id,
+                serde_json::to_string(&candidate).unwrap(),
+            ))
+        }
+        SignalMessage::Error(session_id, _, error) => Some(one_to_many::SignalMessage::Error(
+            session_id.unwrap_or_else(|| SessionId::new(String::new())),
+            error,
+        )),
+        SignalMessage::Hello { .. }
+        | SignalMessage::Welcome { .. }
+        | SignalMessage::SessionJoin(..)
+        | SignalMessage::SessionLeave(..) => None,
+    }
+}
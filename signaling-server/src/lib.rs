@@ -1,3 +1,9 @@
+pub mod connection;
+mod legacy;
 pub mod many_to_many;
 pub mod one_to_many;
 pub mod one_to_one;
+pub mod server;
+pub mod signal;
+pub mod user_ids;
+mod validation;
@@ -1,138 +1,123 @@
 use std::collections::{HashMap, HashSet};
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use futures_util::{SinkExt, StreamExt, TryFutureExt};
-use log::{error, info, warn};
-use tokio::sync::{mpsc, RwLock};
-use tokio_stream::wrappers::UnboundedReceiverStream;
-use warp::ws::{Message, WebSocket};
+use log::warn;
+use tokio::sync::RwLock;
+use warp::ws::WebSocket;
 
-use rusty_games_protocol::one_to_many::SignalMessage;
+use rusty_games_protocol::signal::SignalMessage;
 use rusty_games_protocol::{SessionId, UserId};
 
+use crate::connection::{self, Dialect};
+use crate::user_ids::UserIdGenerator;
+
+pub use crate::connection::Connections;
+
 #[derive(Default, Debug)]
 pub struct Session {
     pub users: HashSet<UserId>,
 }
 
-pub type Connections = Arc<RwLock<HashMap<UserId, mpsc::UnboundedSender<Message>>>>;
 pub type Sessions = Arc<RwLock<HashMap<SessionId, Session>>>;
 
-static NEXT_USER_ID: AtomicUsize = AtomicUsize::new(1);
-
-pub async fn user_connected(ws: WebSocket, connections: Connections, sessions: Sessions) {
-    let user_id = UserId::new(NEXT_USER_ID.fetch_add(1, Ordering::Relaxed));
-    info!("new user connected: {:?}", user_id);
-
-    let (mut user_ws_tx, mut user_ws_rx) = ws.split();
-
-    let (tx, rx) = mpsc::unbounded_channel();
-    let mut rx = UnboundedReceiverStream::new(rx);
-
-    tokio::task::spawn(async move {
-        while let Some(message) = rx.next().await {
-            user_ws_tx
-                .send(message)
-                .unwrap_or_else(|e| eprintln!("websocket send error: {}", e))
-                .await;
-        }
-    });
-
-    connections.write().await.insert(user_id, tx);
-
-    while let Some(result) = user_ws_rx.next().await {
-        let msg = match result {
-            Ok(msg) => msg,
-            Err(e) => {
-                eprintln!("websocket error (id={:?}): {}", user_id, e);
-                break;
-            }
-        };
+/// Entry point of the legacy `/many-to-many` endpoint
+pub async fn user_connected(
+    ws: WebSocket,
+    connections: Connections,
+    sessions: Sessions,
+    user_ids: UserIdGenerator,
+) {
+    let (user_id, mut user_ws_rx) =
+        connection::register(ws, Dialect::OneToMany, &connections, &user_ids).await;
 
-        user_message(user_id, msg, &connections, &sessions).await;
+    while let Some(message) =
+        connection::next_message(user_id, &mut user_ws_rx, Dialect::OneToMany, &connections).await
+    {
+        user_message(user_id, message, &connections, &sessions).await;
     }
 
     eprintln!("user disconnected: {:?}", user_id);
     user_disconnected(user_id, &connections, &sessions).await;
 }
 
-async fn user_message(
+pub(crate) async fn user_message(
     sender_id: UserId,
-    msg: Message,
+    request: SignalMessage,
     connections: &Connections,
     sessions: &Sessions,
 ) {
-    if let Ok(msg) = msg.to_str() {
-        match serde_json::from_str::<SignalMessage>(msg) {
-            Ok(request) => {
-                info!("message received from user {:?}: {:?}", sender_id, request);
-                match request {
-                    SignalMessage::SessionJoin(session_id, _) => {
-                        let mut sessions_writer = sessions.write().await;
-                        let session = sessions_writer
-                            .entry(session_id.clone())
-                            .or_insert_with(Session::default);
-                        let connections_reader = connections.read().await;
-
-                        // start connections with all already present users
-                        for client_id in &session.users {
-                            {
-                                let host_tx = connections_reader
-                                    .get(&sender_id)
-                                    .expect("host not in connections");
-                                let host_response =
-                                    SignalMessage::SessionReady(session_id.clone(), *client_id);
-                                let host_response = serde_json::to_string(&host_response).unwrap();
-                                host_tx
-                                    .send(Message::text(&host_response))
-                                    .expect("failed to send SessionReady message to host");
-                            }
-                        }
-                        session.users.insert(sender_id);
-                    }
-                    // pass offer to the other user in session without changing anything
-                    SignalMessage::SdpOffer(session_id, recipient_id, offer) => {
-                        let response = SignalMessage::SdpOffer(session_id, sender_id, offer);
-                        let response = serde_json::to_string(&response).unwrap();
-                        let connections_reader = connections.read().await;
-                        if let Some(recipient_tx) = connections_reader.get(&recipient_id) {
-                            recipient_tx.send(Message::text(response)).unwrap();
-                        } else {
-                            warn!("tried to send offer to non existing user");
-                        }
-                    }
-                    // pass answer to the other user in session without changing anything
-                    SignalMessage::SdpAnswer(session_id, recipient_id, answer) => {
-                        let response = SignalMessage::SdpAnswer(session_id, sender_id, answer);
-                        let response = serde_json::to_string(&response).unwrap();
-                        let connections_reader = connections.read().await;
-                        if let Some(recipient_tx) = connections_reader.get(&recipient_id) {
-                            recipient_tx.send(Message::text(response)).unwrap();
-                        } else {
-                            warn!("tried to send offer to non existing user");
-                        }
-                    }
-                    SignalMessage::IceCandidate(session_id, recipient_id, candidate) => {
-                        let response =
-                            SignalMessage::IceCandidate(session_id, sender_id, candidate);
-                        let response = serde_json::to_string(&response).unwrap();
-                        let connections_reader = connections.read().await;
-                        let recipient_tx = connections_reader.get(&recipient_id).unwrap();
-
-                        recipient_tx.send(Message::text(response)).unwrap();
-                    }
-                    _ => {}
+    match request {
+        SignalMessage::SessionJoin(session_id, _) => {
+            let mut sessions_writer = sessions.write().await;
+            let session = sessions_writer
+                .entry(session_id.clone())
+                .or_insert_with(Session::default);
+            let connections_reader = connections.read().await;
+
+            // start connections with all already present users
+            for client_id in &session.users {
+                {
+                    let host_tx = connections_reader
+                        .get(&sender_id)
+                        .expect("host not in connections");
+                    let host_response =
+                        SignalMessage::SessionReady(session_id.clone(), *client_id, true);
+                    host_tx.send(host_response);
                 }
             }
-            Err(error) => {
-                error!("An error occurred: {:?}", error);
+            session.users.insert(sender_id);
+        }
+        // pass offer to the other user in session without changing anything
+        SignalMessage::SdpOffer(session_id, recipient_id, offer) => {
+            let response = SignalMessage::SdpOffer(session_id, sender_id, offer);
+            let connections_reader = connections.read().await;
+            if let Some(recipient_tx) = connections_reader.get(&recipient_id) {
+                recipient_tx.send(response);
+            } else {
+                warn!("tried to send offer to non existing user");
+            }
+        }
+        // pass answer to the other user in session without changing anything
+        SignalMessage::SdpAnswer(session_id, recipient_id, answer) => {
+            let response = SignalMessage::SdpAnswer(session_id, sender_id, answer);
+            let connections_reader = connections.read().await;
+            if let Some(recipient_tx) = connections_reader.get(&recipient_id) {
+                recipient_tx.send(response);
+            } else {
+                warn!("tried to send offer to non existing user");
+            }
+        }
+        SignalMessage::IceCandidate(session_id, recipient_id, candidate) => {
+            let response = SignalMessage::IceCandidate(session_id, sender_id, candidate);
+            let connections_reader = connections.read().await;
+            if let Some(recipient_tx) = connections_reader.get(&recipient_id) {
+                recipient_tx.send(response);
+            } else {
+                warn!("tried to send ice candidate to non existing user");
+            }
+        }
+        SignalMessage::SessionLeave(session_id) => {
+            let mut sessions_writer = sessions.write().await;
+            match sessions_writer.get_mut(&session_id) {
+                Some(session) => {
+                    session.users.remove(&sender_id);
+                    // remove session if it's empty
+                    if session.users.is_empty() {
+                        sessions_writer.remove(&session_id);
+                    }
+                }
+                None => warn!("tried to leave non existing session: {:?}", session_id),
             }
         }
+        _ => {}
     }
 }
 
-async fn user_disconnected(user_id: UserId, connections: &Connections, sessions: &Sessions) {
+pub(crate) async fn user_disconnected(
+    user_id: UserId,
+    connections: &Connections,
+    sessions: &Sessions,
+) {
     connections.write().await.remove(&user_id);
 
     let mut session_to_delete = None;
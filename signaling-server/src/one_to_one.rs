@@ -1,211 +1,175 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use futures_util::{SinkExt, StreamExt, TryFutureExt};
-use log::{error, info, warn};
-use tokio::sync::{mpsc, RwLock};
-use tokio_stream::wrappers::UnboundedReceiverStream;
-use warp::ws::{Message, WebSocket};
+use log::{error, warn};
+use tokio::sync::RwLock;
+use warp::ws::WebSocket;
 
-use rusty_games_protocol::one_to_one::SignalMessage;
+use rusty_games_protocol::signal::SignalMessage;
 use rusty_games_protocol::{SessionId, UserId};
 
+use crate::connection::{self, Dialect};
+use crate::user_ids::UserIdGenerator;
+
+pub use crate::connection::Connections;
+
 pub struct Session {
     pub first: Option<UserId>,
     pub second: Option<UserId>,
-    pub offer_received: bool,
 }
 
-pub type Connections = Arc<RwLock<HashMap<UserId, mpsc::UnboundedSender<Message>>>>;
 pub type Sessions = Arc<RwLock<HashMap<SessionId, Session>>>;
 
-static NEXT_USER_ID: AtomicUsize = AtomicUsize::new(1);
-
-pub async fn user_connected(ws: WebSocket, connections: Connections, sessions: Sessions) {
-    let user_id = UserId::new(NEXT_USER_ID.fetch_add(1, Ordering::Relaxed));
-    info!("new user connected: {:?}", user_id);
-
-    let (mut user_ws_tx, mut user_ws_rx) = ws.split();
-
-    let (tx, rx) = mpsc::unbounded_channel();
-    let mut rx = UnboundedReceiverStream::new(rx);
-
-    tokio::task::spawn(async move {
-        while let Some(message) = rx.next().await {
-            user_ws_tx
-                .send(message)
-                .unwrap_or_else(|e| eprintln!("websocket send error: {}", e))
-                .await;
-        }
-    });
-
-    connections.write().await.insert(user_id, tx);
-
-    while let Some(result) = user_ws_rx.next().await {
-        let msg = match result {
-            Ok(msg) => msg,
-            Err(e) => {
-                eprintln!("websocket error (id={:?}): {}", user_id, e);
-                break;
-            }
-        };
+/// Entry point of the legacy `/one-to-one` endpoint
+pub async fn user_connected(
+    ws: WebSocket,
+    connections: Connections,
+    sessions: Sessions,
+    user_ids: UserIdGenerator,
+) {
+    let (user_id, mut user_ws_rx) =
+        connection::register(ws, Dialect::OneToOne, &connections, &user_ids).await;
 
-        user_message(user_id, msg, &connections, &sessions).await;
+    while let Some(message) =
+        connection::next_message(user_id, &mut user_ws_rx, Dialect::OneToOne, &connections).await
+    {
+        user_message(user_id, message, &connections, &sessions).await;
     }
 
     eprintln!("user disconnected: {:?}", user_id);
     user_disconnected(user_id, &connections, &sessions).await;
 }
 
-async fn user_message(
+pub(crate) async fn user_message(
     user_id: UserId,
-    msg: Message,
+    request: SignalMessage,
     connections: &Connections,
     sessions: &Sessions,
 ) {
-    if let Ok(msg) = msg.to_str() {
-        match serde_json::from_str::<SignalMessage>(msg) {
-            Ok(request) => {
-                info!("message received from user {:?}: {:?}", user_id, request);
-                match request {
-                    SignalMessage::SessionJoin(session_id) => {
-                        match sessions.write().await.entry(session_id.clone()) {
-                            // on first user in session - create session object and store connecting user id
-                            Entry::Vacant(entry) => {
-                                entry.insert(Session {
-                                    first: Some(user_id),
-                                    second: None,
-                                    offer_received: false,
-                                });
-                            }
-                            // on second user - add him to existing session and notify users that session is ready
-                            Entry::Occupied(mut entry) => {
-                                entry.get_mut().second = Some(user_id);
-                                let first_response =
-                                    SignalMessage::SessionReady(session_id.clone(), true);
-                                let first_response =
-                                    serde_json::to_string(&first_response).unwrap();
-                                let second_response =
-                                    SignalMessage::SessionReady(session_id, false);
-                                let second_response =
-                                    serde_json::to_string(&second_response).unwrap();
-
-                                let connections_reader = connections.read().await;
-                                if let Some(first_id) = &entry.get().first {
-                                    let first_tx = connections_reader.get(first_id).unwrap();
-                                    first_tx.send(Message::text(first_response)).unwrap();
-                                    let second_tx = connections_reader.get(&user_id).unwrap();
-                                    second_tx.send(Message::text(&second_response)).unwrap();
-                                }
-                            }
-                        }
+    match request {
+        SignalMessage::SessionJoin(session_id, _) => {
+            match sessions.write().await.entry(session_id.clone()) {
+                // on first user in session - create session object and store connecting user id
+                Entry::Vacant(entry) => {
+                    entry.insert(Session {
+                        first: Some(user_id),
+                        second: None,
+                    });
+                }
+                // on second user - add him to existing session and notify users that session is ready
+                Entry::Occupied(mut entry) => {
+                    entry.get_mut().second = Some(user_id);
+
+                    let connections_reader = connections.read().await;
+                    if let Some(first_id) = entry.get().first {
+                        let first_response =
+                            SignalMessage::SessionReady(session_id.clone(), user_id, true);
+                        let second_response =
+                            SignalMessage::SessionReady(session_id, first_id, false);
+
+                        let first_tx = connections_reader.get(&first_id).unwrap();
+                        first_tx.send(first_response);
+                        let second_tx = connections_reader.get(&user_id).unwrap();
+                        second_tx.send(second_response);
                     }
-                    // pass offer to the other user in session without changing anything
-                    SignalMessage::SdpOffer(session_id, offer) => {
-                        match sessions.write().await.get_mut(&session_id) {
-                            Some(session) => {
-                                if session.offer_received {
-                                    warn!("offer already sent by the the peer, ignoring the second offer: {:?}", session_id);
-                                } else {
-                                    session.offer_received = true;
-                                }
-
-                                let recipient = if Some(user_id) == session.first {
-                                    session.second
-                                } else {
-                                    session.first
-                                };
-                                match recipient {
-                                    Some(recipient_id) => {
-                                        let response = SignalMessage::SdpOffer(session_id, offer);
-                                        let response = serde_json::to_string(&response).unwrap();
-                                        let connections_reader = connections.read().await;
-                                        let recipient_tx =
-                                            connections_reader.get(&recipient_id).unwrap();
-
-                                        recipient_tx.send(Message::text(response)).unwrap();
-                                    }
-                                    None => {
-                                        error!("Missing second user in session: {:?}", &session_id);
-                                    }
-                                }
-                            }
-                            None => {
-                                error!("No such session: {:?}", &session_id);
-                            }
-                        }
+                }
+            }
+        }
+        // pass offer to the other user in session without changing anything,
+        // either peer can send more of them to renegotiate the connection
+        SignalMessage::SdpOffer(session_id, _, offer) => {
+            match sessions.read().await.get(&session_id) {
+                Some(session) => match other_user(session, user_id) {
+                    Some(recipient_id) => {
+                        let response = SignalMessage::SdpOffer(session_id, user_id, offer);
+                        let connections_reader = connections.read().await;
+                        let recipient_tx = connections_reader.get(&recipient_id).unwrap();
+
+                        recipient_tx.send(response);
                     }
-                    // pass answer to the other user in session without changing anything
-                    SignalMessage::SdpAnswer(session_id, answer) => {
-                        match sessions.read().await.get(&session_id) {
-                            Some(session) => {
-                                let recipient = if Some(user_id) == session.first {
-                                    session.second
-                                } else {
-                                    session.first
-                                };
-                                match recipient {
-                                    Some(recipient_id) => {
-                                        let response = SignalMessage::SdpAnswer(session_id, answer);
-                                        let response = serde_json::to_string(&response).unwrap();
-                                        let connections_reader = connections.read().await;
-                                        let recipient_tx =
-                                            connections_reader.get(&recipient_id).unwrap();
-
-                                        recipient_tx.send(Message::text(response)).unwrap();
-                                    }
-                                    None => {
-                                        error!("Missing second user in session: {:?}", &session_id);
-                                    }
-                                }
-                            }
-                            None => {
-                                error!("No such session: {:?}", &session_id);
-                            }
-                        }
+                    None => {
+                        error!("Missing second user in session: {:?}", &session_id);
                     }
-                    SignalMessage::IceCandidate(session_id, candidate) => {
-                        match sessions.read().await.get(&session_id) {
-                            Some(session) => {
-                                let recipient = if Some(user_id) == session.first {
-                                    session.second
-                                } else {
-                                    session.first
-                                };
-                                match recipient {
-                                    Some(recipient_id) => {
-                                        let response =
-                                            SignalMessage::IceCandidate(session_id, candidate);
-                                        let response = serde_json::to_string(&response).unwrap();
-                                        let connections_reader = connections.read().await;
-                                        let recipient_tx =
-                                            connections_reader.get(&recipient_id).unwrap();
-
-                                        recipient_tx.send(Message::text(response)).unwrap();
-                                    }
-                                    None => {
-                                        error!("Missing second user in session: {:?}", &session_id);
-                                    }
-                                }
-                            }
-                            None => {
-                                error!("No such session: {:?}", &session_id);
-                            }
-                        }
+                },
+                None => {
+                    error!("No such session: {:?}", &session_id);
+                }
+            }
+        }
+        // pass answer to the other user in session without changing anything
+        SignalMessage::SdpAnswer(session_id, _, answer) => {
+            match sessions.read().await.get(&session_id) {
+                Some(session) => match other_user(session, user_id) {
+                    Some(recipient_id) => {
+                        let response = SignalMessage::SdpAnswer(session_id, user_id, answer);
+                        let connections_reader = connections.read().await;
+                        let recipient_tx = connections_reader.get(&recipient_id).unwrap();
+
+                        recipient_tx.send(response);
                     }
-                    _ => {}
+                    None => {
+                        error!("Missing second user in session: {:?}", &session_id);
+                    }
+                },
+                None => {
+                    error!("No such session: {:?}", &session_id);
                 }
             }
-            Err(error) => {
-                error!("An error occurred: {:?}", error);
+        }
+        SignalMessage::IceCandidate(session_id, _, candidate) => {
+            match sessions.read().await.get(&session_id) {
+                Some(session) => match other_user(session, user_id) {
+                    Some(recipient_id) => {
+                        let response = SignalMessage::IceCandidate(session_id, user_id, candidate);
+                        let connections_reader = connections.read().await;
+                        let recipient_tx = connections_reader.get(&recipient_id).unwrap();
+
+                        recipient_tx.send(response);
+                    }
+                    None => {
+                        error!("Missing second user in session: {:?}", &session_id);
+                    }
+                },
+                None => {
+                    error!("No such session: {:?}", &session_id);
+                }
             }
         }
+        SignalMessage::SessionLeave(session_id) => {
+            let mut sessions_writer = sessions.write().await;
+            match sessions_writer.get_mut(&session_id) {
+                Some(session) => {
+                    if session.first == Some(user_id) {
+                        session.first = None;
+                    } else if session.second == Some(user_id) {
+                        session.second = None;
+                    }
+                    // remove session if it's empty
+                    if session.first.is_none() && session.second.is_none() {
+                        sessions_writer.remove(&session_id);
+                    }
+                }
+                None => warn!("tried to leave non existing session: {:?}", session_id),
+            }
+        }
+        _ => {}
+    }
+}
+
+fn other_user(session: &Session, user_id: UserId) -> Option<UserId> {
+    if Some(user_id) == session.first {
+        session.second
+    } else {
+        session.first
     }
 }
 
-async fn user_disconnected(user_id: UserId, connections: &Connections, sessions: &Sessions) {
+pub(crate) async fn user_disconnected(
+    user_id: UserId,
+    connections: &Connections,
+    sessions: &Sessions,
+) {
     let mut session_to_delete = None;
     for (session_id, session) in sessions.write().await.iter_mut() {
         if session.first == Some(user_id) {
@@ -213,7 +177,7 @@ async fn user_disconnected(user_id: UserId, connections: &Connections, sessions:
         } else if session.second == Some(user_id) {
             session.second = None;
         }
-        if session.first == None && session.second == None {
+        if session.first.is_none() && session.second.is_none() {
             session_to_delete = Some(session_id.clone());
         }
     }
@@ -0,0 +1,251 @@
+//! Embeddable signaling server instance serving all endpoints,
+//! so that it can run inside of an application or test instead of a separate binary.
+
+use std::net::SocketAddr;
+
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use warp::Filter;
+
+use crate::connection::Connections;
+use crate::user_ids::{UserIdFormat, UserIdGenerator};
+use crate::{many_to_many, one_to_many, one_to_one, signal};
+
+/// Signaling server with the unified `/signal` endpoint
+/// as well as the legacy `/one-to-one`, `/one-to-many` and `/many-to-many` ones.
+///
+/// ```no_run
+/// # async fn run() {
+/// use rusty_games_signaling_server::server::SignalingServer;
+///
+/// let server = SignalingServer::builder()
+///     .bind(([127, 0, 0, 1], 0))
+///     .spawn()
+///     .unwrap();
+/// println!("listening on ws://{}/signal", server.local_addr());
+/// server.shutdown().await;
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SignalingServer;
+
+impl SignalingServer {
+    pub fn builder() -> SignalingServerBuilder {
+        SignalingServerBuilder::default()
+    }
+}
+
+/// Configuration of a [SignalingServer] before it starts.
+#[derive(Debug, Clone)]
+pub struct SignalingServerBuilder {
+    address: SocketAddr,
+    user_id_format: UserIdFormat,
+}
+
+impl Default for SignalingServerBuilder {
+    fn default() -> Self {
+        SignalingServerBuilder {
+            address: SocketAddr::from(([127, 0, 0, 1], 9001)),
+            user_id_format: UserIdFormat::default(),
+        }
+    }
+}
+
+impl SignalingServerBuilder {
+    /// Address to listen on, use port 0 to have one assigned by the operating system.
+    /// Defaults to `127.0.0.1:9001`.
+    pub fn bind(mut self, address: impl Into<SocketAddr>) -> Self {
+        self.address = address.into();
+        self
+    }
+
+    /// How [UserId](rusty_games_protocol::UserId)s are assigned to connecting users
+    pub fn user_id_format(mut self, user_id_format: UserIdFormat) -> Self {
+        self.user_id_format = user_id_format;
+        self
+    }
+
+    /// Start serving on the tokio runtime of the caller.
+    /// Fails if the address can't be bound.
+    pub fn spawn(self) -> Result<SignalingServerHandle, warp::Error> {
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let (local_addr, server) = warp::serve(routes(self.user_id_format))
+            .try_bind_with_graceful_shutdown(self.address, async {
+                // dropping the handle without calling shutdown stops the server as well
+                let _ = shutdown_rx.await;
+            })?;
+        let task = tokio::spawn(server);
+
+        Ok(SignalingServerHandle {
+            local_addr,
+            shutdown_tx,
+            task,
+        })
+    }
+}
+
+/// Running [SignalingServer] instance.
+#[derive(Debug)]
+pub struct SignalingServerHandle {
+    local_addr: SocketAddr,
+    shutdown_tx: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl SignalingServerHandle {
+    /// Address the server actually listens on, useful when bound to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting new connections and wait for the server to stop
+    pub async fn shutdown(self) {
+        let _ = self.shutdown_tx.send(());
+        let _ = self.task.await;
+    }
+
+    /// Wait until the server stops, which only happens if it fails
+    pub async fn join(self) {
+        let SignalingServerHandle {
+            shutdown_tx, task, ..
+        } = self;
+        let _ = task.await;
+        // kept alive until now, dropping it earlier would stop the server
+        drop(shutdown_tx);
+    }
+}
+
+fn routes(
+    user_id_format: UserIdFormat,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let user_ids = UserIdGenerator::new(user_id_format);
+    let user_ids = warp::any().map(move || user_ids.clone());
+
+    // all endpoints share connections, so that legacy and new peers can meet in one session
+    let connections = Connections::default();
+    let connections = warp::any().map(move || connections.clone());
+
+    let signal_sessions = signal::Sessions::default();
+    let one_to_one_sessions = signal_sessions.one_to_one.clone();
+    let one_to_many_sessions = signal_sessions.one_to_many.clone();
+    let many_to_many_sessions = signal_sessions.many_to_many.clone();
+
+    let signaling = {
+        let sessions = warp::any().map(move || signal_sessions.clone());
+
+        warp::path("signal")
+            .and(warp::ws())
+            .and(connections.clone())
+            .and(sessions)
+            .and(user_ids.clone())
+            .map(|ws: warp::ws::Ws, connections, sessions, user_ids| {
+                ws.on_upgrade(move |socket| {
+                    signal::user_connected(socket, connections, sessions, user_ids)
+                })
+            })
+    };
+
+    let one_to_one_signaling = {
+        let sessions = warp::any().map(move || one_to_one_sessions.clone());
+
+        warp::path("one-to-one")
+            .and(warp::ws())
+            .and(connections.clone())
+            .and(sessions)
+            .and(user_ids.clone())
+            .map(|ws: warp::ws::Ws, connections, sessions, user_ids| {
+                ws.on_upgrade(move |socket| {
+                    one_to_one::user_connected(socket, connections, sessions, user_ids)
+                })
+            })
+    };
+
+    let one_to_many_signaling = {
+        let sessions = warp::any().map(move || one_to_many_sessions.clone());
+
+        warp::path("one-to-many")
+            .and(warp::ws())
+            .and(connections.clone())
+            .and(sessions)
+            .and(user_ids.clone())
+            .map(|ws: warp::ws::Ws, connections, sessions, user_ids| {
+                ws.on_upgrade(move |socket| {
+                    one_to_many::user_connected(socket, connections, sessions, user_ids)
+                })
+            })
+    };
+
+    let many_to_many_signaling = {
+        let sessions = warp::any().map(move || many_to_many_sessions.clone());
+
+        warp::path("many-to-many")
+            .and(warp::ws())
+            .and(connections)
+            .and(sessions)
+            .and(user_ids)
+            .map(|ws: warp::ws::Ws, connections, sessions, user_ids| {
+                ws.on_upgrade(move |socket| {
+                    many_to_many::user_connected(socket, connections, sessions, user_ids)
+                })
+            })
+    };
+
+    signaling
+        .or(one_to_one_signaling)
+        .or(one_to_many_signaling)
+        .or(many_to_many_signaling)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn binds_ephemeral_port_and_shuts_down() {
+        let server = SignalingServer::builder()
+            .bind(([127, 0, 0, 1], 0))
+            .spawn()
+            .unwrap();
+        let address = server.local_addr();
+        assert_ne!(address.port(), 0);
+        assert!(tokio::net::TcpStream::connect(address).await.is_ok());
+
+        server.shutdown().await;
+        assert!(tokio::net::TcpStream::connect(address).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn legacy_clients_decode_user_ids_with_random_format() {
+        use rusty_games_protocol::SessionId;
+        use serde::Deserialize;
+
+        /// Message of the legacy `/one-to-many` protocol as old clients decode it
+        #[derive(Debug, Deserialize)]
+        enum LegacySignalMessage {
+            SessionReady(SessionId, usize),
+        }
+
+        let routes = routes(UserIdFormat::Random);
+        let mut host = warp::test::ws()
+            .path("/one-to-many")
+            .handshake(routes.clone())
+            .await
+            .unwrap();
+        host.send_text(r#"{"SessionJoin":["legacy-session",true]}"#)
+            .await;
+        let mut client = warp::test::ws()
+            .path("/one-to-many")
+            .handshake(routes)
+            .await
+            .unwrap();
+        client
+            .send_text(r#"{"SessionJoin":["legacy-session",false]}"#)
+            .await;
+
+        let message = host.recv().await.unwrap();
+        let LegacySignalMessage::SessionReady(session_id, client_id) =
+            serde_json::from_str(message.to_str().unwrap()).unwrap();
+        assert_eq!(session_id.as_str(), "legacy-session");
+        assert_ne!(client_id, 0);
+    }
+}
@@ -0,0 +1,141 @@
+use log::{info, warn};
+use warp::ws::WebSocket;
+
+use rusty_games_protocol::signal::{
+    Capability, Encoding, ErrorCode, SignalMessage, Topology, SUPPORTED_PROTOCOL_VERSIONS,
+};
+
+use crate::connection::{self, Connections, Dialect};
+use crate::user_ids::UserIdGenerator;
+use crate::{many_to_many, one_to_many, one_to_one};
+
+/// Capabilities this signaling server supports
+const SERVER_CAPABILITIES: &[Capability] = &[Capability::TrickleIce, Capability::MessagePack];
+
+/// Sessions of all topologies, the `/signal` endpoint serves each of them
+#[derive(Default, Clone)]
+pub struct Sessions {
+    pub one_to_one: one_to_one::Sessions,
+    pub one_to_many: one_to_many::Sessions,
+    pub many_to_many: many_to_many::Sessions,
+}
+
+/// Entry point of the unified `/signal` endpoint.
+/// Topology of the connection is chosen by [SignalMessage::Hello] that must be sent first.
+pub async fn user_connected(
+    ws: WebSocket,
+    connections: Connections,
+    sessions: Sessions,
+    user_ids: UserIdGenerator,
+) {
+    let (user_id, mut user_ws_rx) =
+        connection::register(ws, Dialect::Signal, &connections, &user_ids).await;
+
+    let topology =
+        match connection::next_message(user_id, &mut user_ws_rx, Dialect::Signal, &connections)
+            .await
+        {
+            Some(SignalMessage::Hello {
+                protocol_version,
+                topology,
+                capabilities,
+            }) => {
+                let mut connections_writer = connections.write().await;
+                let user_tx = connections_writer.get_mut(&user_id).unwrap();
+                if SUPPORTED_PROTOCOL_VERSIONS.contains(protocol_version) {
+                    info!(
+                        "user {:?} speaks protocol version {} in {:?} topology",
+                        user_id, protocol_version, topology
+                    );
+                    let capabilities: Vec<_> = capabilities
+                        .into_iter()
+                        .filter(|capability| SERVER_CAPABILITIES.contains(capability))
+                        .collect();
+                    let encoding = Encoding::negotiated(&capabilities);
+                    user_tx.send(SignalMessage::Welcome {
+                        protocol_version,
+                        supported_versions: SUPPORTED_PROTOCOL_VERSIONS,
+                        capabilities,
+                    });
+                    // Welcome itself is still sent as JSON, the peer switches encoding after reading it
+                    user_tx.set_encoding(encoding);
+                    Some(topology)
+                } else {
+                    warn!(
+                        "user {:?} speaks unsupported protocol version {}",
+                        user_id, protocol_version
+                    );
+                    user_tx.send(SignalMessage::Error(
+                        None,
+                        ErrorCode::UnsupportedVersion {
+                            supported: SUPPORTED_PROTOCOL_VERSIONS,
+                        },
+                        format!(
+                            "unsupported protocol version {}, server supports versions {}",
+                            protocol_version, SUPPORTED_PROTOCOL_VERSIONS
+                        ),
+                    ));
+                    user_tx.close();
+                    None
+                }
+            }
+            Some(_) => {
+                let connections_reader = connections.read().await;
+                let user_tx = connections_reader.get(&user_id).unwrap();
+                user_tx.send(SignalMessage::Error(
+                    None,
+                    ErrorCode::HandshakeRequired,
+                    "first message must be a Hello".to_string(),
+                ));
+                user_tx.close();
+                None
+            }
+            None => None,
+        };
+
+    if let Some(topology) = topology {
+        while let Some(message) =
+            connection::next_message(user_id, &mut user_ws_rx, Dialect::Signal, &connections).await
+        {
+            if let SignalMessage::Hello { .. } = message {
+                warn!("user {:?} sent Hello more than once, ignoring it", user_id);
+                continue;
+            }
+            match topology {
+                Topology::OneToOne => {
+                    one_to_one::user_message(user_id, message, &connections, &sessions.one_to_one)
+                        .await
+                }
+                Topology::OneToMany => {
+                    one_to_many::user_message(user_id, message, &connections, &sessions.one_to_many)
+                        .await
+                }
+                Topology::ManyToMany => {
+                    many_to_many::user_message(
+                        user_id,
+                        message,
+                        &connections,
+                        &sessions.many_to_many,
+                    )
+                    .await
+                }
+            }
+        }
+    }
+
+    eprintln!("user disconnected: {:?}", user_id);
+    match topology {
+        Some(Topology::OneToOne) => {
+            one_to_one::user_disconnected(user_id, &connections, &sessions.one_to_one).await
+        }
+        Some(Topology::OneToMany) => {
+            one_to_many::user_disconnected(user_id, &connections, &sessions.one_to_many).await
+        }
+        Some(Topology::ManyToMany) => {
+            many_to_many::user_disconnected(user_id, &connections, &sessions.many_to_many).await
+        }
+        None => {
+            connections.write().await.remove(&user_id);
+        }
+    }
+}
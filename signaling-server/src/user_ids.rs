@@ -0,0 +1,67 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use rusty_games_protocol::UserId;
+
+/// Specifies how [UserId]s are assigned to connecting users.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum UserIdFormat {
+    /// Random 128-bit identifiers, non-guessable and unique across restarts and instances.
+    /// Users of the legacy endpoints get sequential ones anyway, see [UserIdGenerator::next_legacy_id].
+    #[default]
+    Random,
+    /// Numbers from a counter starting at 1, reset on every server start
+    Sequential,
+}
+
+impl FromStr for UserIdFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(UserIdFormat::Random),
+            "sequential" => Ok(UserIdFormat::Sequential),
+            other => Err(format!("unknown user id format: {}", other)),
+        }
+    }
+}
+
+/// Source of [UserId]s shared by all topologies of a single server instance.
+#[derive(Debug, Clone)]
+pub struct UserIdGenerator {
+    format: UserIdFormat,
+    next_sequential: Arc<AtomicU64>,
+}
+
+impl UserIdGenerator {
+    pub fn new(format: UserIdFormat) -> Self {
+        UserIdGenerator {
+            format,
+            next_sequential: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    pub fn next_id(&self) -> UserId {
+        match self.format {
+            UserIdFormat::Random => UserId::new(rand::random()),
+            UserIdFormat::Sequential => self.next_sequential_id(),
+        }
+    }
+
+    /// Number from the sequential counter no matter the format, for users of the legacy endpoints,
+    /// whose clients decode user ids into integers too small for random ones
+    pub fn next_legacy_id(&self) -> UserId {
+        self.next_sequential_id()
+    }
+
+    fn next_sequential_id(&self) -> UserId {
+        UserId::new(self.next_sequential.fetch_add(1, Ordering::Relaxed) as u128)
+    }
+}
+
+impl Default for UserIdGenerator {
+    fn default() -> Self {
+        UserIdGenerator::new(UserIdFormat::default())
+    }
+}
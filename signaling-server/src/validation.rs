@@ -0,0 +1,87 @@
+//! Sanity checks of WebRTC payloads, so that malformed ones are rejected
+//! by the signaling server instead of failing later in the other peer's browser.
+
+use log::debug;
+use rusty_games_protocol::rtc::IceCandidate;
+use rusty_games_protocol::signal::SignalMessage;
+
+pub(crate) fn validate(message: &SignalMessage) -> Result<(), String> {
+    match message {
+        SignalMessage::SdpOffer(_, _, offer) => validate_sdp(&offer.sdp),
+        SignalMessage::SdpAnswer(_, _, answer) => validate_sdp(&answer.sdp),
+        SignalMessage::IceCandidate(_, _, candidate) => validate_ice_candidate(candidate),
+        _ => Ok(()),
+    }
+}
+
+fn validate_sdp(sdp: &str) -> Result<(), String> {
+    // every session description starts with the protocol version line
+    if sdp.starts_with("v=0") {
+        Ok(())
+    } else {
+        Err("session description must start with \"v=0\"".to_string())
+    }
+}
+
+fn validate_ice_candidate(candidate: &IceCandidate) -> Result<(), String> {
+    if candidate.sdp_mid.is_none() && candidate.sdp_m_line_index.is_none() {
+        return Err("ICE candidate requires either sdp_mid or sdp_m_line_index".to_string());
+    }
+    match candidate.parse() {
+        Ok(Some(parsed)) => {
+            debug!("ICE candidate: {:?}", parsed);
+            Ok(())
+        }
+        // an empty candidate signals the end of candidates
+        Ok(None) => Ok(()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rusty_games_protocol::rtc::SdpOffer;
+    use rusty_games_protocol::{SessionId, UserId};
+
+    fn ice_candidate(candidate: &str, sdp_mid: Option<&str>) -> SignalMessage {
+        SignalMessage::IceCandidate(
+            SessionId::new("s".to_string()),
+            UserId::new(1),
+            IceCandidate {
+                candidate: candidate.to_string(),
+                sdp_mid: sdp_mid.map(str::to_string),
+                sdp_m_line_index: None,
+            },
+        )
+    }
+
+    #[test]
+    fn accepts_well_formed_candidates() {
+        let candidate = "candidate:1 1 udp 2122260223 192.168.1.2 54321 typ host";
+        assert!(validate(&ice_candidate(candidate, Some("0"))).is_ok());
+        assert!(validate(&ice_candidate("", Some("0"))).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_candidates() {
+        assert!(validate(&ice_candidate("{\"candidate\":\"\"}", Some("0"))).is_err());
+        assert!(validate(&ice_candidate("candidate:1 1 udp", Some("0"))).is_err());
+        assert!(validate(&ice_candidate("candidate:1", None)).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_sdp() {
+        let offer = |sdp: &str| {
+            SignalMessage::SdpOffer(
+                SessionId::new("s".to_string()),
+                UserId::new(1),
+                SdpOffer {
+                    sdp: sdp.to_string(),
+                },
+            )
+        };
+        assert!(validate(&offer("v=0\r\no=- 1 2 IN IP4 127.0.0.1\r\n")).is_ok());
+        assert!(validate(&offer("not an sdp")).is_err());
+    }
+}